@@ -1,7 +1,17 @@
+use std::io;
 use std::net::ToSocketAddrs;
-use stp::client::{RequestResult, StpClient};
+use std::time::Duration;
+use stp::blocking::client::{RequestResult, StpClient};
+use stp::blocking::discovery;
 use stp::error::ConnectResult;
 
+pub use stp::discovery::Announcement;
+
+/// Finds servers on the local network, waiting `wait` for them to reply.
+pub fn discover(wait: Duration) -> io::Result<Vec<Announcement>> {
+    discovery::discover(&stp::discovery::default_targets(), wait)
+}
+
 pub struct SocketClient {
     stp: StpClient,
 }
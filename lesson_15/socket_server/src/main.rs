@@ -5,12 +5,27 @@ use handler::{Request, RequestHandler};
 use home::Home;
 use std::error::Error;
 use std::{fs, thread};
-use stp::server::{StpConnection, StpServer};
+use stp::blocking::discovery::Responder;
+use stp::blocking::server::{StpConnection, StpServer};
+use stp::discovery;
 
 fn main() -> Result<(), Box<dyn Error>> {
     let addr =
         fs::read_to_string("settings/addr").unwrap_or_else(|_| String::from("127.0.0.1:55331"));
     let server = StpServer::bind(addr)?;
+    // Answer discovery queries on the same interface, so announced address is reachable.
+    let local = server.local_addr()?;
+    let discovery_addr = (local.ip(), discovery::DEFAULT_PORT);
+    match Responder::bind(discovery_addr, local.port(), "smart home") {
+        Ok(responder) => {
+            thread::spawn(move || {
+                if let Err(e) = responder.serve() {
+                    eprintln!("Discovery stopped: {}", e);
+                }
+            });
+        }
+        Err(e) => eprintln!("Server can't be discovered: {}", e),
+    }
     let home = Home::default();
 
     for connection in server.incoming() {
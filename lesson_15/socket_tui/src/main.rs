@@ -1,7 +1,8 @@
 use socket_client::SocketClient;
 use state::{Main, State};
 use std::error::Error;
-use std::fs;
+use std::time::Duration;
+use std::{fs, io};
 
 mod state;
 
@@ -17,6 +18,39 @@ fn main() -> Result<(), Box<dyn Error>> {
     Ok(())
 }
 
+const DEFAULT_ADDR: &str = "127.0.0.1:55331";
+
+/// Address from settings, otherwise one of the servers found on the local network.
 fn get_server_addr() -> String {
-    fs::read_to_string("settings/addr").unwrap_or_else(|_| String::from("127.0.0.1:55331"))
+    fs::read_to_string("settings/addr").unwrap_or_else(|_| pick_server())
+}
+
+fn pick_server() -> String {
+    let servers = match socket_client::discover(Duration::from_secs(1)) {
+        Ok(servers) => servers,
+        Err(e) => {
+            println!("Can't look for servers: {}", e);
+            return DEFAULT_ADDR.into();
+        }
+    };
+    match servers.as_slice() {
+        [] => return DEFAULT_ADDR.into(),
+        [server] => {
+            println!("Found server: {}", server);
+            return server.addr.to_string();
+        }
+        _ => {}
+    }
+
+    println!("Select server:");
+    for (i, server) in servers.iter().enumerate() {
+        println!("    {}) {}", i + 1, server);
+    }
+    let mut buf = String::new();
+    if io::stdin().read_line(&mut buf).is_err() {
+        return servers[0].addr.to_string();
+    }
+    let selected = buf.trim().parse::<usize>().ok();
+    let server = selected.and_then(|n| servers.get(n.checked_sub(1)?));
+    server.unwrap_or(&servers[0]).addr.to_string()
 }
@@ -1,7 +1,83 @@
-use stp::client::{RequestResult, StpClient};
-use stp::error::ConnectResult;
+/// Finding servers on the local network.
+pub use stp::discovery;
+
+use std::time::Duration;
+use stp::client::StpClient;
+use stp::error::{ConnectError, ConnectResult, RequestError};
+use thiserror::Error;
 use tokio::net::ToSocketAddrs;
 
+const LIST_SEPARATOR: &str = "|||";
+
+/// Server replies to requests naming missing device.
+const NOT_FOUND: [&str; 4] = [
+    "Unknown socket",
+    "Unknown thermo",
+    "Bad socket",
+    "Bad thermo",
+];
+
+pub type ClientResult<T = ()> = Result<T, ClientError>;
+
+#[derive(Debug, Error)]
+pub enum ClientError {
+    /// Connection to server can't be established.
+    #[error(transparent)]
+    Connect(#[from] ConnectError),
+    /// Request wasn't delivered or response wasn't received, connection should be dropped.
+    #[error(transparent)]
+    Request(#[from] RequestError),
+    #[error("{0}")]
+    NotFound(String),
+    #[error("{0}")]
+    AlreadyExists(String),
+    /// Server refused request for other reason, like bad argument or unknown command.
+    #[error("{0}")]
+    Rejected(String),
+}
+
+impl ClientError {
+    /// Whether connection failed, unlike the server refusing request.
+    pub fn is_transport(&self) -> bool {
+        matches!(self, Self::Connect(_) | Self::Request(_))
+    }
+
+    fn from_response(response: String) -> Self {
+        if NOT_FOUND.contains(&response.as_str()) {
+            Self::NotFound(response)
+        } else if response.ends_with(" already exists") {
+            Self::AlreadyExists(response)
+        } else {
+            Self::Rejected(response)
+        }
+    }
+}
+
+/// Response to `fetch_socket`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SocketInfo {
+    pub id: String,
+    pub state: bool,
+    /// Watts drawn when switched on.
+    pub power: u64,
+}
+
+/// Response to `fetch_thermo`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ThermoInfo {
+    pub id: String,
+    pub temperature: i64,
+}
+
+/// Turns missing device into `None`, e.g. when it was deleted by other client.
+pub fn found<T>(result: ClientResult<T>) -> ClientResult<Option<T>> {
+    match result {
+        Ok(value) => Ok(Some(value)),
+        Err(ClientError::NotFound(_)) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
 pub struct Client {
     stp: StpClient,
 }
@@ -12,38 +88,138 @@ impl Client {
         Ok(Self { stp })
     }
 
-    pub async fn fetch_socket(&mut self, socket_id: &str) -> RequestResult {
-        let request = format!("fetch_socket|||{}", socket_id);
-        self.stp.send_request(request).await
+    /// Max time for sending request and receiving response, `None` means to wait forever.
+    pub fn set_request_timeout(&mut self, timeout: Option<Duration>) {
+        self.stp.set_request_timeout(timeout);
+    }
+
+    async fn send(&mut self, request: String) -> ClientResult<String> {
+        Ok(self.stp.send_request(request).await?)
+    }
+
+    /// Passes if server replied exactly `accepted`.
+    async fn expect(&mut self, request: String, accepted: String) -> ClientResult {
+        let response = self.send(request).await?;
+        match response == accepted {
+            true => Ok(()),
+            false => Err(ClientError::from_response(response)),
+        }
+    }
+
+    /// `Socket <id> state is <state>, power is <power>`
+    pub async fn fetch_socket(&mut self, socket_id: &str) -> ClientResult<SocketInfo> {
+        let response = self.send(format!("fetch_socket|||{}", socket_id)).await?;
+        let prefix = format!("Socket {} state is ", socket_id);
+        let parsed = response.strip_prefix(&prefix).and_then(|rest| {
+            let (state, power) = rest.split_once(", power is ")?;
+            Some((state.parse().ok()?, power.parse().ok()?))
+        });
+        match parsed {
+            Some((state, power)) => Ok(SocketInfo {
+                id: socket_id.into(),
+                state,
+                power,
+            }),
+            None => Err(ClientError::from_response(response)),
+        }
     }
 
     pub async fn create_socket(
         &mut self,
         socket_id: &str,
-        power: &str,
-        state: &str,
-    ) -> RequestResult {
+        power: u64,
+        state: bool,
+    ) -> ClientResult {
         let request = format!("create_socket|||{}|||{}|||{}", socket_id, power, state);
-        self.stp.send_request(request).await
+        let accepted = format!("Socket `{}` created", socket_id);
+        self.expect(request, accepted).await
     }
 
-    pub async fn toggle_socket(&mut self, socket_id: &str) -> RequestResult {
+    pub async fn toggle_socket(&mut self, socket_id: &str) -> ClientResult {
         let request = format!("toggle_socket|||{}", socket_id);
-        self.stp.send_request(request).await
+        let accepted = format!("Socket `{}` toggled", socket_id);
+        self.expect(request, accepted).await
     }
 
-    pub async fn fetch_thermo(&mut self, thermo_id: &str) -> RequestResult {
-        let request = format!("fetch_thermo|||{}", thermo_id);
-        self.stp.send_request(request).await
+    /// `Thermo <id> temperature is <temperature>`
+    pub async fn fetch_thermo(&mut self, thermo_id: &str) -> ClientResult<ThermoInfo> {
+        let response = self.send(format!("fetch_thermo|||{}", thermo_id)).await?;
+        let prefix = format!("Thermo {} temperature is ", thermo_id);
+        let temperature = response.strip_prefix(&prefix).and_then(|t| t.parse().ok());
+        match temperature {
+            Some(temperature) => Ok(ThermoInfo {
+                id: thermo_id.into(),
+                temperature,
+            }),
+            None => Err(ClientError::from_response(response)),
+        }
     }
 
-    pub async fn create_thermo(&mut self, thermo_id: &str, temp: &str) -> RequestResult {
+    pub async fn create_thermo(&mut self, thermo_id: &str, temp: i64) -> ClientResult {
         let request = format!("create_thermo|||{}|||{}", thermo_id, temp);
-        self.stp.send_request(request).await
+        let accepted = format!("Thermo `{}` created", thermo_id);
+        self.expect(request, accepted).await
     }
 
-    pub async fn set_thermo(&mut self, thermo_id: &str, temp: &str) -> RequestResult {
+    pub async fn set_thermo(&mut self, thermo_id: &str, temp: i64) -> ClientResult {
         let request = format!("set_thermo|||{}|||{}", thermo_id, temp);
-        self.stp.send_request(request).await
+        let accepted = format!("Thermo `{}` set temp {}", thermo_id, temp);
+        self.expect(request, accepted).await
+    }
+
+    pub async fn list_sockets(&mut self) -> ClientResult<Vec<String>> {
+        self.list("list_sockets").await
+    }
+
+    pub async fn list_thermos(&mut self) -> ClientResult<Vec<String>> {
+        self.list("list_thermos").await
+    }
+
+    /// Ids joined by separator. Listing takes no arguments, so the only refusal is from
+    /// servers that don't know the command.
+    async fn list(&mut self, command: &str) -> ClientResult<Vec<String>> {
+        let response = self.send(command.into()).await?;
+        match response.as_str() {
+            "" => Ok(Vec::new()),
+            "Bad command" => Err(ClientError::Rejected(response)),
+            ids => Ok(ids.split(LIST_SEPARATOR).map(String::from).collect()),
+        }
+    }
+
+    pub async fn delete_socket(&mut self, socket_id: &str) -> ClientResult {
+        let request = format!("delete_socket|||{}", socket_id);
+        let accepted = format!("Socket `{}` deleted", socket_id);
+        self.expect(request, accepted).await
+    }
+
+    pub async fn delete_thermo(&mut self, thermo_id: &str) -> ClientResult {
+        let request = format!("delete_thermo|||{}", thermo_id);
+        let accepted = format!("Thermo `{}` deleted", thermo_id);
+        self.expect(request, accepted).await
+    }
+
+    pub async fn rename_socket(&mut self, socket_id: &str, new_id: &str) -> ClientResult {
+        let request = format!("rename_socket|||{}|||{}", socket_id, new_id);
+        let accepted = format!("Socket `{}` renamed to `{}`", socket_id, new_id);
+        self.expect(request, accepted).await
+    }
+
+    pub async fn rename_thermo(&mut self, thermo_id: &str, new_id: &str) -> ClientResult {
+        let request = format!("rename_thermo|||{}|||{}", thermo_id, new_id);
+        let accepted = format!("Thermo `{}` renamed to `{}`", thermo_id, new_id);
+        self.expect(request, accepted).await
+    }
+
+    /// Every socket and thermo on the server. Devices deleted while fetching are skipped.
+    pub async fn fetch_devices(&mut self) -> ClientResult<(Vec<SocketInfo>, Vec<ThermoInfo>)> {
+        let mut sockets = Vec::new();
+        for id in self.list_sockets().await? {
+            sockets.extend(found(self.fetch_socket(&id).await)?);
+        }
+        let mut thermos = Vec::new();
+        for id in self.list_thermos().await? {
+            thermos.extend(found(self.fetch_thermo(&id).await)?);
+        }
+        Ok((sockets, thermos))
     }
 }
@@ -1,6 +1,9 @@
-use crate::home::Home;
+use crate::home::{Home, HomeError};
 use std::str::Split;
 
+/// Separates ids in response to `list_*` commands.
+pub const LIST_SEPARATOR: &str = "|||";
+
 pub struct Request<'a>(Split<'a, &'a str>);
 
 impl<'a> Request<'a> {
@@ -31,6 +34,12 @@ impl RequestHandler {
             "create_thermo" => self.create_thermo(request),
             "fetch_thermo" => self.fetch_thermo(request),
             "set_thermo" => self.set_thermo(request),
+            "list_sockets" => self.home.socket_ids().join(LIST_SEPARATOR),
+            "list_thermos" => self.home.thermo_ids().join(LIST_SEPARATOR),
+            "delete_socket" => self.delete_socket(request),
+            "delete_thermo" => self.delete_thermo(request),
+            "rename_socket" => self.rename_socket(request),
+            "rename_thermo" => self.rename_thermo(request),
             _ => "Bad command".into(),
         }
     }
@@ -133,6 +142,66 @@ impl RequestHandler {
             None => "Bad thermo".into(),
         }
     }
+
+    fn delete_socket(&mut self, mut request: Request) -> String {
+        let socket_id = request.next();
+        if socket_id.is_empty() {
+            return "Select socket id".into();
+        }
+
+        match self.home.delete_socket(socket_id) {
+            Some(_) => format!("Socket `{}` deleted", socket_id),
+            None => "Unknown socket".into(),
+        }
+    }
+
+    fn delete_thermo(&mut self, mut request: Request) -> String {
+        let thermo_id = request.next();
+        if thermo_id.is_empty() {
+            return "Select thermo id".into();
+        }
+
+        match self.home.delete_thermo(thermo_id) {
+            Some(_) => format!("Thermo `{}` deleted", thermo_id),
+            None => "Unknown thermo".into(),
+        }
+    }
+
+    fn rename_socket(&mut self, mut request: Request) -> String {
+        let socket_id = request.next();
+        if socket_id.is_empty() {
+            return "Select socket id".into();
+        }
+
+        let new_id = request.next();
+        if new_id.is_empty() {
+            return "Provide new socket id".into();
+        }
+
+        match self.home.rename_socket(socket_id, new_id.into()) {
+            Ok(r) => format!("Socket `{}` renamed to `{}`", socket_id, r),
+            Err(HomeError::AlreadyExists) => format!("Socket `{}` already exists", new_id),
+            Err(_) => "Unknown socket".into(),
+        }
+    }
+
+    fn rename_thermo(&mut self, mut request: Request) -> String {
+        let thermo_id = request.next();
+        if thermo_id.is_empty() {
+            return "Select thermo id".into();
+        }
+
+        let new_id = request.next();
+        if new_id.is_empty() {
+            return "Provide new thermo id".into();
+        }
+
+        match self.home.rename_thermo(thermo_id, new_id.into()) {
+            Ok(r) => format!("Thermo `{}` renamed to `{}`", thermo_id, r),
+            Err(HomeError::AlreadyExists) => format!("Thermo `{}` already exists", new_id),
+            Err(_) => "Unknown thermo".into(),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -186,4 +255,39 @@ mod tests {
 
         assert_eq!(fetched, "Thermo thermo_1 temperature is 50");
     }
+
+    #[test]
+    fn list_rename_delete() {
+        let home = Home::default();
+        let mut handler = RequestHandler::new(home);
+
+        for req_str in [
+            "create_socket|||socket_2|||10|||true",
+            "create_socket|||socket_1|||20|||false",
+            "create_thermo|||thermo_1|||20",
+        ] {
+            handler.handle(Request::new(req_str));
+        }
+        let listed = handler.handle(Request::new("list_sockets"));
+        assert_eq!(listed, "socket_1|||socket_2");
+
+        let req = Request::new("rename_socket|||socket_1|||socket_2");
+        assert_eq!(handler.handle(req), "Socket `socket_2` already exists");
+        let req = Request::new("rename_socket|||socket_1|||socket_3");
+        assert_eq!(
+            handler.handle(req),
+            "Socket `socket_1` renamed to `socket_3`"
+        );
+        let req = Request::new("fetch_socket|||socket_3");
+        assert_eq!(
+            handler.handle(req),
+            "Socket socket_3 state is false, power is 20"
+        );
+
+        let req = Request::new("delete_thermo|||thermo_1");
+        assert_eq!(handler.handle(req), "Thermo `thermo_1` deleted");
+        let req = Request::new("delete_thermo|||thermo_1");
+        assert_eq!(handler.handle(req), "Unknown thermo");
+        assert_eq!(handler.handle(Request::new("list_thermos")), "");
+    }
 }
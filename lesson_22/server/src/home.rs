@@ -1,11 +1,19 @@
 use dashmap::mapref::entry::Entry;
 use dashmap::DashMap;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum HomeError {
+    AlreadyExists,
+    NotFound,
+}
 
 #[derive(Default, Clone)]
 pub struct Home {
     sockets: Arc<DashMap<String, Socket>>,
     thermos: Arc<DashMap<String, Thermo>>,
+    /// Held while devices are created or renamed, so a rename never races a create of its ids.
+    ids: Arc<Mutex<()>>,
 }
 
 impl Home {
@@ -17,7 +25,22 @@ impl Home {
         Some(self.thermos.get(&thermo_id)?.info())
     }
 
+    /// Ids of all sockets, sorted.
+    pub fn socket_ids(&self) -> Vec<String> {
+        let mut ids: Vec<_> = self.sockets.iter().map(|s| s.key().clone()).collect();
+        ids.sort();
+        ids
+    }
+
+    /// Ids of all thermos, sorted.
+    pub fn thermo_ids(&self) -> Vec<String> {
+        let mut ids: Vec<_> = self.thermos.iter().map(|t| t.key().clone()).collect();
+        ids.sort();
+        ids
+    }
+
     pub fn create_socket(&self, socket_id: String, power: u64, state: bool) -> Option<String> {
+        let _ids = self.ids.lock().unwrap_or_else(|e| e.into_inner());
         let socket_entry = self.sockets.entry(socket_id.clone());
         match socket_entry {
             Entry::Occupied(_) => None,
@@ -30,6 +53,7 @@ impl Home {
     }
 
     pub fn create_thermo(&self, thermo_id: String, temp: i64) -> Option<String> {
+        let _ids = self.ids.lock().unwrap_or_else(|e| e.into_inner());
         let thermo_entry = self.thermos.entry(thermo_id.clone());
         match thermo_entry {
             Entry::Occupied(_) => None,
@@ -52,6 +76,49 @@ impl Home {
         thermo.set_temp(temp);
         Some(thermo_id.into())
     }
+
+    pub fn delete_socket(&self, socket_id: &str) -> Option<String> {
+        let (id, _) = self.sockets.remove(socket_id)?;
+        Some(id)
+    }
+
+    pub fn delete_thermo(&self, thermo_id: &str) -> Option<String> {
+        let (id, _) = self.thermos.remove(thermo_id)?;
+        Some(id)
+    }
+
+    pub fn rename_socket(&self, socket_id: &str, new_id: String) -> Result<String, HomeError> {
+        let _ids = self.ids.lock().unwrap_or_else(|e| e.into_inner());
+        rename(&self.sockets, socket_id, new_id)
+    }
+
+    pub fn rename_thermo(&self, thermo_id: &str, new_id: String) -> Result<String, HomeError> {
+        let _ids = self.ids.lock().unwrap_or_else(|e| e.into_inner());
+        rename(&self.thermos, thermo_id, new_id)
+    }
+}
+
+trait Named {
+    fn rename(&mut self, name: &str);
+}
+
+/// Moves device to new key. Callers hold `Home::ids`, so no one takes either id meanwhile.
+fn rename<D: Named>(
+    devices: &DashMap<String, D>,
+    id: &str,
+    new_id: String,
+) -> Result<String, HomeError> {
+    if !devices.contains_key(id) {
+        return Err(HomeError::NotFound);
+    }
+    if new_id != id && devices.contains_key(&new_id) {
+        return Err(HomeError::AlreadyExists);
+    }
+
+    let (_, mut device) = devices.remove(id).ok_or(HomeError::NotFound)?;
+    device.rename(&new_id);
+    devices.insert(new_id.clone(), device);
+    Ok(new_id)
 }
 
 pub struct Socket {
@@ -81,6 +148,12 @@ impl Socket {
     }
 }
 
+impl Named for Socket {
+    fn rename(&mut self, name: &str) {
+        self.name = name.into();
+    }
+}
+
 pub struct Thermo {
     name: String,
     temp: i64,
@@ -103,6 +176,12 @@ impl Thermo {
     }
 }
 
+impl Named for Thermo {
+    fn rename(&mut self, name: &str) {
+        self.name = name.into();
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::Home;
@@ -3,6 +3,7 @@ mod home;
 
 use handler::{Request, RequestHandler};
 use home::Home;
+use stp::discovery::{self, Responder};
 use stp::server::{StpConnection, StpServer};
 use tokio::fs;
 
@@ -12,26 +13,46 @@ async fn main() -> anyhow::Result<()> {
         .await
         .unwrap_or_else(|_| String::from("127.0.0.1:55331"));
     let server = StpServer::bind(addr).await?;
+    // Answer discovery queries on the same interface, so announced address is reachable.
+    let local = server.local_addr()?;
+    let discovery_addr = (local.ip(), discovery::DEFAULT_PORT);
+    match Responder::bind(discovery_addr, local.port(), "smart home").await {
+        Ok(responder) => {
+            tokio::spawn(async move {
+                if let Err(e) = responder.serve().await {
+                    eprintln!("Discovery stopped: {}", e);
+                }
+            });
+        }
+        Err(e) => eprintln!("Server can't be discovered: {}", e),
+    }
     let home = Home::default();
 
     loop {
-        let connection = match server.accept().await {
-            Ok(c) => c,
+        let incoming = match server.accept().await {
+            Ok(incoming) => incoming,
             Err(e) => {
-                eprintln!("Can't establish connection: {}", e);
+                eprintln!("Can't accept connection: {}", e);
                 continue;
             }
         };
 
-        let addr = match connection.peer_addr().await {
+        let addr = match incoming.peer_addr() {
             Ok(addr) => addr.to_string(),
             Err(_) => "unknown".into(),
         };
 
-        println!("New client connected: {}", addr);
-
         let home = home.clone();
+        // Handshake is done in own task, so silent client doesn't hold up others.
         tokio::spawn(async move {
+            let connection = match incoming.handshake().await {
+                Ok(connection) => connection,
+                Err(e) => {
+                    eprintln!("Can't establish connection with {}: {}", addr, e);
+                    return;
+                }
+            };
+            println!("New client connected: {}", addr);
             if handle_connection(connection, home).await.is_err() {
                 println!("Client disconnected: {}", addr);
             }
@@ -39,7 +60,7 @@ async fn main() -> anyhow::Result<()> {
     }
 }
 
-async fn handle_connection(connection: StpConnection, home: Home) -> Result<(), anyhow::Error> {
+async fn handle_connection(mut connection: StpConnection, home: Home) -> Result<(), anyhow::Error> {
     let mut handler = RequestHandler::new(home);
     loop {
         let req_str = connection.recv_request().await?;
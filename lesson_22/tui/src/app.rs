@@ -0,0 +1,389 @@
+use client::discovery::Announcement;
+use client::{SocketInfo, ThermoInfo};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Focus {
+    Sockets,
+    Thermos,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Connection {
+    Connecting,
+    Connected,
+    Disconnected(String),
+}
+
+/// Request to the server made by user.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Action {
+    Quit,
+    Refresh,
+    /// Look for servers on the local network.
+    Discover,
+    /// Switch to server with given address.
+    Connect(String),
+    Toggle(String),
+    SetThermo {
+        id: String,
+        temperature: String,
+    },
+    CreateSocket {
+        id: String,
+        power: String,
+    },
+    CreateThermo {
+        id: String,
+        temperature: String,
+    },
+    Rename {
+        focus: Focus,
+        id: String,
+        new_id: String,
+    },
+    Delete {
+        focus: Focus,
+        id: String,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum FormKind {
+    CreateSocket,
+    CreateThermo,
+    SetThermo(String),
+    Rename(Focus, String),
+    Delete(Focus, String),
+}
+
+/// Values entered by user one by one, shown as popup.
+#[derive(Debug)]
+pub struct Form {
+    kind: FormKind,
+    fields: Vec<Field>,
+    current: usize,
+}
+
+#[derive(Debug)]
+struct Field {
+    label: &'static str,
+    value: String,
+}
+
+impl Form {
+    fn new(kind: FormKind, labels: &[&'static str]) -> Self {
+        let fields = labels
+            .iter()
+            .map(|label| Field {
+                label,
+                value: String::new(),
+            })
+            .collect();
+        Self {
+            kind,
+            fields,
+            current: 0,
+        }
+    }
+
+    pub fn title(&self) -> String {
+        match &self.kind {
+            FormKind::CreateSocket => "New socket".into(),
+            FormKind::CreateThermo => "New thermo".into(),
+            FormKind::SetThermo(id) => format!("Set {}", id),
+            FormKind::Rename(_, id) => format!("Rename {}", id),
+            FormKind::Delete(_, id) => format!("Delete {}", id),
+        }
+    }
+
+    /// Label and value of every field entered so far, including current one.
+    pub fn fields(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.fields[..=self.current]
+            .iter()
+            .map(|f| (f.label, f.value.as_str()))
+    }
+
+    fn value(&mut self) -> &mut String {
+        &mut self.fields[self.current].value
+    }
+
+    fn into_action(self) -> Option<Action> {
+        let mut values = self.fields.into_iter().map(|f| f.value);
+        let mut next = || values.next().unwrap_or_default();
+        let action = match self.kind {
+            FormKind::CreateSocket => Action::CreateSocket {
+                id: next(),
+                power: next(),
+            },
+            FormKind::CreateThermo => Action::CreateThermo {
+                id: next(),
+                temperature: next(),
+            },
+            FormKind::SetThermo(id) => Action::SetThermo {
+                id,
+                temperature: next(),
+            },
+            FormKind::Rename(focus, id) => Action::Rename {
+                focus,
+                id,
+                new_id: next(),
+            },
+            FormKind::Delete(focus, id) => match next().as_str() {
+                "y" | "Y" => Action::Delete { focus, id },
+                _ => return None,
+            },
+        };
+        Some(action)
+    }
+}
+
+pub struct App {
+    pub addr: String,
+    pub sockets: Vec<SocketInfo>,
+    pub thermos: Vec<ThermoInfo>,
+    pub focus: Focus,
+    /// Selected socket and thermo.
+    pub selected: (usize, usize),
+    pub form: Option<Form>,
+    /// Discovered servers and selected one, shown as popup.
+    pub servers: Option<(Vec<Announcement>, usize)>,
+    pub connection: Connection,
+    /// Last response of the server.
+    pub message: String,
+}
+
+impl App {
+    pub fn new(addr: String) -> Self {
+        Self {
+            addr,
+            sockets: Vec::new(),
+            thermos: Vec::new(),
+            focus: Focus::Sockets,
+            selected: (0, 0),
+            form: None,
+            servers: None,
+            connection: Connection::Connecting,
+            message: String::new(),
+        }
+    }
+
+    /// Replaces devices with fresh ones, keeping selection in bounds.
+    pub fn update(&mut self, sockets: Vec<SocketInfo>, thermos: Vec<ThermoInfo>) {
+        self.selected.0 = self.selected.0.min(sockets.len().saturating_sub(1));
+        self.selected.1 = self.selected.1.min(thermos.len().saturating_sub(1));
+        self.sockets = sockets;
+        self.thermos = thermos;
+    }
+
+    pub fn selected_socket(&self) -> Option<&SocketInfo> {
+        self.sockets.get(self.selected.0)
+    }
+
+    pub fn selected_thermo(&self) -> Option<&ThermoInfo> {
+        self.thermos.get(self.selected.1)
+    }
+
+    fn selected_id(&self) -> Option<String> {
+        match self.focus {
+            Focus::Sockets => self.selected_socket().map(|s| s.id.clone()),
+            Focus::Thermos => self.selected_thermo().map(|t| t.id.clone()),
+        }
+    }
+
+    pub fn on_key(&mut self, key: KeyEvent) -> Option<Action> {
+        if key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('c') {
+            return Some(Action::Quit);
+        }
+        if let Some(servers) = self.servers.take() {
+            return self.on_servers_key(servers, key);
+        }
+        match self.form.take() {
+            Some(form) => self.on_form_key(form, key),
+            None => self.on_list_key(key),
+        }
+    }
+
+    fn on_servers_key(
+        &mut self,
+        (servers, mut selected): (Vec<Announcement>, usize),
+        key: KeyEvent,
+    ) -> Option<Action> {
+        match key.code {
+            KeyCode::Esc | KeyCode::Char('q') => return None,
+            KeyCode::Up | KeyCode::Char('k') => selected = selected.saturating_sub(1),
+            KeyCode::Down | KeyCode::Char('j') => {
+                selected = (selected + 1).min(servers.len().saturating_sub(1))
+            }
+            KeyCode::Char('c') => return Some(Action::Discover),
+            KeyCode::Enter => {
+                let server = servers.get(selected)?;
+                return Some(Action::Connect(server.addr.to_string()));
+            }
+            _ => {}
+        }
+        self.servers = Some((servers, selected));
+        None
+    }
+
+    fn on_form_key(&mut self, mut form: Form, key: KeyEvent) -> Option<Action> {
+        match key.code {
+            KeyCode::Esc => return None,
+            KeyCode::Enter if form.current + 1 < form.fields.len() => form.current += 1,
+            KeyCode::Enter => return form.into_action(),
+            KeyCode::Backspace => {
+                form.value().pop();
+            }
+            KeyCode::Char(c) => form.value().push(c),
+            _ => {}
+        }
+        self.form = Some(form);
+        None
+    }
+
+    fn on_list_key(&mut self, key: KeyEvent) -> Option<Action> {
+        let (selected, len) = match self.focus {
+            Focus::Sockets => (&mut self.selected.0, self.sockets.len()),
+            Focus::Thermos => (&mut self.selected.1, self.thermos.len()),
+        };
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => return Some(Action::Quit),
+            KeyCode::Char('r') => return Some(Action::Refresh),
+            KeyCode::Char('c') => return Some(Action::Discover),
+            KeyCode::Up | KeyCode::Char('k') => *selected = selected.saturating_sub(1),
+            KeyCode::Down | KeyCode::Char('j') => {
+                *selected = (*selected + 1).min(len.saturating_sub(1))
+            }
+            KeyCode::Tab | KeyCode::Left | KeyCode::Right => {
+                self.focus = match self.focus {
+                    Focus::Sockets => Focus::Thermos,
+                    Focus::Thermos => Focus::Sockets,
+                }
+            }
+            KeyCode::Enter | KeyCode::Char(' ') => return self.activate(),
+            KeyCode::Char('s') => {
+                self.form = Some(Form::new(FormKind::CreateSocket, &["Name", "Power"]))
+            }
+            KeyCode::Char('t') => {
+                let labels = ["Name", "Temperature"];
+                self.form = Some(Form::new(FormKind::CreateThermo, &labels))
+            }
+            KeyCode::Char('n') => {
+                let id = self.selected_id()?;
+                let kind = FormKind::Rename(self.focus, id);
+                self.form = Some(Form::new(kind, &["New name"]))
+            }
+            KeyCode::Char('d') => {
+                let id = self.selected_id()?;
+                let kind = FormKind::Delete(self.focus, id);
+                self.form = Some(Form::new(kind, &["Type y to confirm"]))
+            }
+            _ => {}
+        }
+        None
+    }
+
+    /// Toggles selected socket or asks temperature for selected thermo.
+    fn activate(&mut self) -> Option<Action> {
+        match self.focus {
+            Focus::Sockets => {
+                let socket = self.selected_socket()?;
+                Some(Action::Toggle(socket.id.clone()))
+            }
+            Focus::Thermos => {
+                let kind = FormKind::SetThermo(self.selected_thermo()?.id.clone());
+                self.form = Some(Form::new(kind, &["Temperature"]));
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::app::{Action, App, Focus};
+    use client::discovery::Announcement;
+    use client::{SocketInfo, ThermoInfo};
+    use crossterm::event::{KeyCode, KeyEvent};
+
+    fn press(app: &mut App, keys: &str) -> Option<Action> {
+        let mut action = None;
+        for c in keys.chars() {
+            let code = match c {
+                '\n' => KeyCode::Enter,
+                '\t' => KeyCode::Tab,
+                c => KeyCode::Char(c),
+            };
+            action = app.on_key(KeyEvent::from(code));
+        }
+        action
+    }
+
+    fn app() -> App {
+        let mut app = App::new("127.0.0.1:55331".into());
+        let socket = |id: &str, state, power| SocketInfo {
+            id: id.into(),
+            state,
+            power,
+        };
+        let sockets = vec![socket("kettle", true, 2000), socket("lamp", false, 60)];
+        let thermo = ThermoInfo {
+            id: "fridge".into(),
+            temperature: 4,
+        };
+        app.update(sockets, vec![thermo]);
+        app
+    }
+
+    #[test]
+    fn toggle_selected_socket() {
+        let mut app = app();
+        let action = press(&mut app, "j ");
+        assert_eq!(action, Some(Action::Toggle("lamp".into())));
+
+        // Selection stays in bounds when devices disappear.
+        app.update(Vec::new(), Vec::new());
+        assert_eq!(press(&mut app, "j "), None);
+    }
+
+    #[test]
+    fn forms() {
+        let mut app = app();
+        let action = press(&mut app, "\t\n21\n");
+        let expected = Action::SetThermo {
+            id: "fridge".into(),
+            temperature: "21".into(),
+        };
+        assert_eq!(action, Some(expected));
+
+        let action = press(&mut app, "soven\n3000\n");
+        let expected = Action::CreateSocket {
+            id: "oven".into(),
+            power: "3000".into(),
+        };
+        assert_eq!(action, Some(expected));
+
+        assert_eq!(press(&mut app, "dn\n"), None);
+        assert!(app.form.is_none());
+        let expected = Action::Delete {
+            focus: Focus::Thermos,
+            id: "fridge".into(),
+        };
+        assert_eq!(press(&mut app, "dy\n"), Some(expected));
+    }
+
+    #[test]
+    fn pick_server() {
+        let mut app = app();
+        assert_eq!(press(&mut app, "c"), Some(Action::Discover));
+
+        let found = ["192.168.1.5:55331", "192.168.1.6:55331"].map(|addr| Announcement {
+            addr: addr.parse().unwrap(),
+            name: "home".into(),
+        });
+        app.servers = Some((found.to_vec(), 0));
+        let expected = Action::Connect("192.168.1.6:55331".into());
+        assert_eq!(press(&mut app, "jj\n"), Some(expected));
+        assert!(app.servers.is_none());
+    }
+}
@@ -0,0 +1,216 @@
+//! Text commands of REPL and scripts, like `toggle socket#1`.
+
+use std::fmt;
+use std::str::FromStr;
+
+pub const HELP: &str = "\
+list                                  show all devices
+toggle <socket>                       switch socket on or off
+set <thermo> <temperature>            set thermo temperature
+create socket <id> <watts>            add socket, switched off
+create thermo <id> <temperature>      add thermo
+rename socket|thermo <id> <new id>    rename device
+delete socket|thermo <id>             delete device
+help                                  show this help
+quit                                  exit";
+
+/// Completed as the first word of the line.
+const COMMANDS: &[&str] = &[
+    "list", "toggle", "set", "create", "rename", "delete", "help", "quit",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Kind {
+    Socket,
+    Thermo,
+}
+
+impl Kind {
+    fn parse(word: &str) -> Option<Self> {
+        match word {
+            "socket" => Some(Self::Socket),
+            "thermo" => Some(Self::Thermo),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Kind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Socket => f.write_str("socket"),
+            Self::Thermo => f.write_str("thermo"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    List,
+    Toggle(String),
+    Set {
+        id: String,
+        temperature: i64,
+    },
+    CreateSocket {
+        id: String,
+        power: u64,
+    },
+    CreateThermo {
+        id: String,
+        temperature: i64,
+    },
+    Rename {
+        kind: Kind,
+        id: String,
+        new_id: String,
+    },
+    Delete {
+        kind: Kind,
+        id: String,
+    },
+    Help,
+    Quit,
+}
+
+impl Command {
+    /// Parses single line, `None` for blank lines and `#` comments.
+    pub fn parse(line: &str) -> Result<Option<Self>, String> {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            return Ok(None);
+        }
+        let words: Vec<_> = line.split_whitespace().collect();
+        let kind = |word: &str| Kind::parse(word).ok_or_else(|| usage(words[0]));
+        let command = match (words[0], &words[1..]) {
+            ("list", []) => Self::List,
+            ("toggle", [id]) => Self::Toggle(id.to_string()),
+            ("set", [id, temperature]) => Self::Set {
+                id: id.to_string(),
+                temperature: number("temperature", temperature)?,
+            },
+            ("create", [kind_word, id, value]) => match kind(kind_word)? {
+                Kind::Socket => Self::CreateSocket {
+                    id: id.to_string(),
+                    power: number("power", value)?,
+                },
+                Kind::Thermo => Self::CreateThermo {
+                    id: id.to_string(),
+                    temperature: number("temperature", value)?,
+                },
+            },
+            ("rename", [kind_word, id, new_id]) => Self::Rename {
+                kind: kind(kind_word)?,
+                id: id.to_string(),
+                new_id: new_id.to_string(),
+            },
+            ("delete", [kind_word, id]) => Self::Delete {
+                kind: kind(kind_word)?,
+                id: id.to_string(),
+            },
+            ("help", []) => Self::Help,
+            ("quit" | "exit", []) => Self::Quit,
+            (name, _) if COMMANDS.contains(&name) || name == "exit" => return Err(usage(name)),
+            (name, _) => return Err(format!("unknown command `{}`, try `help`", name)),
+        };
+        Ok(Some(command))
+    }
+}
+
+fn number<T: FromStr>(name: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("{} `{}` is not a number", name, value))
+}
+
+/// Usage line of command from help.
+fn usage(name: &str) -> String {
+    let usage = HELP
+        .lines()
+        .filter_map(|line| line.split("  ").next())
+        .find(|usage| usage.starts_with(name));
+    match usage {
+        Some(usage) => format!("usage: {}", usage),
+        None => format!("usage: {}", name),
+    }
+}
+
+/// Device ids known to the server, used for completion.
+#[derive(Debug, Clone, Default)]
+pub struct Names {
+    pub sockets: Vec<String>,
+    pub thermos: Vec<String>,
+}
+
+/// Completes the last word of `line`, which is the text before cursor.
+///
+/// Returns where the completed word starts and candidates to replace it with.
+pub fn complete(line: &str, names: &Names) -> (usize, Vec<String>) {
+    let start = line
+        .rfind(char::is_whitespace)
+        .map(|i| i + 1)
+        .unwrap_or_default();
+    let (before, word) = line.split_at(start);
+    let before: Vec<_> = before.split_whitespace().collect();
+
+    let kinds = &["socket", "thermo"][..];
+    let candidates: Vec<&str> = match before[..] {
+        [] => COMMANDS.to_vec(),
+        ["toggle"] => names.sockets.iter().map(|s| s.as_str()).collect(),
+        ["set"] => names.thermos.iter().map(|s| s.as_str()).collect(),
+        ["create" | "rename" | "delete"] => kinds.to_vec(),
+        ["rename" | "delete", "socket"] => names.sockets.iter().map(|s| s.as_str()).collect(),
+        ["rename" | "delete", "thermo"] => names.thermos.iter().map(|s| s.as_str()).collect(),
+        _ => Vec::new(),
+    };
+    let matching = candidates
+        .into_iter()
+        .filter(|c| c.starts_with(word))
+        .map(String::from)
+        .collect();
+    (start, matching)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::command::{complete, Command, Names};
+
+    #[test]
+    fn parse() {
+        let command = Command::parse("  toggle socket#1 ").unwrap();
+        assert_eq!(command, Some(Command::Toggle("socket#1".into())));
+        let command = Command::parse("create thermo fridge -18").unwrap();
+        let expected = Command::CreateThermo {
+            id: "fridge".into(),
+            temperature: -18,
+        };
+        assert_eq!(command, Some(expected));
+        assert_eq!(Command::parse("# comment").unwrap(), None);
+
+        let err = Command::parse("toggle").unwrap_err();
+        assert_eq!(err, "usage: toggle <socket>");
+        let err = Command::parse("delete lamp kettle").unwrap_err();
+        assert_eq!(err, "usage: delete socket|thermo <id>");
+        assert!(Command::parse("set fridge cold").is_err());
+        let err = Command::parse("create socket kettle -5").unwrap_err();
+        assert_eq!(err, "power `-5` is not a number");
+        assert!(Command::parse("jump")
+            .unwrap_err()
+            .contains("unknown command"));
+    }
+
+    #[test]
+    fn completion() {
+        let names = Names {
+            sockets: vec!["kettle".into(), "lamp".into()],
+            thermos: vec!["fridge".into()],
+        };
+        assert_eq!(complete("t", &names), (0, vec!["toggle".into()]));
+        assert_eq!(complete("toggle l", &names), (7, vec!["lamp".into()]));
+        assert_eq!(complete("set ", &names), (4, vec!["fridge".into()]));
+        assert_eq!(complete("delete s", &names), (7, vec!["socket".into()]));
+        let (_, all) = complete("rename socket ", &names);
+        assert_eq!(all, ["kettle", "lamp"]);
+        assert!(complete("toggle lamp ", &names).1.is_empty());
+    }
+}
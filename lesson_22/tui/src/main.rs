@@ -1,20 +1,283 @@
-use client::Client;
-use state::{Main, State};
+use app::{Action, App, Connection, Focus};
+use clap::{Parser, Subcommand};
+use client::{discovery, Client, ClientError, SocketInfo, ThermoInfo};
+use crossterm::event::{Event, EventStream, KeyEventKind};
+use futures::StreamExt;
+use ratatui::DefaultTerminal;
 use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::time;
 
-mod state;
+mod app;
+mod command;
+mod repl;
+mod ui;
+
+/// How often devices are fetched from the server.
+const REFRESH_PERIOD: Duration = Duration::from_secs(1);
+/// Keeps UI responsive while server is unreachable.
+const CONNECT_TIMEOUT: Duration = Duration::from_millis(500);
+/// Connection to server that doesn't answer in time is dropped and made again.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(2);
+/// Jobs waiting for server to finish earlier ones, further are refused.
+const QUEUED_JOBS: usize = 4;
+/// How long to wait for servers to reply to discovery query.
+const DISCOVERY_WAIT: Duration = Duration::from_millis(500);
+
+/// Smart home terminal client, full-screen unless other mode is given.
+#[derive(Debug, Parser)]
+struct Args {
+    #[command(subcommand)]
+    mode: Option<Mode>,
+}
+
+#[derive(Debug, Subcommand)]
+enum Mode {
+    /// Enter commands line by line, with history and tab completion
+    Repl,
+    /// Run commands from file, one per line
+    Run {
+        script: PathBuf,
+        /// Skip the rest of script once server refuses a command
+        #[arg(long)]
+        stop_on_error: bool,
+    },
+}
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
+    let args = Args::parse();
     let addr = get_server_addr();
-    let mut client = Client::new(addr).await?;
+    match args.mode {
+        Some(Mode::Repl) => return repl::repl(addr).await,
+        Some(Mode::Run {
+            script,
+            stop_on_error,
+        }) => return repl::run_script(addr, &script, stop_on_error).await,
+        None => {}
+    }
+
+    let mut terminal = ratatui::init();
+    let result = run(&mut terminal, App::new(addr)).await;
+    ratatui::restore();
+    result
+}
+
+async fn run(terminal: &mut DefaultTerminal, mut app: App) -> anyhow::Result<()> {
+    let (jobs, queue) = mpsc::channel(QUEUED_JOBS);
+    let (updates, mut updated) = mpsc::unbounded_channel();
+    tokio::spawn(Server::new(app.addr.clone(), updates).work(queue));
 
-    let mut state: Box<dyn State> = Box::new(Main);
-    while !state.exit() {
-        state = state.update(&mut client).await?;
+    let mut events = EventStream::new();
+    let mut refresh = time::interval(REFRESH_PERIOD);
+    loop {
+        terminal.draw(|frame| ui::draw(frame, &app))?;
+        tokio::select! {
+            _ = refresh.tick() => {
+                // Skipped while server is slow to answer earlier requests.
+                let _ = jobs.try_send(Job::Refresh);
+            }
+            Some(update) = updated.recv() => update.apply(&mut app),
+            event = events.next() => {
+                let key = match event {
+                    Some(Ok(Event::Key(key))) if key.kind == KeyEventKind::Press => key,
+                    Some(Ok(_)) => continue,
+                    Some(Err(e)) => return Err(e.into()),
+                    None => return Ok(()),
+                };
+                let job = match app.on_key(key) {
+                    Some(Action::Quit) => return Ok(()),
+                    Some(Action::Discover) => {
+                        discover(&mut app).await;
+                        continue;
+                    }
+                    Some(Action::Connect(addr)) => {
+                        app.addr = addr.clone();
+                        app.connection = Connection::Connecting;
+                        app.update(Vec::new(), Vec::new());
+                        Job::Connect(addr)
+                    }
+                    Some(Action::Refresh) => Job::Refresh,
+                    Some(action) => Job::Perform(action),
+                    None => continue,
+                };
+                if jobs.try_send(job).is_err() {
+                    app.message = "Server is busy, try again later".into();
+                }
+            }
+        }
     }
+}
+
+/// Work for [`Server`], done in background so UI doesn't wait for network.
+enum Job {
+    Refresh,
+    /// Switch to server with given address.
+    Connect(String),
+    Perform(Action),
+}
+
+/// Result of [`Job`] to show in UI.
+enum Update {
+    Connection(Connection),
+    Devices(Vec<SocketInfo>, Vec<ThermoInfo>),
+    Message(String),
+}
 
-    Ok(())
+impl Update {
+    fn apply(self, app: &mut App) {
+        match self {
+            Update::Connection(connection) => app.connection = connection,
+            Update::Devices(sockets, thermos) => app.update(sockets, thermos),
+            Update::Message(message) => app.message = message,
+        }
+    }
+}
+
+/// Connection to the server, re-established on next request after failure.
+struct Server {
+    addr: String,
+    client: Option<Client>,
+    updates: mpsc::UnboundedSender<Update>,
+}
+
+impl Server {
+    fn new(addr: String, updates: mpsc::UnboundedSender<Update>) -> Self {
+        Self {
+            addr,
+            client: None,
+            updates,
+        }
+    }
+
+    /// Does jobs one by one until UI is closed.
+    async fn work(mut self, mut queue: mpsc::Receiver<Job>) {
+        while let Some(job) = queue.recv().await {
+            match job {
+                Job::Refresh => {}
+                Job::Connect(addr) => {
+                    self.client = None;
+                    self.addr = addr;
+                }
+                Job::Perform(action) => self.perform(action).await,
+            }
+            self.refresh().await;
+        }
+    }
+
+    fn update(&self, update: Update) {
+        // Fails only if UI is closed, then result isn't needed.
+        let _ = self.updates.send(update);
+    }
+
+    async fn client(&mut self) -> Option<&mut Client> {
+        if self.client.is_none() {
+            let connecting = Client::new(self.addr.as_str());
+            match time::timeout(CONNECT_TIMEOUT, connecting).await {
+                Ok(Ok(mut client)) => {
+                    client.set_request_timeout(Some(REQUEST_TIMEOUT));
+                    self.client = Some(client);
+                    self.update(Update::Connection(Connection::Connected));
+                }
+                Ok(Err(e)) => {
+                    self.update(Update::Connection(Connection::Disconnected(e.to_string())));
+                    return None;
+                }
+                Err(_) => {
+                    let timed_out = Connection::Disconnected("connection timed out".into());
+                    self.update(Update::Connection(timed_out));
+                    return None;
+                }
+            }
+        }
+        self.client.as_mut()
+    }
+
+    /// Shows error, connection is dropped only if it failed.
+    fn fail(&mut self, err: ClientError) {
+        if err.is_transport() {
+            self.client = None;
+            self.update(Update::Connection(Connection::Disconnected(
+                err.to_string(),
+            )));
+        } else {
+            self.update(Update::Message(err.to_string()));
+        }
+    }
+
+    async fn refresh(&mut self) {
+        let Some(client) = self.client().await else {
+            return;
+        };
+        match client.fetch_devices().await {
+            Ok((sockets, thermos)) => self.update(Update::Devices(sockets, thermos)),
+            Err(e) => self.fail(e),
+        }
+    }
+
+    async fn perform(&mut self, action: Action) {
+        let Some(client) = self.client().await else {
+            return;
+        };
+        let result = match action {
+            Action::Quit | Action::Refresh | Action::Discover | Action::Connect(_) => return,
+            Action::Toggle(id) => {
+                let result = client.toggle_socket(&id).await;
+                result.map(|_| format!("Socket `{}` toggled", id))
+            }
+            Action::SetThermo { id, temperature } => match temperature.parse() {
+                Ok(temperature) => {
+                    let result = client.set_thermo(&id, temperature).await;
+                    result.map(|_| format!("Thermo `{}` set to {}°", id, temperature))
+                }
+                Err(_) => Ok(format!("Bad temperature `{}`", temperature)),
+            },
+            Action::CreateSocket { id, power } => match power.parse() {
+                Ok(power) => {
+                    let result = client.create_socket(&id, power, false).await;
+                    result.map(|_| format!("Socket `{}` created", id))
+                }
+                Err(_) => Ok(format!("Bad power `{}`", power)),
+            },
+            Action::CreateThermo { id, temperature } => match temperature.parse() {
+                Ok(temperature) => {
+                    let result = client.create_thermo(&id, temperature).await;
+                    result.map(|_| format!("Thermo `{}` created", id))
+                }
+                Err(_) => Ok(format!("Bad temperature `{}`", temperature)),
+            },
+            Action::Rename { focus, id, new_id } => {
+                let result = match focus {
+                    Focus::Sockets => client.rename_socket(&id, &new_id).await,
+                    Focus::Thermos => client.rename_thermo(&id, &new_id).await,
+                };
+                result.map(|_| format!("`{}` renamed to `{}`", id, new_id))
+            }
+            Action::Delete { focus, id } => {
+                let result = match focus {
+                    Focus::Sockets => client.delete_socket(&id).await,
+                    Focus::Thermos => client.delete_thermo(&id).await,
+                };
+                result.map(|_| format!("`{}` deleted", id))
+            }
+        };
+        match result {
+            Ok(message) => self.update(Update::Message(message)),
+            Err(e) => self.fail(e),
+        }
+    }
+}
+
+async fn discover(app: &mut App) {
+    match discovery::discover(&discovery::default_targets(), DISCOVERY_WAIT).await {
+        Ok(servers) => {
+            app.message = format!("Found {} server(s)", servers.len());
+            app.servers = Some((servers, 0));
+        }
+        Err(e) => app.message = format!("Can't look for servers: {}", e),
+    }
 }
 
 fn get_server_addr() -> String {
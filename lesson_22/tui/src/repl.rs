@@ -0,0 +1,222 @@
+//! Line-oriented interface: interactive REPL and batch scripts.
+
+use crate::command::{self, Command, Kind, Names};
+use client::{Client, ClientResult, SocketInfo, ThermoInfo};
+use rustyline::completion::Completer;
+use rustyline::error::ReadlineError;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::history::DefaultHistory;
+use rustyline::validate::Validator;
+use rustyline::{Context, Editor, Helper};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::{fs, io};
+
+const HISTORY: &str = "settings/history";
+const PROMPT: &str = "> ";
+
+/// Completes commands and device names, names are updated after every command.
+#[derive(Clone, Default)]
+struct ReplHelper {
+    names: Arc<Mutex<Names>>,
+}
+
+impl Completer for ReplHelper {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<String>)> {
+        let names = self.names.lock().expect("names lock poisoned");
+        Ok(command::complete(&line[..pos], &names))
+    }
+}
+
+impl Hinter for ReplHelper {
+    type Hint = String;
+}
+
+impl Highlighter for ReplHelper {}
+
+impl Validator for ReplHelper {}
+
+impl Helper for ReplHelper {}
+
+pub async fn repl(addr: String) -> anyhow::Result<()> {
+    let mut client = connect(&addr).await?;
+    println!("Connected to {}, type `help` for commands", addr);
+
+    let helper = ReplHelper::default();
+    let names = helper.names.clone();
+    let mut editor = Editor::<ReplHelper, DefaultHistory>::new()?;
+    editor.set_helper(Some(helper));
+    // History is missing on the first run.
+    let _ = editor.load_history(HISTORY);
+    if let Some(dir) = Path::new(HISTORY).parent() {
+        fs::create_dir_all(dir)?;
+    }
+    refresh_names(&mut client, &names).await?;
+
+    loop {
+        // Editor blocks on terminal input, so it runs outside of async runtime threads.
+        let (returned, line) = tokio::task::spawn_blocking(move || {
+            let line = editor.readline(PROMPT);
+            (editor, line)
+        })
+        .await?;
+        editor = returned;
+
+        let line = match line {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => break,
+            Err(e) => return Err(e.into()),
+        };
+        editor.add_history_entry(line.as_str())?;
+        // Saved right away, so it isn't lost if connection fails later.
+        editor.save_history(HISTORY)?;
+
+        let command = match Command::parse(&line) {
+            Ok(Some(command)) => command,
+            Ok(None) => continue,
+            Err(e) => {
+                println!("{}", e);
+                continue;
+            }
+        };
+        match command {
+            Command::Quit => break,
+            Command::Help => println!("{}", command::HELP),
+            command => match execute(&mut client, command).await {
+                Ok(message) => println!("{}", message),
+                Err(e) if !e.is_transport() => println!("{}", e),
+                Err(e) => return Err(e.into()),
+            },
+        }
+        refresh_names(&mut client, &names).await?;
+    }
+    Ok(())
+}
+
+/// Runs commands from script file, nothing is run if any line is bad.
+///
+/// Fails if server refused any command, with `stop_on_error` the rest of script is skipped then.
+pub async fn run_script(addr: String, path: &Path, stop_on_error: bool) -> anyhow::Result<()> {
+    let script = fs::read_to_string(path)
+        .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", path.display(), e)))?;
+    let mut commands = Vec::new();
+    for (number, line) in script.lines().enumerate() {
+        let command = Command::parse(line)
+            .map_err(|e| anyhow::anyhow!("{}:{}: {}", path.display(), number + 1, e))?;
+        commands.extend(command);
+    }
+
+    let mut client = connect(&addr).await?;
+    let mut refused = 0;
+    for command in commands {
+        match command {
+            Command::Quit => break,
+            Command::Help => println!("{}", command::HELP),
+            command => match execute(&mut client, command).await {
+                Ok(message) => println!("{}", message),
+                Err(e) if !e.is_transport() => {
+                    println!("{}", e);
+                    refused += 1;
+                    if stop_on_error {
+                        break;
+                    }
+                }
+                Err(e) => return Err(e.into()),
+            },
+        }
+    }
+    match refused {
+        0 => Ok(()),
+        refused => Err(anyhow::anyhow!("server refused {} command(s)", refused)),
+    }
+}
+
+async fn connect(addr: &str) -> anyhow::Result<Client> {
+    Client::new(addr)
+        .await
+        .map_err(|e| anyhow::anyhow!("can't connect to {}: {}", addr, e))
+}
+
+/// Updates names to complete, old ones are kept if server refuses to list devices.
+///
+/// Fails only if connection is broken.
+async fn refresh_names(client: &mut Client, names: &Mutex<Names>) -> ClientResult {
+    match fetch_names(client).await {
+        Ok(fetched) => *names.lock().expect("names lock poisoned") = fetched,
+        Err(e) if !e.is_transport() => println!("Can't refresh device names: {}", e),
+        Err(e) => return Err(e),
+    }
+    Ok(())
+}
+
+async fn fetch_names(client: &mut Client) -> ClientResult<Names> {
+    Ok(Names {
+        sockets: client.list_sockets().await?,
+        thermos: client.list_thermos().await?,
+    })
+}
+
+/// Sends command to the server, returns result to show to user.
+async fn execute(client: &mut Client, command: Command) -> ClientResult<String> {
+    match command {
+        Command::List => {
+            let (sockets, thermos) = client.fetch_devices().await?;
+            Ok(list(&sockets, &thermos))
+        }
+        Command::Toggle(id) => {
+            let result = client.toggle_socket(&id).await;
+            result.map(|_| format!("Socket `{}` toggled", id))
+        }
+        Command::Set { id, temperature } => {
+            let result = client.set_thermo(&id, temperature).await;
+            result.map(|_| format!("Thermo `{}` set to {}", id, temperature))
+        }
+        Command::CreateSocket { id, power } => {
+            let result = client.create_socket(&id, power, false).await;
+            result.map(|_| format!("Socket `{}` created", id))
+        }
+        Command::CreateThermo { id, temperature } => {
+            let result = client.create_thermo(&id, temperature).await;
+            result.map(|_| format!("Thermo `{}` created", id))
+        }
+        Command::Rename { kind, id, new_id } => {
+            let result = match kind {
+                Kind::Socket => client.rename_socket(&id, &new_id).await,
+                Kind::Thermo => client.rename_thermo(&id, &new_id).await,
+            };
+            result.map(|_| format!("{} `{}` renamed to `{}`", kind, id, new_id))
+        }
+        Command::Delete { kind, id } => {
+            let result = match kind {
+                Kind::Socket => client.delete_socket(&id).await,
+                Kind::Thermo => client.delete_thermo(&id).await,
+            };
+            result.map(|_| format!("{} `{}` deleted", kind, id))
+        }
+        Command::Help | Command::Quit => unreachable!("handled without server"),
+    }
+}
+
+fn list(sockets: &[SocketInfo], thermos: &[ThermoInfo]) -> String {
+    let sockets = sockets.iter().map(|s| {
+        let state = if s.state { "on" } else { "off" };
+        format!("socket {} {} {} W", s.id, state, s.power)
+    });
+    let thermos = thermos
+        .iter()
+        .map(|t| format!("thermo {} {}", t.id, t.temperature));
+    let lines: Vec<_> = sockets.chain(thermos).collect();
+    match lines.is_empty() {
+        true => "No devices".into(),
+        false => lines.join("\n"),
+    }
+}
@@ -0,0 +1,136 @@
+use crate::app::{App, Connection, Focus};
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Borders, Clear, List, ListItem, ListState, Paragraph};
+use ratatui::Frame;
+
+const HELP: &str = "↑↓ select  Tab switch  Enter toggle/set  s new socket  t new thermo  \
+    n rename  d delete  c servers  q quit";
+
+pub fn draw(frame: &mut Frame, app: &App) {
+    let [main, status, help] = Layout::vertical([
+        Constraint::Min(3),
+        Constraint::Length(1),
+        Constraint::Length(1),
+    ])
+    .areas(frame.area());
+    let [sockets, thermos] =
+        Layout::horizontal([Constraint::Percentage(50), Constraint::Percentage(50)]).areas(main);
+
+    let items: Vec<_> = app
+        .sockets
+        .iter()
+        .map(|s| {
+            let (state, color) = match s.state {
+                true => ("on ", Color::Green),
+                false => ("off", Color::DarkGray),
+            };
+            ListItem::new(Line::from(vec![
+                Span::styled(state, Style::default().fg(color)),
+                Span::raw(format!(" {} ({} W)", s.id, s.power)),
+            ]))
+        })
+        .collect();
+    let focused = app.focus == Focus::Sockets;
+    draw_list(frame, sockets, "Sockets", items, app.selected.0, focused);
+
+    let items: Vec<_> = app
+        .thermos
+        .iter()
+        .map(|t| ListItem::new(format!("{} {}°", t.id, t.temperature)))
+        .collect();
+    let focused = app.focus == Focus::Thermos;
+    draw_list(frame, thermos, "Thermos", items, app.selected.1, focused);
+
+    let (connection, color) = match &app.connection {
+        Connection::Connecting => (" connecting ".to_string(), Color::Yellow),
+        Connection::Connected => (" connected ".to_string(), Color::Green),
+        Connection::Disconnected(e) => (format!(" disconnected: {} ", e), Color::Red),
+    };
+    let connection_style = Style::default().fg(color).add_modifier(Modifier::REVERSED);
+    let status_line = Line::from(vec![
+        Span::styled(connection, connection_style),
+        Span::raw(format!(" {}  {}", app.addr, app.message)),
+    ]);
+    frame.render_widget(Paragraph::new(status_line), status);
+    frame.render_widget(
+        Paragraph::new(HELP).style(Style::default().fg(Color::DarkGray)),
+        help,
+    );
+
+    if let Some(form) = &app.form {
+        let lines: Vec<_> = form
+            .fields()
+            .map(|(label, value)| Line::from(format!("{}: {}", label, value)))
+            .collect();
+        let height = lines.len() as u16 + 2;
+        let area = popup(frame.area(), 40, height);
+        let block = Block::default()
+            .borders(Borders::ALL)
+            .title(format!(" {} (Esc to cancel) ", form.title()));
+        frame.render_widget(Clear, area);
+        frame.render_widget(Paragraph::new(lines).block(block), area);
+    }
+
+    if let Some((servers, selected)) = &app.servers {
+        let items: Vec<_> = match servers.is_empty() {
+            true => vec![ListItem::new("No servers found, c to retry")],
+            false => servers
+                .iter()
+                .map(|s| ListItem::new(s.to_string()))
+                .collect(),
+        };
+        let area = popup(frame.area(), 50, items.len() as u16 + 2);
+        frame.render_widget(Clear, area);
+        draw_list(
+            frame,
+            area,
+            "Servers (Esc to cancel)",
+            items,
+            *selected,
+            !servers.is_empty(),
+        );
+    }
+}
+
+fn draw_list(
+    frame: &mut Frame,
+    area: Rect,
+    title: &str,
+    items: Vec<ListItem>,
+    selected: usize,
+    focused: bool,
+) {
+    let border = match focused {
+        true => Style::default().fg(Color::Cyan),
+        false => Style::default(),
+    };
+    let empty = items.is_empty();
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_style(border)
+                .title(format!(" {} ", title)),
+        )
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+
+    let mut state = ListState::default();
+    if focused && !empty {
+        state.select(Some(selected));
+    }
+    frame.render_stateful_widget(list, area, &mut state);
+}
+
+/// Area of given size in the middle of `area`.
+fn popup(area: Rect, width: u16, height: u16) -> Rect {
+    let width = width.min(area.width);
+    let height = height.min(area.height);
+    Rect {
+        x: area.x + (area.width - width) / 2,
+        y: area.y + (area.height - height) / 2,
+        width,
+        height,
+    }
+}
@@ -0,0 +1,370 @@
+use clap::{Parser, Subcommand};
+use client::{discovery, Client, ClientError, SocketInfo, ThermoInfo};
+use serde::Serialize;
+use std::net::SocketAddr;
+use std::process::ExitCode;
+use std::time::Duration;
+use std::{fmt, io};
+use stp::error::{ConnectError, RequestError};
+use thiserror::Error;
+
+/// Controls smart home devices from scripts.
+///
+/// Exit codes: 0 on success, 1 if server rejected request,
+/// 2 on bad usage, 3 if server can't be reached.
+#[derive(Debug, Parser)]
+#[command(version)]
+struct Args {
+    /// Server address
+    #[arg(long, env = "SMARTHOME_ADDR", default_value = "127.0.0.1:55331")]
+    addr: String,
+
+    /// Token to authenticate with, if server requires one
+    #[arg(long, env = "SMARTHOME_TOKEN", hide_env_values = true)]
+    token: Option<String>,
+
+    /// Print result as JSON
+    #[arg(long, global = true)]
+    json: bool,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Manage sockets
+    #[command(subcommand)]
+    Socket(SocketCommand),
+    /// Manage thermos
+    #[command(subcommand)]
+    Thermo(ThermoCommand),
+    /// List ids of all devices
+    List,
+    /// Find servers on the local network, doesn't connect to `--addr`
+    Discover {
+        /// Milliseconds to wait for servers to reply
+        #[arg(long, default_value_t = 1000)]
+        wait: u64,
+        /// Address to send query to instead of broadcast and local host, may be repeated
+        #[arg(long = "target")]
+        targets: Vec<SocketAddr>,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum SocketCommand {
+    /// Create socket, switched off unless `--on` is given
+    Create {
+        id: String,
+        /// Power drawn when switched on, in watts
+        #[arg(long)]
+        power: u64,
+        #[arg(long)]
+        on: bool,
+    },
+    /// Show socket state
+    Get { id: String },
+    /// Switch socket on or off
+    Toggle { id: String },
+}
+
+#[derive(Debug, Subcommand)]
+enum ThermoCommand {
+    /// Create thermo
+    Create {
+        id: String,
+        #[arg(allow_negative_numbers = true)]
+        temperature: i64,
+    },
+    /// Set thermo temperature
+    Set {
+        id: String,
+        #[arg(allow_negative_numbers = true)]
+        temperature: i64,
+    },
+    /// Show thermo temperature
+    Get { id: String },
+}
+
+#[derive(Debug, Error)]
+enum CliError {
+    #[error("can't connect to server: {0}")]
+    Connect(#[from] ConnectError),
+    #[error("request failed: {0}")]
+    Request(#[from] RequestError),
+    #[error("discovery failed: {0}")]
+    Discover(#[from] io::Error),
+    #[error("{0}")]
+    Rejected(ClientError),
+}
+
+impl CliError {
+    fn exit_code(&self) -> ExitCode {
+        match self {
+            Self::Rejected(_) => ExitCode::from(1),
+            Self::Connect(_) | Self::Request(_) | Self::Discover(_) => ExitCode::from(3),
+        }
+    }
+}
+
+impl From<ClientError> for CliError {
+    fn from(e: ClientError) -> Self {
+        match e {
+            ClientError::Connect(e) => Self::Connect(e),
+            ClientError::Request(e) => Self::Request(e),
+            e => Self::Rejected(e),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(untagged)]
+enum Output {
+    Socket {
+        id: String,
+        on: bool,
+        power: u64,
+        version: u64,
+    },
+    Thermo {
+        id: String,
+        temperature: i64,
+    },
+    Devices {
+        sockets: Vec<String>,
+        thermos: Vec<String>,
+    },
+    Servers {
+        servers: Vec<Server>,
+    },
+    Done {
+        message: String,
+    },
+}
+
+impl fmt::Display for Output {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Socket {
+                id,
+                on,
+                power,
+                version,
+            } => {
+                let state = if *on { "on" } else { "off" };
+                write!(f, "{}: {}, {} W, version {}", id, state, power, version)
+            }
+            Self::Thermo { id, temperature } => write!(f, "{}: {}", id, temperature),
+            Self::Devices { sockets, thermos } => {
+                let sockets = sockets.iter().map(|id| format!("socket {}", id));
+                let thermos = thermos.iter().map(|id| format!("thermo {}", id));
+                let lines: Vec<_> = sockets.chain(thermos).collect();
+                f.write_str(&lines.join("\n"))
+            }
+            Self::Servers { servers } => {
+                let lines: Vec<_> = servers
+                    .iter()
+                    .map(|s| format!("{} {}", s.addr, s.name))
+                    .collect();
+                f.write_str(&lines.join("\n"))
+            }
+            Self::Done { message } => f.write_str(message),
+        }
+    }
+}
+
+impl From<SocketInfo> for Output {
+    fn from(socket: SocketInfo) -> Self {
+        Self::Socket {
+            id: socket.id,
+            on: socket.state,
+            power: socket.power,
+            version: socket.version,
+        }
+    }
+}
+
+impl From<ThermoInfo> for Output {
+    fn from(thermo: ThermoInfo) -> Self {
+        Self::Thermo {
+            id: thermo.id,
+            temperature: thermo.temperature,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+struct Server {
+    addr: String,
+    name: String,
+}
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> ExitCode {
+    let args = Args::parse();
+    let json = args.json;
+    match run(args).await {
+        Ok(output) if json => {
+            let json = serde_json::to_string(&output).expect("output is always serializable");
+            println!("{}", json);
+            ExitCode::SUCCESS
+        }
+        Ok(output) => {
+            println!("{}", output);
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            eprintln!("error: {}", e);
+            e.exit_code()
+        }
+    }
+}
+
+async fn run(args: Args) -> Result<Output, CliError> {
+    if let Command::Discover { wait, targets } = &args.command {
+        return discover(targets, Duration::from_millis(*wait)).await;
+    }
+
+    let mut client = Client::new(args.addr.as_str()).await?;
+    if let Some(token) = &args.token {
+        client.auth(token).await?;
+    }
+
+    match args.command {
+        Command::Socket(SocketCommand::Create { id, power, on }) => {
+            client.create_socket(&id, power, on).await?;
+            done(format!("Socket `{}` created", id))
+        }
+        Command::Socket(SocketCommand::Get { id }) => Ok(client.fetch_socket(&id).await?.into()),
+        Command::Socket(SocketCommand::Toggle { id }) => {
+            client.toggle_socket(&id).await?;
+            done(format!("Socket `{}` toggled", id))
+        }
+        Command::Thermo(ThermoCommand::Create { id, temperature }) => {
+            client.create_thermo(&id, temperature).await?;
+            done(format!("Thermo `{}` created", id))
+        }
+        Command::Thermo(ThermoCommand::Set { id, temperature }) => {
+            client.set_thermo(&id, temperature).await?;
+            done(format!("Thermo `{}` set temp {}", id, temperature))
+        }
+        Command::Thermo(ThermoCommand::Get { id }) => Ok(client.fetch_thermo(&id).await?.into()),
+        Command::List => Ok(Output::Devices {
+            sockets: client.list_sockets().await?,
+            thermos: client.list_thermos().await?,
+        }),
+        Command::Discover { .. } => unreachable!("handled without connection"),
+    }
+}
+
+async fn discover(targets: &[SocketAddr], wait: Duration) -> Result<Output, CliError> {
+    let defaults = discovery::default_targets();
+    let targets = match targets.is_empty() {
+        true => &defaults[..],
+        false => targets,
+    };
+    let servers = discovery::discover(targets, wait)
+        .await?
+        .into_iter()
+        .map(|found| Server {
+            addr: found.addr.to_string(),
+            name: found.name,
+        })
+        .collect();
+    Ok(Output::Servers { servers })
+}
+
+fn done(message: String) -> Result<Output, CliError> {
+    Ok(Output::Done { message })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{run, Args, CliError, Output, Server};
+    use clap::Parser;
+    use client::{ClientError, SocketInfo, ThermoInfo};
+    use mock_server::{MockServer, Reply};
+    use std::process::ExitCode;
+    use stp::error::RequestError;
+
+    #[test]
+    fn responses() {
+        let socket = Output::from(SocketInfo {
+            id: "kettle".into(),
+            state: true,
+            power: 2000,
+            version: 3,
+        });
+        let json = serde_json::to_string(&socket).unwrap();
+        assert_eq!(
+            json,
+            r#"{"id":"kettle","on":true,"power":2000,"version":3}"#
+        );
+        assert_eq!(socket.to_string(), "kettle: on, 2000 W, version 3");
+
+        let thermo = Output::from(ThermoInfo {
+            id: "fridge".into(),
+            temperature: -18,
+        });
+        assert_eq!(thermo.to_string(), "fridge: -18");
+
+        let rejected = CliError::from(ClientError::NotFound("Unknown socket".into()));
+        assert_eq!(rejected.to_string(), "Unknown socket");
+        assert_eq!(rejected.exit_code(), ExitCode::from(1));
+        let failed = CliError::from(ClientError::Request(RequestError::Timeout));
+        assert_eq!(failed.exit_code(), ExitCode::from(3));
+
+        let devices = Output::Devices {
+            sockets: vec!["kettle".into()],
+            thermos: vec!["fridge".into()],
+        };
+        assert_eq!(devices.to_string(), "socket kettle\nthermo fridge");
+
+        let servers = Output::Servers {
+            servers: vec![Server {
+                addr: "192.168.1.5:55331".into(),
+                name: "smart home".into(),
+            }],
+        };
+        assert_eq!(servers.to_string(), "192.168.1.5:55331 smart home");
+    }
+
+    #[test]
+    fn args() {
+        let args = Args::try_parse_from(["cli", "thermo", "set", "fridge", "-18", "--json"]);
+        assert!(args.unwrap().json);
+        let args = Args::try_parse_from(["cli", "socket", "create", "kettle"]);
+        assert!(args.is_err());
+        let args = Args::try_parse_from(["cli", "discover", "--target", "localhost"]);
+        assert!(args.is_err());
+    }
+
+    #[tokio::test]
+    async fn requests() {
+        let server = MockServer::start().await.unwrap();
+        server.reply("auth|||secret", Reply::text("Authorized"));
+        server.reply("socket_version|||kettle", Reply::text("kettle,0"));
+        server.reply("fetch_socket|||kettle", Reply::text("kettle,false,2000"));
+        server.reply("toggle_socket|||lamp", Reply::text("Bad socket"));
+        let addr = server.addr().to_string();
+        let args = |command: &[&str]| {
+            let base = ["cli", "--addr", &addr, "--token", "secret"];
+            Args::try_parse_from(base.iter().chain(command)).unwrap()
+        };
+
+        let socket = run(args(&["socket", "get", "kettle"])).await.unwrap();
+        assert_eq!(socket.to_string(), "kettle: off, 2000 W, version 0");
+        let err = run(args(&["socket", "toggle", "lamp"])).await.unwrap_err();
+        assert_eq!(err.exit_code(), ExitCode::from(1));
+
+        let requests = [
+            "auth|||secret",
+            "socket_version|||kettle",
+            "fetch_socket|||kettle",
+            "auth|||secret",
+            "toggle_socket|||lamp",
+        ];
+        assert_eq!(server.requests(), requests);
+    }
+}
@@ -1,4 +1,15 @@
-use stp::client::{RequestResult, StpClient};
+pub mod pool;
+mod request;
+mod response;
+
+/// Finding servers on the local network.
+pub use stp::discovery;
+
+pub use pool::Pool;
+pub use response::{found, ClientError, ClientResult, Metering, SocketInfo, ThermoInfo};
+
+use std::time::Duration;
+use stp::client::StpClient;
 use stp::error::ConnectResult;
 use tokio::net::ToSocketAddrs;
 
@@ -12,38 +23,217 @@ impl Client {
         Ok(Self { stp })
     }
 
-    pub async fn fetch_socket(&mut self, socket_id: &str) -> RequestResult {
-        let request = format!("fetch_socket|||{}", socket_id);
-        self.stp.send_request(request).await
+    /// Like [`new`](Self::new), but connecting and handshake each take at most `timeout`.
+    pub async fn connect_timeout<Addr: ToSocketAddrs>(
+        addr: Addr,
+        timeout: Duration,
+    ) -> ConnectResult<Self> {
+        let stp = StpClient::connect_timeout(addr, timeout).await?;
+        Ok(Self { stp })
+    }
+
+    /// Whether some request failed or was cancelled midway, such connection shouldn't be
+    /// used anymore.
+    pub fn is_broken(&self) -> bool {
+        self.stp.is_broken()
+    }
+
+    /// Max time for sending request and receiving response, `None` means to wait forever.
+    ///
+    /// Request that times out fails with [`ClientError::Request`] and breaks connection.
+    pub fn set_request_timeout(&mut self, timeout: Option<Duration>) {
+        self.stp.set_request_timeout(timeout);
+    }
+
+    /// Checks that server is alive, returns round trip time.
+    pub async fn ping(&mut self) -> ClientResult<Duration> {
+        Ok(self.stp.ping().await?)
+    }
+
+    async fn send(&mut self, request: String) -> ClientResult<String> {
+        Ok(self.stp.send_request(request).await?)
+    }
+
+    /// Authenticates connection, required if server is configured with token.
+    pub async fn auth(&mut self, token: &str) -> ClientResult {
+        let response = self.send(request::auth(token)).await?;
+        response::expect(response, "Authorized")
+    }
+
+    /// Version is fetched before state, so if socket changes in between, `set_socket_state`
+    /// with it fails instead of acting on stale state.
+    pub async fn fetch_socket(&mut self, socket_id: &str) -> ClientResult<SocketInfo> {
+        let version = self.socket_version(socket_id).await?;
+        let response = self.send(request::fetch_socket(socket_id)).await?;
+        response::socket_info(socket_id, version, response)
+    }
+
+    /// Current socket version, see `set_socket_state`.
+    pub async fn socket_version(&mut self, socket_id: &str) -> ClientResult<u64> {
+        let response = self.send(request::socket_version(socket_id)).await?;
+        response::version(socket_id, response)
     }
 
     pub async fn create_socket(
         &mut self,
         socket_id: &str,
-        power: &str,
-        state: &str,
-    ) -> RequestResult {
-        let request = format!("create_socket|||{}|||{}|||{}", socket_id, power, state);
-        self.stp.send_request(request).await
+        power: u64,
+        state: bool,
+    ) -> ClientResult {
+        let request = request::create_socket(socket_id, power, state);
+        let response = self.send(request).await?;
+        response::expect(response, &format!("Socket `{}` created", socket_id))
+    }
+
+    pub async fn toggle_socket(&mut self, socket_id: &str) -> ClientResult {
+        let response = self.send(request::toggle_socket(socket_id)).await?;
+        response::expect(response, &format!("Socket `{}` toggled", socket_id))
+    }
+
+    /// Sets socket state if it wasn't changed since `version`, see `socket_version`.
+    ///
+    /// Returns new socket version.
+    pub async fn set_socket_state(
+        &mut self,
+        socket_id: &str,
+        state: bool,
+        version: Option<u64>,
+    ) -> ClientResult<u64> {
+        let request = request::set_socket_state(socket_id, state, version);
+        let response = self.send(request).await?;
+        response::socket_version(socket_id, state, response)
     }
 
-    pub async fn toggle_socket(&mut self, socket_id: &str) -> RequestResult {
-        let request = format!("toggle_socket|||{}", socket_id);
-        self.stp.send_request(request).await
+    pub async fn set_power(&mut self, socket_id: &str, power: u64) -> ClientResult {
+        let response = self.send(request::set_power(socket_id, power)).await?;
+        let accepted = format!("Socket `{}` power set to {}", socket_id, power);
+        response::expect(response, &accepted)
     }
 
-    pub async fn fetch_thermo(&mut self, thermo_id: &str) -> RequestResult {
-        let request = format!("fetch_thermo|||{}", thermo_id);
-        self.stp.send_request(request).await
+    pub async fn meter_socket(&mut self, socket_id: &str) -> ClientResult<Metering> {
+        let response = self.send(request::meter_socket(socket_id)).await?;
+        response::metering(response)
     }
 
-    pub async fn create_thermo(&mut self, thermo_id: &str, temp: &str) -> RequestResult {
-        let request = format!("create_thermo|||{}|||{}", thermo_id, temp);
-        self.stp.send_request(request).await
+    pub async fn fetch_thermo(&mut self, thermo_id: &str) -> ClientResult<ThermoInfo> {
+        let response = self.send(request::fetch_thermo(thermo_id)).await?;
+        response::thermo_info(thermo_id, response)
     }
 
-    pub async fn set_thermo(&mut self, thermo_id: &str, temp: &str) -> RequestResult {
-        let request = format!("set_thermo|||{}|||{}", thermo_id, temp);
-        self.stp.send_request(request).await
+    pub async fn create_thermo(&mut self, thermo_id: &str, temp: i64) -> ClientResult {
+        let response = self.send(request::create_thermo(thermo_id, temp)).await?;
+        response::expect(response, &format!("Thermo `{}` created", thermo_id))
+    }
+
+    pub async fn set_thermo(&mut self, thermo_id: &str, temp: i64) -> ClientResult {
+        let response = self.send(request::set_thermo(thermo_id, temp)).await?;
+        let accepted = format!("Thermo `{}` set temp {}", thermo_id, temp);
+        response::expect(response, &accepted)
+    }
+
+    pub async fn list_sockets(&mut self) -> ClientResult<Vec<String>> {
+        let response = self.send(request::list_sockets()).await?;
+        response::list(response, &[])
+    }
+
+    pub async fn delete_socket(&mut self, socket_id: &str) -> ClientResult {
+        let response = self.send(request::delete_socket(socket_id)).await?;
+        response::expect(response, &format!("Socket `{}` deleted", socket_id))
+    }
+
+    pub async fn rename_socket(&mut self, socket_id: &str, new_id: &str) -> ClientResult {
+        let response = self.send(request::rename_socket(socket_id, new_id)).await?;
+        let accepted = format!("Socket `{}` renamed to `{}`", socket_id, new_id);
+        response::expect(response, &accepted)
+    }
+
+    pub async fn list_thermos(&mut self) -> ClientResult<Vec<String>> {
+        let response = self.send(request::list_thermos()).await?;
+        response::list(response, &[])
+    }
+
+    pub async fn delete_thermo(&mut self, thermo_id: &str) -> ClientResult {
+        let response = self.send(request::delete_thermo(thermo_id)).await?;
+        response::expect(response, &format!("Thermo `{}` deleted", thermo_id))
+    }
+
+    pub async fn rename_thermo(&mut self, thermo_id: &str, new_id: &str) -> ClientResult {
+        let response = self.send(request::rename_thermo(thermo_id, new_id)).await?;
+        let accepted = format!("Thermo `{}` renamed to `{}`", thermo_id, new_id);
+        response::expect(response, &accepted)
+    }
+
+    pub async fn list_houses(&mut self) -> ClientResult<Vec<String>> {
+        let response = self.send(request::list_houses()).await?;
+        response::list(response, &[])
+    }
+
+    pub async fn create_house(&mut self, house: &str) -> ClientResult {
+        let response = self.send(request::create_house(house)).await?;
+        response::expect(response, &format!("House `{}` created", house))
+    }
+
+    pub async fn delete_house(&mut self, house: &str) -> ClientResult {
+        let response = self.send(request::delete_house(house)).await?;
+        response::expect(response, &format!("House `{}` deleted", house))
+    }
+
+    pub async fn list_rooms(&mut self, house: &str) -> ClientResult<Vec<String>> {
+        let response = self.send(request::list_rooms(house)).await?;
+        response::list(response, &response::house_refusals(house))
+    }
+
+    pub async fn create_room(&mut self, house: &str, room: &str) -> ClientResult {
+        let response = self.send(request::create_room(house, room)).await?;
+        let accepted = format!("Room `{}` created in house `{}`", room, house);
+        response::expect(response, &accepted)
+    }
+
+    pub async fn delete_room(&mut self, house: &str, room: &str) -> ClientResult {
+        let response = self.send(request::delete_room(house, room)).await?;
+        response::expect(response, &format!("Room `{}` deleted", room))
+    }
+
+    pub async fn place_socket(&mut self, socket_id: &str, house: &str, room: &str) -> ClientResult {
+        let request = request::place_socket(socket_id, house, room);
+        let response = self.send(request).await?;
+        let accepted = format!("Socket `{}` placed in room `{}`", socket_id, room);
+        response::expect(response, &accepted)
+    }
+
+    pub async fn place_thermo(&mut self, thermo_id: &str, house: &str, room: &str) -> ClientResult {
+        let request = request::place_thermo(thermo_id, house, room);
+        let response = self.send(request).await?;
+        let accepted = format!("Thermo `{}` placed in room `{}`", thermo_id, room);
+        response::expect(response, &accepted)
+    }
+
+    /// Every socket and thermo on the server. Devices deleted while fetching are skipped.
+    pub async fn fetch_devices(&mut self) -> ClientResult<(Vec<SocketInfo>, Vec<ThermoInfo>)> {
+        let mut sockets = Vec::new();
+        for id in self.list_sockets().await? {
+            sockets.extend(found(self.fetch_socket(&id).await)?);
+        }
+        let mut thermos = Vec::new();
+        for id in self.list_thermos().await? {
+            thermos.extend(found(self.fetch_thermo(&id).await)?);
+        }
+        Ok((sockets, thermos))
+    }
+
+    /// Human readable description of house rooms and their devices.
+    pub async fn report(&mut self, house: &str) -> ClientResult<String> {
+        let response = self.send(request::report(house)).await?;
+        response::text(response, &response::house_refusals(house))
+    }
+
+    /// Requires server to keep audit log.
+    pub async fn history(
+        &mut self,
+        target: &str,
+        limit: Option<usize>,
+    ) -> ClientResult<Vec<String>> {
+        let response = self.send(request::history(target, limit)).await?;
+        response::history(response)
     }
 }
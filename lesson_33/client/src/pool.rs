@@ -0,0 +1,323 @@
+//! Pool of connections to one server, shared by many tasks.
+
+use crate::{Client, ClientResult};
+use std::ops::{Deref, DerefMut};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+/// Idle connections older than this are pinged before being handed out.
+const DEFAULT_CHECK_AFTER: Duration = Duration::from_secs(10);
+/// Requests not answered in time break their connection, see [`Client::set_request_timeout`].
+const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+/// Connecting to unresponsive server fails after this, see [`Client::connect_timeout`].
+const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Keeps up to `size` connections open, each used by one task at a time.
+///
+/// Connections are opened on demand. Ones that failed a request, or were dropped while
+/// waiting for response, are closed instead of being returned to the pool,
+/// so the next task gets a fresh connection.
+#[derive(Clone)]
+pub struct Pool {
+    addr: String,
+    token: Option<String>,
+    check_after: Duration,
+    request_timeout: Option<Duration>,
+    connect_timeout: Option<Duration>,
+    idle: Arc<Mutex<Vec<Idle>>>,
+    slots: Arc<Semaphore>,
+}
+
+struct Idle {
+    client: Client,
+    since: Instant,
+}
+
+impl Pool {
+    /// Panics if `size` is 0, such pool could never hand out a connection.
+    pub fn new(addr: impl Into<String>, size: usize) -> Self {
+        assert!(size > 0, "pool size must be positive");
+        Self {
+            addr: addr.into(),
+            token: None,
+            check_after: DEFAULT_CHECK_AFTER,
+            request_timeout: Some(DEFAULT_REQUEST_TIMEOUT),
+            connect_timeout: Some(DEFAULT_CONNECT_TIMEOUT),
+            idle: Arc::new(Mutex::new(Vec::with_capacity(size))),
+            slots: Arc::new(Semaphore::new(size)),
+        }
+    }
+
+    /// New connections authenticate with `token`, see [`Client::auth`].
+    pub fn token(mut self, token: impl Into<String>) -> Self {
+        self.token = Some(token.into());
+        self
+    }
+
+    /// Connections idle for longer than `check_after` are pinged before being handed out.
+    pub fn check_after(mut self, check_after: Duration) -> Self {
+        self.check_after = check_after;
+        self
+    }
+
+    /// Max time for request on pooled connection, including health check.
+    ///
+    /// `None` means to wait forever.
+    pub fn request_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.request_timeout = timeout;
+        self
+    }
+
+    /// Max time for connecting and for handshake of new connection.
+    ///
+    /// `None` means to wait forever.
+    pub fn connect_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.connect_timeout = timeout;
+        self
+    }
+
+    /// Waits for free connection, opening new one if there is no idle healthy connection.
+    ///
+    /// Connection returns to the pool when dropped.
+    pub async fn get(&self) -> ClientResult<Pooled> {
+        let permit = self
+            .slots
+            .clone()
+            .acquire_owned()
+            .await
+            .expect("pool slots are never closed");
+
+        while let Some(idle) = self.take_idle() {
+            let mut client = idle.client;
+            if idle.since.elapsed() < self.check_after || client.ping().await.is_ok() {
+                return Ok(self.pooled(client, permit));
+            }
+        }
+
+        let addr = self.addr.as_str();
+        let mut client = match self.connect_timeout {
+            Some(timeout) => Client::connect_timeout(addr, timeout).await?,
+            None => Client::new(addr).await?,
+        };
+        client.set_request_timeout(self.request_timeout);
+        if let Some(token) = &self.token {
+            client.auth(token).await?;
+        }
+        Ok(self.pooled(client, permit))
+    }
+
+    /// Number of open connections not used by any task.
+    pub fn idle(&self) -> usize {
+        self.idle.lock().expect("pool lock poisoned").len()
+    }
+
+    fn take_idle(&self) -> Option<Idle> {
+        self.idle.lock().expect("pool lock poisoned").pop()
+    }
+
+    fn pooled(&self, client: Client, permit: OwnedSemaphorePermit) -> Pooled {
+        Pooled {
+            client: Some(client),
+            idle: self.idle.clone(),
+            _permit: permit,
+        }
+    }
+}
+
+/// Connection taken from [`Pool`], returned to it on drop unless broken.
+pub struct Pooled {
+    client: Option<Client>,
+    idle: Arc<Mutex<Vec<Idle>>>,
+    _permit: OwnedSemaphorePermit,
+}
+
+impl Deref for Pooled {
+    type Target = Client;
+
+    fn deref(&self) -> &Client {
+        self.client.as_ref().expect("client is taken only on drop")
+    }
+}
+
+impl DerefMut for Pooled {
+    fn deref_mut(&mut self) -> &mut Client {
+        self.client.as_mut().expect("client is taken only on drop")
+    }
+}
+
+impl Drop for Pooled {
+    fn drop(&mut self) {
+        let client = self.client.take().expect("client is taken only on drop");
+        if !client.is_broken() {
+            let idle = Idle {
+                client,
+                since: Instant::now(),
+            };
+            self.idle.lock().expect("pool lock poisoned").push(idle);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::pool::Pool;
+    use crate::ClientError;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+    use stp::error::ConnectError;
+    use stp::server::StpServer;
+    use tokio::net::TcpListener;
+    use tokio::time;
+
+    /// Answers every request with empty list, dropping connections after `limit` requests.
+    async fn server(limit: usize) -> (String, Arc<AtomicUsize>) {
+        let server = StpServer::bind("127.0.0.1:0").await.unwrap();
+        let addr = server.local_addr().unwrap().to_string();
+        let accepted = Arc::new(AtomicUsize::new(0));
+        let counter = accepted.clone();
+        tokio::spawn(async move {
+            while let Ok(incoming) = server.accept().await {
+                counter.fetch_add(1, Ordering::SeqCst);
+                tokio::spawn(async move {
+                    let mut connection = incoming.handshake().await.unwrap();
+                    for _ in 0..limit {
+                        if connection.recv_request().await.is_err() {
+                            return;
+                        }
+                        connection.send_response("").await.unwrap();
+                    }
+                });
+            }
+        });
+        (addr, accepted)
+    }
+
+    /// Answers `answered` requests on every connection, then stops reading but keeps it open.
+    async fn stalling_server(answered: usize) -> String {
+        let server = StpServer::bind("127.0.0.1:0").await.unwrap();
+        let addr = server.local_addr().unwrap().to_string();
+        tokio::spawn(async move {
+            while let Ok(incoming) = server.accept().await {
+                tokio::spawn(async move {
+                    let mut connection = incoming.handshake().await.unwrap();
+                    for _ in 0..answered {
+                        connection.recv_request().await.unwrap();
+                        connection.send_response("").await.unwrap();
+                    }
+                    time::sleep(Duration::from_secs(60)).await;
+                });
+            }
+        });
+        addr
+    }
+
+    #[tokio::test]
+    async fn reuses_connections() {
+        let (addr, accepted) = server(usize::MAX).await;
+        let pool = Pool::new(addr, 2);
+
+        let tasks: Vec<_> = (0..8)
+            .map(|_| {
+                let pool = pool.clone();
+                tokio::spawn(async move {
+                    let mut client = pool.get().await.unwrap();
+                    client.list_sockets().await.unwrap();
+                    time::sleep(Duration::from_millis(10)).await;
+                })
+            })
+            .collect();
+        for task in tasks {
+            task.await.unwrap();
+        }
+        assert_eq!(accepted.load(Ordering::SeqCst), 2);
+        assert_eq!(pool.idle(), 2);
+
+        let _first = pool.get().await.unwrap();
+        let _second = pool.get().await.unwrap();
+        let third = time::timeout(Duration::from_millis(50), pool.get()).await;
+        assert!(third.is_err(), "pool is exhausted");
+    }
+
+    #[tokio::test]
+    async fn recycles_broken_connections() {
+        let (addr, accepted) = server(1).await;
+        let pool = Pool::new(addr, 1).check_after(Duration::ZERO);
+
+        let mut client = pool.get().await.unwrap();
+        client.list_sockets().await.unwrap();
+        assert!(client.list_sockets().await.is_err());
+        assert!(client.is_broken());
+        drop(client);
+        assert_eq!(pool.idle(), 0);
+
+        let mut client = pool.get().await.unwrap();
+        client.list_sockets().await.unwrap();
+        drop(client);
+
+        // Server dropped idle connection, health check notices it.
+        let mut client = pool.get().await.unwrap();
+        client.list_sockets().await.unwrap();
+        assert_eq!(accepted.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn drops_connection_of_cancelled_request() {
+        let addr = stalling_server(0).await;
+        let pool = Pool::new(addr, 1).request_timeout(None);
+
+        let mut client = pool.get().await.unwrap();
+        let cancelled = time::timeout(Duration::from_millis(50), client.list_sockets()).await;
+        assert!(cancelled.is_err());
+        assert!(client.is_broken());
+        drop(client);
+        assert_eq!(pool.idle(), 0);
+    }
+
+    #[tokio::test]
+    async fn times_out_connecting() {
+        // Accepts connections, but never answers handshake.
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        tokio::spawn(async move {
+            let mut accepted = Vec::new();
+            while let Ok((stream, _)) = listener.accept().await {
+                accepted.push(stream);
+            }
+        });
+
+        let pool = Pool::new(addr, 1).connect_timeout(Some(Duration::from_millis(50)));
+        let get = time::timeout(Duration::from_secs(5), pool.get()).await;
+        let Err(err) = get.expect("connecting timed out") else {
+            panic!("handshake with silent server succeeded");
+        };
+        assert!(matches!(err, ClientError::Connect(ConnectError::Timeout)));
+    }
+
+    #[test]
+    #[should_panic(expected = "pool size must be positive")]
+    fn empty_pool_is_rejected() {
+        Pool::new("127.0.0.1:55331", 0);
+    }
+
+    #[tokio::test]
+    async fn times_out_requests_and_health_checks() {
+        let addr = stalling_server(1).await;
+        let pool = Pool::new(addr, 1)
+            .check_after(Duration::ZERO)
+            .request_timeout(Some(Duration::from_millis(50)));
+
+        let mut client = pool.get().await.unwrap();
+        client.list_sockets().await.unwrap();
+        drop(client);
+
+        // Ping of stalled idle connection times out, so new one is opened.
+        let get = time::timeout(Duration::from_secs(5), pool.get()).await;
+        let mut client = get.expect("health check timed out").unwrap();
+        client.list_sockets().await.unwrap();
+        let stalled = time::timeout(Duration::from_secs(5), client.list_sockets()).await;
+        assert!(stalled.expect("request timed out").is_err());
+        assert!(client.is_broken());
+    }
+}
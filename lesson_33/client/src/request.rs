@@ -0,0 +1,118 @@
+//! Request strings understood by the server.
+
+pub fn auth(token: &str) -> String {
+    format!("auth|||{}", token)
+}
+
+pub fn fetch_socket(socket_id: &str) -> String {
+    format!("fetch_socket|||{}", socket_id)
+}
+
+pub fn socket_version(socket_id: &str) -> String {
+    format!("socket_version|||{}", socket_id)
+}
+
+pub fn create_socket(socket_id: &str, power: u64, state: bool) -> String {
+    format!("create_socket|||{}|||{}|||{}", socket_id, power, state)
+}
+
+pub fn toggle_socket(socket_id: &str) -> String {
+    format!("toggle_socket|||{}", socket_id)
+}
+
+/// Without `version` state is set unconditionally.
+pub fn set_socket_state(socket_id: &str, state: bool, version: Option<u64>) -> String {
+    match version {
+        Some(version) => format!("set_socket_state|||{}|||{}|||{}", socket_id, state, version),
+        None => format!("set_socket_state|||{}|||{}", socket_id, state),
+    }
+}
+
+pub fn set_power(socket_id: &str, power: u64) -> String {
+    format!("set_power|||{}|||{}", socket_id, power)
+}
+
+pub fn meter_socket(socket_id: &str) -> String {
+    format!("meter_socket|||{}", socket_id)
+}
+
+pub fn fetch_thermo(thermo_id: &str) -> String {
+    format!("fetch_thermo|||{}", thermo_id)
+}
+
+pub fn create_thermo(thermo_id: &str, temp: i64) -> String {
+    format!("create_thermo|||{}|||{}", thermo_id, temp)
+}
+
+pub fn set_thermo(thermo_id: &str, temp: i64) -> String {
+    format!("set_thermo|||{}|||{}", thermo_id, temp)
+}
+
+pub fn list_sockets() -> String {
+    "list_sockets".into()
+}
+
+pub fn delete_socket(socket_id: &str) -> String {
+    format!("delete_socket|||{}", socket_id)
+}
+
+pub fn rename_socket(socket_id: &str, new_id: &str) -> String {
+    format!("rename_socket|||{}|||{}", socket_id, new_id)
+}
+
+pub fn list_thermos() -> String {
+    "list_thermos".into()
+}
+
+pub fn delete_thermo(thermo_id: &str) -> String {
+    format!("delete_thermo|||{}", thermo_id)
+}
+
+pub fn rename_thermo(thermo_id: &str, new_id: &str) -> String {
+    format!("rename_thermo|||{}|||{}", thermo_id, new_id)
+}
+
+pub fn list_houses() -> String {
+    "list_houses".into()
+}
+
+pub fn create_house(house: &str) -> String {
+    format!("create_house|||{}", house)
+}
+
+pub fn delete_house(house: &str) -> String {
+    format!("delete_house|||{}", house)
+}
+
+pub fn list_rooms(house: &str) -> String {
+    format!("list_rooms|||{}", house)
+}
+
+pub fn create_room(house: &str, room: &str) -> String {
+    format!("create_room|||{}|||{}", house, room)
+}
+
+pub fn delete_room(house: &str, room: &str) -> String {
+    format!("delete_room|||{}|||{}", house, room)
+}
+
+pub fn place_socket(socket_id: &str, house: &str, room: &str) -> String {
+    format!("place_socket|||{}|||{}|||{}", socket_id, house, room)
+}
+
+pub fn place_thermo(thermo_id: &str, house: &str, room: &str) -> String {
+    format!("place_thermo|||{}|||{}|||{}", thermo_id, house, room)
+}
+
+pub fn report(house: &str) -> String {
+    format!("report|||{}", house)
+}
+
+/// Latest changes of device or house recorded by server, `limit` defaults to 10.
+/// Server answers with one change per line.
+pub fn history(target: &str, limit: Option<usize>) -> String {
+    match limit {
+        Some(limit) => format!("history|||{}|||{}", target, limit),
+        None => format!("history|||{}", target),
+    }
+}
@@ -0,0 +1,326 @@
+//! Typed server responses.
+//!
+//! Server answers every request with plain text, these functions tell
+//! successful responses from refusals and parse the former.
+
+use std::time::Duration;
+use stp::error::{ConnectError, RequestError};
+use thiserror::Error;
+
+const LIST_SEPARATOR: &str = "|||";
+
+pub type ClientResult<T = ()> = Result<T, ClientError>;
+
+#[derive(Debug, Error)]
+pub enum ClientError {
+    /// Connection to server can't be established.
+    #[error(transparent)]
+    Connect(#[from] ConnectError),
+    /// Request wasn't delivered or response wasn't received, connection should be dropped.
+    #[error(transparent)]
+    Request(#[from] RequestError),
+    /// Device, house or room doesn't exist.
+    #[error("{0}")]
+    NotFound(String),
+    #[error("{0}")]
+    AlreadyExists(String),
+    /// Socket was changed since version it was expected to have.
+    #[error("socket was changed, current version is {current}")]
+    VersionMismatch { current: u64 },
+    /// House or room can't be deleted while it has rooms or devices.
+    #[error("{0}")]
+    NotEmpty(String),
+    #[error("not authorized, send valid token first")]
+    Unauthorized,
+    #[error("throttled, retry in {} ms", .retry_in.as_millis())]
+    Throttled { retry_in: Duration },
+    /// Server refused request for other reason, like bad argument or failed storage.
+    #[error("{0}")]
+    Rejected(String),
+}
+
+impl ClientError {
+    /// Whether connection failed, unlike the server refusing request.
+    pub fn is_transport(&self) -> bool {
+        matches!(self, Self::Connect(_) | Self::Request(_))
+    }
+}
+
+/// Response to `fetch_socket` and `socket_version`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SocketInfo {
+    pub id: String,
+    pub state: bool,
+    /// Watts drawn when switched on.
+    pub power: u64,
+    /// Incremented on every state change, see `set_socket_state`.
+    pub version: u64,
+}
+
+/// Response to `fetch_thermo`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ThermoInfo {
+    pub id: String,
+    pub temperature: i64,
+}
+
+/// Response to `meter_socket`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Metering {
+    /// Power drawn right now, in watts.
+    pub current: u64,
+    /// Energy drawn so far, in watt-hours. Server doesn't persist it, so it restarts from 0
+    /// after server restart.
+    pub energy: f64,
+}
+
+/// Refusals any request may get, besides throttling.
+const COMMON_REFUSALS: [&str; 3] = ["Unauthorized", "Bad command", "Server busy"];
+
+/// `Throttled, retry in <ms> ms`
+fn throttled(response: &str) -> Option<Duration> {
+    let ms = response
+        .strip_prefix("Throttled, retry in ")?
+        .strip_suffix(" ms")?;
+    Some(Duration::from_millis(ms.parse().ok()?))
+}
+
+/// Classifies reply that isn't the one expected, so it's known to be a refusal.
+fn error(response: String) -> ClientError {
+    if response == "Unauthorized" || response == "Bad token" {
+        return ClientError::Unauthorized;
+    }
+    if let Some(retry_in) = throttled(&response) {
+        return ClientError::Throttled { retry_in };
+    }
+    let mismatch = response.split_once(" was changed, current version is ");
+    if let Some(current) = mismatch.and_then(|(_, current)| current.parse().ok()) {
+        return ClientError::VersionMismatch { current };
+    }
+
+    if response.starts_with("Unknown ") || response == "Bad socket" || response == "Bad thermo" {
+        ClientError::NotFound(response)
+    } else if response.ends_with(" already exists") {
+        ClientError::AlreadyExists(response)
+    } else if response.ends_with(" still has rooms") || response.ends_with(" still has devices") {
+        ClientError::NotEmpty(response)
+    } else {
+        ClientError::Rejected(response)
+    }
+}
+
+/// Turns missing device into `None`, e.g. when it was deleted by other client.
+pub fn found<T>(result: ClientResult<T>) -> ClientResult<Option<T>> {
+    match result {
+        Ok(value) => Ok(Some(value)),
+        Err(ClientError::NotFound(_)) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// Passes if server replied exactly `accepted`.
+pub fn expect(response: String, accepted: &str) -> ClientResult {
+    match response == accepted {
+        true => Ok(()),
+        false => Err(error(response)),
+    }
+}
+
+/// Free-form text, like report, unless it's one of `refusals` or common ones.
+///
+/// Refusals are matched exactly, as ids in the text may look like them.
+pub fn text(response: String, refusals: &[String]) -> ClientResult<String> {
+    let refused = COMMON_REFUSALS.contains(&response.as_str())
+        || refusals.contains(&response)
+        || throttled(&response).is_some();
+    match refused {
+        true => Err(error(response)),
+        false => Ok(response),
+    }
+}
+
+/// Refusals of requests reading `house`.
+pub fn house_refusals(house: &str) -> [String; 2] {
+    ["Select house".into(), format!("Unknown house `{}`", house)]
+}
+
+/// Ids or names returned by `list_*` requests, see [`text`] for `refusals`.
+pub fn list(response: String, refusals: &[String]) -> ClientResult<Vec<String>> {
+    let response = text(response, refusals)?;
+    if response.is_empty() {
+        return Ok(Vec::new());
+    }
+    Ok(response.split(LIST_SEPARATOR).map(String::from).collect())
+}
+
+/// `id,state,power`, `version` comes from separate `socket_version` request.
+pub fn socket_info(socket_id: &str, version: u64, response: String) -> ClientResult<SocketInfo> {
+    let parse = |response: &str| {
+        let (state, power) = response
+            .strip_prefix(socket_id)?
+            .strip_prefix(',')?
+            .split_once(',')?;
+        Some(SocketInfo {
+            id: socket_id.into(),
+            state: state.parse().ok()?,
+            power: power.parse().ok()?,
+            version,
+        })
+    };
+    parse(&response).ok_or_else(|| error(response))
+}
+
+/// `id,version`
+pub fn version(socket_id: &str, response: String) -> ClientResult<u64> {
+    let version = response
+        .strip_prefix(socket_id)
+        .and_then(|rest| rest.strip_prefix(','))
+        .and_then(|version| version.parse().ok());
+    version.ok_or_else(|| error(response))
+}
+
+/// `Thermo <id> temperature is <temperature>`
+pub fn thermo_info(thermo_id: &str, response: String) -> ClientResult<ThermoInfo> {
+    let prefix = format!("Thermo {} temperature is ", thermo_id);
+    let temperature = response.strip_prefix(&prefix).and_then(|t| t.parse().ok());
+    match temperature {
+        Some(temperature) => Ok(ThermoInfo {
+            id: thermo_id.into(),
+            temperature,
+        }),
+        None => Err(error(response)),
+    }
+}
+
+/// `id,current,energy`
+pub fn metering(response: String) -> ClientResult<Metering> {
+    let parse = |response: &str| {
+        let mut parts = response.rsplit(',');
+        let energy = parts.next()?.parse().ok()?;
+        let current = parts.next()?.parse().ok()?;
+        Some(Metering { current, energy })
+    };
+    parse(&response).ok_or_else(|| error(response))
+}
+
+/// `Socket `<id>` state is <state>, version <version>`, returns new version.
+pub fn socket_version(socket_id: &str, state: bool, response: String) -> ClientResult<u64> {
+    let prefix = format!("Socket `{}` state is {}, version ", socket_id, state);
+    let version = response.strip_prefix(&prefix).and_then(|v| v.parse().ok());
+    version.ok_or_else(|| error(response))
+}
+
+/// Changes recorded by server, oldest first.
+///
+/// Records start with timestamp, so reply starting like read failure can't be one.
+pub fn history(response: String) -> ClientResult<Vec<String>> {
+    if response.starts_with("Can't read history: ") {
+        return Err(error(response));
+    }
+    let refusals = ["Select device".into(), "Bad history limit".into()];
+    let response = text(response, &refusals)?;
+    Ok(response.lines().map(String::from).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::response::{self, ClientError, Metering, SocketInfo};
+    use std::time::Duration;
+
+    #[test]
+    fn values() {
+        let info = response::socket_info("kettle", 3, "kettle,true,2000".into()).unwrap();
+        let expected = SocketInfo {
+            id: "kettle".into(),
+            state: true,
+            power: 2000,
+            version: 3,
+        };
+        assert_eq!(info, expected);
+        let info = response::socket_info("hall,kettle", 1, "hall,kettle,false,0".into());
+        assert!(!info.unwrap().state);
+        assert_eq!(
+            response::version("hall,kettle", "hall,kettle,4".into()).unwrap(),
+            4
+        );
+
+        let info = response::thermo_info("fridge", "Thermo fridge temperature is -18".into());
+        assert_eq!(info.unwrap().temperature, -18);
+
+        let metering = response::metering("kettle,2000,0.125".into()).unwrap();
+        let expected = Metering {
+            current: 2000,
+            energy: 0.125,
+        };
+        assert_eq!(metering, expected);
+
+        let version =
+            response::socket_version("k", true, "Socket `k` state is true, version 4".into());
+        assert_eq!(version.unwrap(), 4);
+
+        assert!(response::list("".into(), &[]).unwrap().is_empty());
+        assert_eq!(response::list("a|||b".into(), &[]).unwrap(), ["a", "b"]);
+        // Ids looking like refusals are still ids.
+        assert_eq!(
+            response::list("Bad lamp".into(), &[]).unwrap(),
+            ["Bad lamp"]
+        );
+        let listed = response::list("Unknown kettle|||Can't stop".into(), &[]).unwrap();
+        assert_eq!(listed, ["Unknown kettle", "Can't stop"]);
+    }
+
+    #[test]
+    fn errors() {
+        let err = response::socket_info("kettle", 0, "Unknown socket".into()).unwrap_err();
+        assert!(matches!(err, ClientError::NotFound(_)));
+        let err = response::version("kettle", "Unknown socket".into()).unwrap_err();
+        assert!(matches!(err, ClientError::NotFound(_)));
+        let err = response::expect("Bad socket".into(), "Socket `kettle` toggled");
+        assert!(matches!(err, Err(ClientError::NotFound(_))));
+
+        let response = "Socket `kettle` already exists".to_string();
+        let err = response::expect(response, "Socket `kettle` created").unwrap_err();
+        assert_eq!(err.to_string(), "Socket `kettle` already exists");
+        // Refusal mentioning new id isn't taken for success.
+        let response = "Socket `x renamed to y` already exists".to_string();
+        let err = response::expect(response, "Socket `lamp` renamed to `x renamed to y`");
+        assert!(matches!(err, Err(ClientError::AlreadyExists(_))));
+
+        let response = "Socket `kettle` was changed, current version is 7".into();
+        let err = response::socket_version("kettle", false, response).unwrap_err();
+        assert!(matches!(err, ClientError::VersionMismatch { current: 7 }));
+
+        let err = response::list("Throttled, retry in 250 ms".into(), &[]).unwrap_err();
+        let retry_in = Duration::from_millis(250);
+        assert!(matches!(err, ClientError::Throttled { retry_in: r } if r == retry_in));
+        let err = response::list("Unauthorized".into(), &[]).unwrap_err();
+        assert!(matches!(err, ClientError::Unauthorized));
+
+        let err = response::expect("Provide socket power".into(), "Authorized").unwrap_err();
+        assert!(matches!(err, ClientError::Rejected(_)));
+        assert!(!err.is_transport());
+
+        // Replies that aren't data must not be taken for it.
+        let refusals = response::house_refusals("cabin");
+        for refusal in ["Bad command", "Server busy", "Select house"] {
+            let err = response::list(refusal.into(), &refusals).unwrap_err();
+            assert!(matches!(err, ClientError::Rejected(_)), "{}", refusal);
+            let err = response::text(refusal.into(), &refusals).unwrap_err();
+            assert!(matches!(err, ClientError::Rejected(_)), "{}", refusal);
+        }
+        let err = response::text("Unknown house `cabin`".into(), &refusals).unwrap_err();
+        assert!(matches!(err, ClientError::NotFound(_)));
+        let err = response::history("Can't read history: denied".into()).unwrap_err();
+        assert!(matches!(err, ClientError::Rejected(_)));
+        let err = response::history("Select device".into()).unwrap_err();
+        assert!(matches!(err, ClientError::Rejected(_)));
+    }
+
+    #[test]
+    fn found() {
+        assert_eq!(response::found(Ok(1)).unwrap(), Some(1));
+        let gone = response::found::<()>(Err(ClientError::NotFound("Unknown socket".into())));
+        assert_eq!(gone.unwrap(), None);
+        assert!(response::found::<()>(Err(ClientError::Unauthorized)).is_err());
+    }
+}
@@ -0,0 +1,136 @@
+//! Rolling chart of thermo temperature.
+
+use iced::canvas::{self, Cache, Cursor, Frame, Geometry, LineDash, Path, Stroke, Text};
+use iced::{Color, Point, Rectangle};
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// Time span shown by chart, older samples are dropped.
+const WINDOW: Duration = Duration::from_secs(5 * 60);
+/// Degrees left above maximum and below minimum, so flat line isn't drawn at the edge.
+const MIN_MARGIN: f32 = 1.0;
+
+const LINE: Color = Color::from_rgb(0.2, 0.4, 0.8);
+const MIN: Color = Color::from_rgb(0.1, 0.6, 0.7);
+const MAX: Color = Color::from_rgb(0.8, 0.3, 0.2);
+
+pub struct TemperatureChart {
+    pub thermo_id: String,
+    samples: VecDeque<(Instant, f32)>,
+    cache: Cache,
+}
+
+impl TemperatureChart {
+    pub fn new(thermo_id: String) -> Self {
+        Self {
+            thermo_id,
+            samples: VecDeque::new(),
+            cache: Cache::new(),
+        }
+    }
+
+    /// Adds temperature measured at `time`, forgetting ones out of window.
+    pub fn push(&mut self, time: Instant, temperature: f32) {
+        self.samples.push_back((time, temperature));
+        while let Some(&(oldest, _)) = self.samples.front() {
+            if time.saturating_duration_since(oldest) <= WINDOW {
+                break;
+            }
+            self.samples.pop_front();
+        }
+        self.cache.clear();
+    }
+
+    pub fn latest(&self) -> Option<f32> {
+        self.samples.back().map(|&(_, temperature)| temperature)
+    }
+
+    pub fn min(&self) -> Option<f32> {
+        self.temperatures().reduce(f32::min)
+    }
+
+    pub fn max(&self) -> Option<f32> {
+        self.temperatures().reduce(f32::max)
+    }
+
+    fn temperatures(&self) -> impl Iterator<Item = f32> + '_ {
+        self.samples.iter().map(|&(_, temperature)| temperature)
+    }
+
+    fn draw_chart(&self, frame: &mut Frame) {
+        let (last, min, max) = match (self.samples.back(), self.min(), self.max()) {
+            (Some(&(last, _)), Some(min), Some(max)) => (last, min, max),
+            _ => return,
+        };
+        let margin = ((max - min) * 0.1).max(MIN_MARGIN);
+        let (low, high) = (min - margin, max + margin);
+        let (width, height) = (frame.width(), frame.height());
+
+        // Latest sample is at the right edge, window start at the left one.
+        let point = |time: Instant, temperature: f32| {
+            let age = last.saturating_duration_since(time).as_secs_f32();
+            let x = width * (1.0 - age / WINDOW.as_secs_f32());
+            let y = height * (high - temperature) / (high - low);
+            Point::new(x, y)
+        };
+
+        for (temperature, label, color) in [(max, "max", MAX), (min, "min", MIN)] {
+            let y = point(last, temperature).y;
+            let line = Path::line(Point::new(0.0, y), Point::new(width, y));
+            let stroke = Stroke {
+                line_dash: LineDash {
+                    segments: &[4.0, 4.0],
+                    offset: 0,
+                },
+                ..Stroke::default().with_color(color).with_width(1.0)
+            };
+            frame.stroke(&line, stroke);
+            frame.fill_text(Text {
+                content: format!("{} {} °C", label, temperature),
+                position: Point::new(4.0, y - 18.0),
+                color,
+                ..Text::default()
+            });
+        }
+
+        let line = Path::new(|builder| {
+            let mut points = self.samples.iter().map(|&(time, t)| point(time, t));
+            if let Some(first) = points.next() {
+                builder.move_to(first);
+            }
+            points.for_each(|p| builder.line_to(p));
+        });
+        frame.stroke(&line, Stroke::default().with_color(LINE).with_width(2.0));
+    }
+}
+
+impl<Message> canvas::Program<Message> for TemperatureChart {
+    fn draw(&self, bounds: Rectangle, _cursor: Cursor) -> Vec<Geometry> {
+        vec![self
+            .cache
+            .draw(bounds.size(), |frame| self.draw_chart(frame))]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::chart::{TemperatureChart, WINDOW};
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn rolling_window() {
+        let mut chart = TemperatureChart::new("fridge".into());
+        assert_eq!(chart.max(), None);
+
+        let start = Instant::now();
+        chart.push(start, -30.0);
+        chart.push(start + Duration::from_secs(1), 4.0);
+        chart.push(start + Duration::from_secs(2), 6.0);
+        assert_eq!((chart.min(), chart.max()), (Some(-30.0), Some(6.0)));
+
+        // First sample falls out of window.
+        chart.push(start + WINDOW + Duration::from_millis(500), 5.0);
+        assert_eq!((chart.min(), chart.max()), (Some(4.0), Some(6.0)));
+        assert_eq!(chart.latest(), Some(5.0));
+    }
+}
@@ -0,0 +1,231 @@
+//! Cards showing single device with its controls.
+
+use client::{Metering, SocketInfo, ThermoInfo};
+use iced::{
+    button, text_input, Alignment, Button, Column, Element, Length, ProgressBar, Row, Text,
+    TextInput,
+};
+
+/// Temperatures covered by thermo gauge.
+const GAUGE_RANGE: std::ops::RangeInclusive<f32> = -30.0..=50.0;
+
+#[derive(Debug, Clone)]
+pub enum SocketMessage {
+    Toggle,
+    ChangePower(String),
+    SetPower,
+    Delete,
+}
+
+#[derive(Debug, Clone)]
+pub enum ThermoMessage {
+    ChangeTemperature(String),
+    SetTemperature,
+    /// Shows temperature chart of this thermo.
+    Chart,
+    Delete,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Socket {
+    pub id: String,
+    pub state: bool,
+    pub power: u64,
+    pub version: u64,
+    /// Power drawn right now and drawn energy, as metered by server.
+    pub consumption: String,
+}
+
+impl Socket {
+    pub fn new(info: SocketInfo, metering: Metering) -> Self {
+        let consumption = format!(
+            "{} W now, drawn {:.3} Wh",
+            metering.current, metering.energy
+        );
+        Self {
+            id: info.id,
+            state: info.state,
+            power: info.power,
+            version: info.version,
+            consumption,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Thermo {
+    pub id: String,
+    pub temperature: f32,
+}
+
+impl From<ThermoInfo> for Thermo {
+    fn from(info: ThermoInfo) -> Self {
+        Self {
+            id: info.id,
+            temperature: info.temperature as f32,
+        }
+    }
+}
+
+pub struct SocketView {
+    pub socket: Socket,
+    pub new_power: String,
+
+    toggle_button: button::State,
+    power_input: text_input::State,
+    power_button: button::State,
+    delete_button: button::State,
+}
+
+impl SocketView {
+    pub fn new(socket: Socket) -> Self {
+        Self {
+            socket,
+            new_power: String::new(),
+            toggle_button: button::State::new(),
+            power_input: text_input::State::new(),
+            power_button: button::State::new(),
+            delete_button: button::State::new(),
+        }
+    }
+
+    pub fn view(&mut self) -> Element<'_, SocketMessage> {
+        let socket = &self.socket;
+        let state = if socket.state { "on" } else { "off" };
+        let toggle = if socket.state {
+            "Switch off"
+        } else {
+            "Switch on"
+        };
+        Column::new()
+            .spacing(5)
+            .push(
+                Row::new()
+                    .spacing(10)
+                    .align_items(Alignment::Center)
+                    .push(Text::new(&socket.id).size(24).width(Length::Fill))
+                    .push(
+                        Button::new(&mut self.delete_button, Text::new("Delete"))
+                            .on_press(SocketMessage::Delete),
+                    ),
+            )
+            .push(Text::new(format!("{}, {} W", state, socket.power)))
+            .push(Text::new(&socket.consumption).size(16))
+            .push(
+                Row::new()
+                    .spacing(10)
+                    .push(
+                        Button::new(&mut self.toggle_button, Text::new(toggle))
+                            .on_press(SocketMessage::Toggle),
+                    )
+                    .push(
+                        TextInput::new(
+                            &mut self.power_input,
+                            "New power",
+                            &self.new_power,
+                            SocketMessage::ChangePower,
+                        )
+                        .padding(5)
+                        .on_submit(SocketMessage::SetPower),
+                    )
+                    .push(
+                        Button::new(&mut self.power_button, Text::new("Set power"))
+                            .on_press(SocketMessage::SetPower),
+                    ),
+            )
+            .into()
+    }
+}
+
+pub struct ThermoView {
+    pub thermo: Thermo,
+    pub new_temperature: String,
+
+    temperature_input: text_input::State,
+    temperature_button: button::State,
+    chart_button: button::State,
+    delete_button: button::State,
+}
+
+impl ThermoView {
+    pub fn new(thermo: Thermo) -> Self {
+        Self {
+            thermo,
+            new_temperature: String::new(),
+            temperature_input: text_input::State::new(),
+            temperature_button: button::State::new(),
+            chart_button: button::State::new(),
+            delete_button: button::State::new(),
+        }
+    }
+
+    pub fn view(&mut self) -> Element<'_, ThermoMessage> {
+        Column::new()
+            .spacing(5)
+            .push(
+                Row::new()
+                    .spacing(10)
+                    .align_items(Alignment::Center)
+                    .push(Text::new(&self.thermo.id).size(24).width(Length::Fill))
+                    .push(
+                        Button::new(&mut self.chart_button, Text::new("Chart"))
+                            .on_press(ThermoMessage::Chart),
+                    )
+                    .push(
+                        Button::new(&mut self.delete_button, Text::new("Delete"))
+                            .on_press(ThermoMessage::Delete),
+                    ),
+            )
+            .push(Text::new(format!("{} °C", self.thermo.temperature)).size(40))
+            .push(ProgressBar::new(GAUGE_RANGE, self.thermo.temperature).height(Length::Units(10)))
+            .push(
+                Row::new()
+                    .spacing(10)
+                    .push(
+                        TextInput::new(
+                            &mut self.temperature_input,
+                            "New temperature",
+                            &self.new_temperature,
+                            ThermoMessage::ChangeTemperature,
+                        )
+                        .padding(5)
+                        .on_submit(ThermoMessage::SetTemperature),
+                    )
+                    .push(
+                        Button::new(&mut self.temperature_button, Text::new("Set"))
+                            .on_press(ThermoMessage::SetTemperature),
+                    ),
+            )
+            .into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::device::{Socket, Thermo};
+    use client::{Metering, SocketInfo, ThermoInfo};
+
+    #[test]
+    fn from_info() {
+        let info = SocketInfo {
+            id: "kettle".into(),
+            state: true,
+            power: 2000,
+            version: 3,
+        };
+        let metering = Metering {
+            current: 2000,
+            energy: 15.0,
+        };
+        let socket = Socket::new(info, metering);
+        assert!(socket.state);
+        assert_eq!((socket.power, socket.version), (2000, 3));
+        assert_eq!(socket.consumption, "2000 W now, drawn 15.000 Wh");
+
+        let info = ThermoInfo {
+            id: "fridge".into(),
+            temperature: -18,
+        };
+        assert_eq!(Thermo::from(info).temperature, -18.0);
+    }
+}
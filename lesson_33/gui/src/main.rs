@@ -1,170 +1,541 @@
+use chart::TemperatureChart;
+use client::discovery::Announcement;
+use device::{SocketMessage, SocketView, ThermoMessage, ThermoView};
 use iced::{
-    button, text_input, Alignment, Button, Column, Element, Sandbox, Settings, Text, TextInput,
+    button, executor, scrollable, text_input, Alignment, Application, Button, Canvas, Column,
+    Command, Container, Element, Length, Row, Rule, Scrollable, Settings, Subscription, Text,
+    TextInput,
 };
+use server::{Connection, Devices, Request};
+use std::collections::HashMap;
 use std::fs;
-use tokio::runtime::Runtime;
+use std::time::{Duration, Instant};
+
+mod chart;
+mod device;
+mod server;
+
+/// How often devices are fetched from the server, also retries connection.
+const REFRESH_PERIOD: Duration = Duration::from_secs(2);
+/// How often temperature shown by chart is measured.
+const SAMPLE_PERIOD: Duration = Duration::from_secs(1);
 
 fn main() {
-    Socket::run(
-        Settings{
-            window: iced::window::Settings{
-                size: (400, 300),
-                ..Default::default()
-            },
-            ..Settings::default()
-        }
-    ).expect("Failed to run GUI");
+    Dashboard::run(Settings {
+        window: iced::window::Settings {
+            size: (900, 600),
+            ..Default::default()
+        },
+        ..Settings::default()
+    })
+    .expect("Failed to run GUI");
 }
 
 fn get_server_addr() -> String {
     fs::read_to_string("settings/addr").unwrap_or_else(|_| String::from("127.0.0.1:55331"))
 }
 
-pub struct BlockingClient {
-    inner: client::Client,
-    rt: Runtime,
+/// Every socket and thermo on the server.
+struct Dashboard {
+    addr: String,
+    /// Re-established on next refresh after failure.
+    connection: Option<Connection>,
+    /// Connecting or loading devices right now.
+    busy: bool,
+    /// Devices were changed while loading, so loaded ones may be outdated.
+    stale: bool,
+    /// Measuring temperature for chart right now, further samples are skipped.
+    sampling: bool,
+    sockets: Vec<SocketView>,
+    thermos: Vec<ThermoView>,
+    new_socket: NewDevice,
+    new_thermo: NewDevice,
+    chart: Option<TemperatureChart>,
+    /// Last response of the server or error.
+    status: String,
+    /// Servers found on the local network.
+    servers: Vec<(Announcement, button::State)>,
+
+    refresh_button: button::State,
+    discover_button: button::State,
+    close_chart_button: button::State,
+    sockets_scroll: scrollable::State,
+    thermos_scroll: scrollable::State,
 }
 
-impl BlockingClient {
-    pub fn new(addr: String) -> Self {
-        let rt = Runtime::new().unwrap();
-        let inner = rt.block_on(client::Client::new(addr)).unwrap();
+/// Form creating device with given id and power or temperature.
+#[derive(Default)]
+struct NewDevice {
+    id: String,
+    value: String,
 
-        BlockingClient { inner, rt }
-    }
+    id_input: text_input::State,
+    value_input: text_input::State,
+    create_button: button::State,
+}
 
-    pub fn create_socket(&mut self, socket_id: &str, power: &str, state: &str) -> String {
-        self.rt
-            .block_on(self.inner.create_socket(socket_id, power, state))
-            .unwrap()
+impl NewDevice {
+    fn view(
+        &mut self,
+        value_placeholder: &str,
+        on_id: fn(String) -> Message,
+        on_value: fn(String) -> Message,
+        on_create: Message,
+    ) -> Element<'_, Message> {
+        Row::new()
+            .spacing(10)
+            .push(TextInput::new(&mut self.id_input, "Name", &self.id, on_id).padding(5))
+            .push(
+                TextInput::new(
+                    &mut self.value_input,
+                    value_placeholder,
+                    &self.value,
+                    on_value,
+                )
+                .padding(5)
+                .on_submit(on_create.clone()),
+            )
+            .push(Button::new(&mut self.create_button, Text::new("Create")).on_press(on_create))
+            .into()
     }
 
-    pub fn toggle_socket(&mut self, socket_id: &str) -> String {
-        self.rt
-            .block_on(self.inner.toggle_socket(socket_id))
-            .unwrap()
-    }
-    
-    pub fn fetch_socket(&mut self, socket_id: &str) -> String {
-        self.rt
-            .block_on(self.inner.fetch_socket(socket_id))
-            .unwrap()
+    /// Returns entered values, clearing the form.
+    fn take(&mut self) -> (String, String) {
+        (
+            std::mem::take(&mut self.id),
+            std::mem::take(&mut self.value),
+        )
     }
 }
 
-struct Socket {
-    id: String,
-    power: f64,
-    state: bool,
-    created: bool,
-    client: BlockingClient,
-
-    create_state: text_input::State,
-    button_state: button::State,
-}
-
 #[derive(Debug, Clone)]
 enum Message {
-    ToggleSocket,
+    Tick,
+    Refresh,
+    /// Result of connecting to the server with given address.
+    Connected(String, Result<Connection, String>),
+    /// Devices loaded from the server with given address.
+    Loaded(String, Result<Devices, String>),
+    Responded(Result<String, String>),
+    NewSocketId(String),
+    NewSocketPower(String),
     CreateSocket,
-    ChangePower(String),
+    NewThermoId(String),
+    NewThermoTemperature(String),
+    CreateThermo,
+    Socket(String, SocketMessage),
+    Thermo(String, ThermoMessage),
+    Discover,
+    Discovered(Result<Vec<Announcement>, String>),
+    PickServer(String),
+    Sample,
+    Sampled(String, Result<Option<f32>, String>),
+    CloseChart,
 }
 
-fn socket_create_dashboard(socket: &mut Socket) -> Element<Message> {
-    Column::new()
-    .push(Text::new("Create New Socket").size(50))
-    .push(TextInput::new(
-        &mut socket.create_state,
-        "Enter socket name",
-        &socket.power.to_string(),
-        Message::ChangePower,
-    ))
-    .push(
-        Button::new(&mut socket.button_state, Text::new("Create"))
-            .on_press(Message::CreateSocket),
-    )
-    .padding(20)
-    .align_items(Alignment::Center)
-    .into()
-}  
-
-fn socket_dashboard(socket: &mut Socket) -> Element<Message> {
-    Column::new()
-    .push(Text::new("Socket Dashboard").size(50))
-    .push(Text::new(format!("Socket ID: {}", socket.id)))
-    .push(Text::new(format!("Power: {}", socket.power)))
-    .push(Text::new(format!("State: {}", socket.state)))
-    .push(
-        Button::new(&mut socket.button_state, Text::new("Toggle"))
-            .on_press(Message::ToggleSocket),
-    )
-    .padding(20)
-    .align_items(Alignment::Center)
-    .into()
-}
+impl Dashboard {
+    /// Connects or reloads devices, unless already doing so.
+    fn refresh(&mut self) -> Command<Message> {
+        if self.busy {
+            self.stale = true;
+            return Command::none();
+        }
+        self.busy = true;
+        self.stale = false;
+        // Server may be switched while request is in flight, then its result is outdated.
+        let addr = self.addr.clone();
+        match &self.connection {
+            Some(connection) => Command::perform(connection.clone().devices(), move |result| {
+                Message::Loaded(addr.clone(), result)
+            }),
+            None => Command::perform(Connection::open(addr.clone()), move |result| {
+                Message::Connected(addr.clone(), result)
+            }),
+        }
+    }
 
-impl Sandbox for Socket {
-    type Message = Message;
+    fn send(&mut self, request: Request) -> Command<Message> {
+        match &self.connection {
+            Some(connection) => {
+                Command::perform(connection.clone().send(request), Message::Responded)
+            }
+            None => {
+                self.status = format!("Not connected to {}", self.addr);
+                Command::none()
+            }
+        }
+    }
 
-    fn new() -> Self {
-        let addr = get_server_addr();
-        let client = BlockingClient::new(addr);
+    fn disconnect(&mut self, error: String) {
+        self.connection = None;
+        self.status = format!("Disconnected from {}: {}", self.addr, error);
+    }
 
-        Self {
-            id: "socket#1".to_string(),
-            power: 0.0,
-            state: false,
-            created: false,
-            client,
+    /// Replaces devices with loaded ones, keeping input of ones still present.
+    fn show(&mut self, devices: Devices) {
+        let mut old: HashMap<_, _> = self
+            .sockets
+            .drain(..)
+            .map(|view| (view.socket.id.clone(), view))
+            .collect();
+        for socket in devices.sockets {
+            let view = match old.remove(&socket.id) {
+                Some(mut view) => {
+                    view.socket = socket;
+                    view
+                }
+                None => SocketView::new(socket),
+            };
+            self.sockets.push(view);
+        }
 
-            create_state: text_input::State::new(),
-            button_state: button::State::new(),
+        let mut old: HashMap<_, _> = self
+            .thermos
+            .drain(..)
+            .map(|view| (view.thermo.id.clone(), view))
+            .collect();
+        for thermo in devices.thermos {
+            let view = match old.remove(&thermo.id) {
+                Some(mut view) => {
+                    view.thermo = thermo;
+                    view
+                }
+                None => ThermoView::new(thermo),
+            };
+            self.thermos.push(view);
         }
     }
 
+    fn on_socket(&mut self, id: String, message: SocketMessage) -> Command<Message> {
+        let view = match self.sockets.iter_mut().find(|view| view.socket.id == id) {
+            Some(view) => view,
+            None => return Command::none(),
+        };
+        let request = match message {
+            SocketMessage::Toggle => Request::SetSocketState {
+                id,
+                state: !view.socket.state,
+                version: view.socket.version,
+            },
+            SocketMessage::ChangePower(power) => {
+                view.new_power = power;
+                return Command::none();
+            }
+            SocketMessage::SetPower => match view.new_power.parse() {
+                Ok(power) => {
+                    view.new_power.clear();
+                    Request::SetPower { id, power }
+                }
+                Err(_) => {
+                    self.status = format!("Bad power `{}`", view.new_power);
+                    return Command::none();
+                }
+            },
+            SocketMessage::Delete => Request::DeleteSocket(id),
+        };
+        self.send(request)
+    }
+
+    fn on_thermo(&mut self, id: String, message: ThermoMessage) -> Command<Message> {
+        let view = match self.thermos.iter_mut().find(|view| view.thermo.id == id) {
+            Some(view) => view,
+            None => return Command::none(),
+        };
+        let request = match message {
+            ThermoMessage::ChangeTemperature(temperature) => {
+                view.new_temperature = temperature;
+                return Command::none();
+            }
+            ThermoMessage::SetTemperature => match view.new_temperature.parse() {
+                Ok(temperature) => {
+                    view.new_temperature.clear();
+                    Request::SetThermo { id, temperature }
+                }
+                Err(_) => {
+                    self.status = format!("Bad temperature `{}`", view.new_temperature);
+                    return Command::none();
+                }
+            },
+            ThermoMessage::Chart => {
+                let mut chart = TemperatureChart::new(id);
+                chart.push(Instant::now(), view.thermo.temperature);
+                self.chart = Some(chart);
+                return Command::none();
+            }
+            ThermoMessage::Delete => Request::DeleteThermo(id),
+        };
+        self.send(request)
+    }
+}
+
+impl Application for Dashboard {
+    type Executor = executor::Default;
+    type Message = Message;
+    type Flags = ();
+
+    fn new(_flags: ()) -> (Self, Command<Message>) {
+        let mut dashboard = Self {
+            addr: get_server_addr(),
+            connection: None,
+            busy: false,
+            stale: false,
+            sampling: false,
+            sockets: Vec::new(),
+            thermos: Vec::new(),
+            new_socket: NewDevice::default(),
+            new_thermo: NewDevice::default(),
+            chart: None,
+            status: String::new(),
+            servers: Vec::new(),
+            refresh_button: button::State::new(),
+            discover_button: button::State::new(),
+            close_chart_button: button::State::new(),
+            sockets_scroll: scrollable::State::new(),
+            thermos_scroll: scrollable::State::new(),
+        };
+        dashboard.status = format!("Connecting to {}", dashboard.addr);
+        let connect = dashboard.refresh();
+        let discover = Command::perform(server::discover(), Message::Discovered);
+        (dashboard, Command::batch([connect, discover]))
+    }
+
     fn title(&self) -> String {
-        "Socket".to_string()
+        "Smart home".to_string()
     }
 
-    fn update(&mut self, message: Message) {
+    fn update(&mut self, message: Message) -> Command<Message> {
         match message {
-            Message::ToggleSocket => {
-                self.state = !self.state;
-                self.client.toggle_socket(&self.id);
+            Message::Tick => return self.refresh(),
+            Message::Refresh => {
+                self.status.clear();
+                return self.refresh();
+            }
+            Message::Connected(addr, result) => {
+                self.busy = false;
+                if addr != self.addr {
+                    return self.refresh();
+                }
+                match result {
+                    Ok(connection) => {
+                        self.connection = Some(connection);
+                        self.status = format!("Connected to {}", self.addr);
+                        return self.refresh();
+                    }
+                    Err(e) => self.status = format!("Can't connect to {}: {}", self.addr, e),
+                }
+            }
+            Message::Loaded(addr, result) => {
+                self.busy = false;
+                if addr != self.addr {
+                    return self.refresh();
+                }
+                match result {
+                    Ok(devices) => self.show(devices),
+                    Err(e) => self.disconnect(e),
+                }
+                if self.stale {
+                    return self.refresh();
+                }
             }
+            Message::Responded(Ok(response)) => {
+                self.status = response;
+                return self.refresh();
+            }
+            Message::Responded(Err(e)) => self.disconnect(e),
+            Message::NewSocketId(id) => self.new_socket.id = id,
+            Message::NewSocketPower(power) => self.new_socket.value = power,
             Message::CreateSocket => {
-                self.created = true;
-                self.client.create_socket(
-                    &self.id,
-                    &self.power.to_string(),
-                    &self.state.to_string(),
-                );
+                let (id, power) = self.new_socket.take();
+                match power.parse() {
+                    Ok(power) => return self.send(Request::CreateSocket { id, power }),
+                    Err(_) => self.status = format!("Bad power `{}`", power),
+                }
+            }
+            Message::NewThermoId(id) => self.new_thermo.id = id,
+            Message::NewThermoTemperature(temperature) => self.new_thermo.value = temperature,
+            Message::CreateThermo => {
+                let (id, temperature) = self.new_thermo.take();
+                match temperature.parse() {
+                    Ok(temperature) => return self.send(Request::CreateThermo { id, temperature }),
+                    Err(_) => self.status = format!("Bad temperature `{}`", temperature),
+                }
+            }
+            Message::Socket(id, message) => return self.on_socket(id, message),
+            Message::Thermo(id, message) => return self.on_thermo(id, message),
+            Message::Discover => {
+                self.status = "Looking for servers".into();
+                return Command::perform(server::discover(), Message::Discovered);
+            }
+            Message::Discovered(Ok(servers)) => {
+                self.status = format!("Found {} server(s)", servers.len());
+                self.servers = servers
+                    .into_iter()
+                    .map(|server| (server, button::State::new()))
+                    .collect();
+            }
+            Message::Discovered(Err(e)) => self.status = format!("Can't look for servers: {}", e),
+            Message::PickServer(addr) => {
+                self.addr = addr;
+                self.connection = None;
+                self.sockets.clear();
+                self.thermos.clear();
+                self.chart = None;
+                self.status = format!("Connecting to {}", self.addr);
+                return self.refresh();
+            }
+            Message::Sample => {
+                if self.sampling {
+                    return Command::none();
+                }
+                if let (Some(chart), Some(connection)) = (&self.chart, &self.connection) {
+                    self.sampling = true;
+                    let id = chart.thermo_id.clone();
+                    let measure = connection.clone().temperature(id.clone());
+                    return Command::perform(measure, move |result| {
+                        Message::Sampled(id.clone(), result)
+                    });
+                }
+            }
+            Message::Sampled(id, result) => {
+                self.sampling = false;
+                // Chart may be closed or switched to other thermo while measuring.
+                let chart = match &mut self.chart {
+                    Some(chart) if chart.thermo_id == id => chart,
+                    _ => return Command::none(),
+                };
+                match result {
+                    Ok(Some(temperature)) => chart.push(Instant::now(), temperature),
+                    Ok(None) => {
+                        self.chart = None;
+                        self.status = format!("Thermo `{}` is gone", id);
+                    }
+                    Err(e) => self.disconnect(e),
+                }
             }
-            Message::ChangePower(power) => {
-                self.power = power.parse().unwrap_or(0.0);
+            Message::CloseChart => self.chart = None,
+        }
+        Command::none()
+    }
+
+    fn subscription(&self) -> Subscription<Message> {
+        let refresh = iced::time::every(REFRESH_PERIOD).map(|_| Message::Tick);
+        match self.chart {
+            Some(_) => {
+                let sample = iced::time::every(SAMPLE_PERIOD).map(|_| Message::Sample);
+                Subscription::batch([refresh, sample])
             }
+            None => refresh,
         }
     }
 
-    fn view(&mut self) -> Element<Message> {
-        match self.created {
-            true => socket_dashboard(self),
-            false => {
-                let socket = self.client.fetch_socket(&self.id);
-                match socket.as_str() {
-                    "Unknown socket" => {
-                        socket_create_dashboard(self)
-                    },
-                    _ => {
-                        let socket_data: Vec<&str> = socket.split(',').collect();
-                        self.created = true;
-                        self.power = socket_data[2].parse().unwrap();
-                        self.state = socket_data[1].parse().unwrap();
-
-                        socket_dashboard(self)
-                    }
-                }
-            },
+    fn view(&mut self) -> Element<'_, Message> {
+        let mut sockets = Scrollable::new(&mut self.sockets_scroll)
+            .spacing(20)
+            .height(Length::Fill)
+            .push(Text::new("Sockets").size(32))
+            .push(self.new_socket.view(
+                "Power",
+                Message::NewSocketId,
+                Message::NewSocketPower,
+                Message::CreateSocket,
+            ));
+        for view in &mut self.sockets {
+            let id = view.socket.id.clone();
+            let view = view.view().map(move |m| Message::Socket(id.clone(), m));
+            sockets = sockets.push(view);
+        }
+
+        let mut thermos = Scrollable::new(&mut self.thermos_scroll)
+            .spacing(20)
+            .height(Length::Fill)
+            .push(Text::new("Thermos").size(32))
+            .push(self.new_thermo.view(
+                "Temperature",
+                Message::NewThermoId,
+                Message::NewThermoTemperature,
+                Message::CreateThermo,
+            ));
+        for view in &mut self.thermos {
+            let id = view.thermo.id.clone();
+            let view = view.view().map(move |m| Message::Thermo(id.clone(), m));
+            thermos = thermos.push(view);
+        }
+
+        let devices = Row::new()
+            .spacing(20)
+            .height(Length::Fill)
+            .push(Container::new(sockets).width(Length::FillPortion(1)))
+            .push(Rule::vertical(20))
+            .push(Container::new(thermos).width(Length::FillPortion(1)));
+
+        let status = Row::new()
+            .spacing(10)
+            .align_items(Alignment::Center)
+            .push(
+                Button::new(&mut self.refresh_button, Text::new("Refresh"))
+                    .on_press(Message::Refresh),
+            )
+            .push(Text::new(&self.status).size(16));
+
+        let mut servers = Row::new()
+            .spacing(10)
+            .align_items(Alignment::Center)
+            .push(Text::new(format!("Server {}", self.addr)))
+            .push(
+                Button::new(&mut self.discover_button, Text::new("Find servers"))
+                    .on_press(Message::Discover),
+            );
+        for (server, button) in &mut self.servers {
+            let addr = server.addr.to_string();
+            let mut button = Button::new(button, Text::new(server.to_string()));
+            if addr != self.addr {
+                button = button.on_press(Message::PickServer(addr));
+            }
+            servers = servers.push(button);
+        }
+
+        let mut dashboard = Column::new()
+            .padding(20)
+            .spacing(10)
+            .push(servers)
+            .push(devices);
+        if let Some(chart) = &mut self.chart {
+            dashboard = dashboard
+                .push(Rule::horizontal(20))
+                .push(chart_panel(chart, &mut self.close_chart_button));
         }
-    }  
+        dashboard.push(status).into()
+    }
+}
+
+fn chart_panel<'a>(
+    chart: &'a mut TemperatureChart,
+    close_button: &'a mut button::State,
+) -> Element<'a, Message> {
+    let format = |temperature: Option<f32>| match temperature {
+        Some(temperature) => format!("{} °C", temperature),
+        None => "-".into(),
+    };
+    let summary = format!(
+        "now {}, min {}, max {}",
+        format(chart.latest()),
+        format(chart.min()),
+        format(chart.max())
+    );
+    let header = Row::new()
+        .spacing(20)
+        .align_items(Alignment::Center)
+        .push(Text::new(format!("{} temperature", chart.thermo_id)).size(24))
+        .push(Text::new(summary).width(Length::Fill))
+        .push(Button::new(close_button, Text::new("Close")).on_press(Message::CloseChart));
+
+    Column::new()
+        .spacing(10)
+        .push(header)
+        .push(
+            Canvas::new(chart)
+                .width(Length::Fill)
+                .height(Length::Units(200)),
+        )
+        .into()
 }
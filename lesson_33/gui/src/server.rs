@@ -0,0 +1,142 @@
+//! Requests to the server, run in background so the window never waits for network.
+
+use crate::device::{Socket, Thermo};
+use client::discovery::{self, Announcement};
+use client::{found, Client, ClientResult, Pool};
+use std::fmt;
+use std::time::Duration;
+
+/// Refresh, chart sampling and user's request may be in flight at once.
+const POOL_SIZE: usize = 3;
+/// How long to wait for servers to reply to discovery query.
+const DISCOVERY_WAIT: Duration = Duration::from_secs(1);
+
+/// Finds servers on the local network.
+pub async fn discover() -> Result<Vec<Announcement>, String> {
+    let targets = discovery::default_targets();
+    discovery::discover(&targets, DISCOVERY_WAIT)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Change requested by user.
+#[derive(Debug, Clone)]
+pub enum Request {
+    CreateSocket {
+        id: String,
+        power: u64,
+    },
+    CreateThermo {
+        id: String,
+        temperature: i64,
+    },
+    /// Fails if socket was changed since `version`, e.g. by other client.
+    SetSocketState {
+        id: String,
+        state: bool,
+        version: u64,
+    },
+    SetPower {
+        id: String,
+        power: u64,
+    },
+    SetThermo {
+        id: String,
+        temperature: i64,
+    },
+    DeleteSocket(String),
+    DeleteThermo(String),
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Devices {
+    pub sockets: Vec<Socket>,
+    pub thermos: Vec<Thermo>,
+}
+
+/// Connections shared by requests in flight, so slow request doesn't hold up others.
+///
+/// Errors are returned as strings to be shown to user.
+#[derive(Clone)]
+pub struct Connection(Pool);
+
+impl fmt::Debug for Connection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Connection")
+    }
+}
+
+impl Connection {
+    pub async fn open(addr: String) -> Result<Self, String> {
+        let pool = Pool::new(addr, POOL_SIZE);
+        // Fails early if server is unreachable, connection stays in the pool.
+        pool.get().await.map_err(|e| e.to_string())?;
+        Ok(Self(pool))
+    }
+
+    pub async fn devices(self) -> Result<Devices, String> {
+        let mut client = self.0.get().await.map_err(|e| e.to_string())?;
+        fetch_devices(&mut client).await.map_err(|e| e.to_string())
+    }
+
+    /// Current temperature of thermo, `None` if there is no such thermo.
+    pub async fn temperature(self, thermo_id: String) -> Result<Option<f32>, String> {
+        let mut client = self.0.get().await.map_err(|e| e.to_string())?;
+        let info = found(client.fetch_thermo(&thermo_id).await).map_err(|e| e.to_string())?;
+        Ok(info.map(|info| Thermo::from(info).temperature))
+    }
+
+    /// Returns message describing result, fails only if connection is lost.
+    pub async fn send(self, request: Request) -> Result<String, String> {
+        let mut client = self.0.get().await.map_err(|e| e.to_string())?;
+        let result = match request {
+            Request::CreateSocket { id, power } => {
+                let result = client.create_socket(&id, power, false).await;
+                result.map(|_| format!("Socket `{}` created", id))
+            }
+            Request::CreateThermo { id, temperature } => {
+                let result = client.create_thermo(&id, temperature).await;
+                result.map(|_| format!("Thermo `{}` created", id))
+            }
+            Request::SetSocketState { id, state, version } => {
+                let result = client.set_socket_state(&id, state, Some(version)).await;
+                let state = if state { "on" } else { "off" };
+                result.map(|_| format!("Socket `{}` switched {}", id, state))
+            }
+            Request::SetPower { id, power } => {
+                let result = client.set_power(&id, power).await;
+                result.map(|_| format!("Socket `{}` power set to {} W", id, power))
+            }
+            Request::SetThermo { id, temperature } => {
+                let result = client.set_thermo(&id, temperature).await;
+                result.map(|_| format!("Thermo `{}` set to {} °C", id, temperature))
+            }
+            Request::DeleteSocket(id) => {
+                let result = client.delete_socket(&id).await;
+                result.map(|_| format!("Socket `{}` deleted", id))
+            }
+            Request::DeleteThermo(id) => {
+                let result = client.delete_thermo(&id).await;
+                result.map(|_| format!("Thermo `{}` deleted", id))
+            }
+        };
+        match result {
+            Ok(message) => Ok(message),
+            Err(e) if e.is_transport() => Err(e.to_string()),
+            Err(e) => Ok(e.to_string()),
+        }
+    }
+}
+
+async fn fetch_devices(client: &mut Client) -> ClientResult<Devices> {
+    let (sockets, thermos) = client.fetch_devices().await?;
+    let mut devices = Devices::default();
+    for info in sockets {
+        // Socket deleted after fetching is skipped.
+        if let Some(metering) = found(client.meter_socket(&info.id).await)? {
+            devices.sockets.push(Socket::new(info, metering));
+        }
+    }
+    devices.thermos = thermos.into_iter().map(Thermo::from).collect();
+    Ok(devices)
+}
@@ -0,0 +1,390 @@
+//! In-process STP server with scripted responses, for testing apps built on `client::Client`.
+//!
+//! Server listens on ephemeral local port and answers requests with replies registered
+//! by test. Every received request is recorded. Besides normal responses replies can
+//! inject faults: delays, dropped connections and frames client can't decode.
+//!
+//! Wire format is implemented here rather than reused from `stp`, so it can be broken on purpose.
+//! Only frame size limit is shared.
+
+use std::collections::{HashMap, VecDeque};
+use std::io;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use stp::MAX_PAYLOAD_LEN;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::{JoinHandle, JoinSet};
+
+const CLIENT_HANDSHAKE: [u8; 4] = *b"clnt";
+const SERVER_HANDSHAKE: [u8; 4] = *b"serv";
+
+const TEXT: u8 = 0;
+const BINARY: u8 = 1;
+const PING: u8 = 2;
+const PONG: u8 = 3;
+
+/// Response of real server to unknown command.
+const BAD_COMMAND: &str = "Bad command";
+
+/// What server does in response to request.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Reply {
+    Text(String),
+    /// Waits before replying.
+    Delayed(Duration, Box<Reply>),
+    /// Closes connection without responding.
+    Disconnect,
+    Malformed(Malformed),
+}
+
+impl Reply {
+    pub fn text(text: impl Into<String>) -> Self {
+        Self::Text(text.into())
+    }
+
+    pub fn delayed(self, delay: Duration) -> Self {
+        Self::Delayed(delay, Box::new(self))
+    }
+}
+
+/// Frames client fails to decode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Malformed {
+    /// Frame kind byte not defined by protocol.
+    UnknownKind,
+    /// Text frame with invalid UTF-8.
+    BadEncoding,
+    /// Binary frame instead of text one.
+    Binary,
+    /// Header announces more payload than is sent before connection is closed.
+    Truncated,
+}
+
+#[derive(Debug)]
+struct Script {
+    /// Used before `always` replies, each one once.
+    once: HashMap<String, VecDeque<Reply>>,
+    always: HashMap<String, Reply>,
+    fallback: Reply,
+    requests: Vec<String>,
+    connections: usize,
+}
+
+impl Script {
+    fn reply(&mut self, request: &str) -> Reply {
+        if let Some(reply) = self.once.get_mut(request).and_then(VecDeque::pop_front) {
+            return reply;
+        }
+        match self.always.get(request) {
+            Some(reply) => reply.clone(),
+            None => self.fallback.clone(),
+        }
+    }
+}
+
+/// Stops serving when dropped, connected clients are disconnected.
+pub struct MockServer {
+    addr: SocketAddr,
+    script: Arc<Mutex<Script>>,
+    accepting: JoinHandle<()>,
+}
+
+impl MockServer {
+    /// Starts server on ephemeral port of local host, see [`addr`](Self::addr).
+    ///
+    /// Unscripted requests are answered like real server answers unknown command.
+    pub async fn start() -> io::Result<Self> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        let script = Arc::new(Mutex::new(Script {
+            once: HashMap::new(),
+            always: HashMap::new(),
+            fallback: Reply::text(BAD_COMMAND),
+            requests: Vec::new(),
+            connections: 0,
+        }));
+        let accepting = tokio::spawn(accept(listener, script.clone()));
+        Ok(Self {
+            addr,
+            script,
+            accepting,
+        })
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Answers every `request` with `reply`.
+    pub fn reply(&self, request: impl Into<String>, reply: Reply) {
+        self.script().always.insert(request.into(), reply);
+    }
+
+    /// Answers next `request` with `reply`, takes precedence over [`reply`](Self::reply).
+    ///
+    /// Replies registered for the same request are used in order.
+    pub fn reply_once(&self, request: impl Into<String>, reply: Reply) {
+        let mut script = self.script();
+        script
+            .once
+            .entry(request.into())
+            .or_default()
+            .push_back(reply);
+    }
+
+    /// Answers requests without scripted reply.
+    pub fn fallback(&self, reply: Reply) {
+        self.script().fallback = reply;
+    }
+
+    /// All requests received so far, in order of arrival.
+    pub fn requests(&self) -> Vec<String> {
+        self.script().requests.clone()
+    }
+
+    /// Number of clients that completed handshake so far.
+    pub fn connections(&self) -> usize {
+        self.script().connections
+    }
+
+    fn script(&self) -> std::sync::MutexGuard<'_, Script> {
+        self.script.lock().expect("script lock poisoned")
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        // Connections are served by tasks owned by accepting one, so they are aborted too.
+        self.accepting.abort();
+    }
+}
+
+async fn accept(listener: TcpListener, script: Arc<Mutex<Script>>) {
+    let mut connections = JoinSet::new();
+    while let Ok((stream, _)) = listener.accept().await {
+        // Forgets connections that are already closed.
+        while connections.try_join_next().is_some() {}
+        let script = script.clone();
+        connections.spawn(async move {
+            // Client going away is expected, whatever the reason.
+            let _ = serve(stream, script).await;
+        });
+    }
+}
+
+async fn serve(mut stream: TcpStream, script: Arc<Mutex<Script>>) -> io::Result<()> {
+    let mut handshake = [0; 4];
+    stream.read_exact(&mut handshake).await?;
+    if handshake != CLIENT_HANDSHAKE {
+        return Ok(());
+    }
+    stream.write_all(&SERVER_HANDSHAKE).await?;
+    script.lock().expect("script lock poisoned").connections += 1;
+
+    loop {
+        let mut header = [0; 5];
+        stream.read_exact(&mut header).await?;
+        let len = u32::from_be_bytes([header[1], header[2], header[3], header[4]]) as usize;
+        if len > MAX_PAYLOAD_LEN {
+            let message = format!("frame of {} bytes exceeds limit", len);
+            return Err(io::Error::new(io::ErrorKind::InvalidData, message));
+        }
+        let mut payload = vec![0; len];
+        stream.read_exact(&mut payload).await?;
+
+        match header[0] {
+            PING => send(&mut stream, PONG, &[]).await?,
+            TEXT | BINARY => {
+                let request = String::from_utf8_lossy(&payload).into_owned();
+                let reply = {
+                    let mut script = script.lock().expect("script lock poisoned");
+                    script.requests.push(request.clone());
+                    script.reply(&request)
+                };
+                if !respond(&mut stream, reply).await? {
+                    return Ok(());
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Returns whether connection stays open.
+async fn respond(stream: &mut TcpStream, mut reply: Reply) -> io::Result<bool> {
+    while let Reply::Delayed(delay, delayed) = reply {
+        tokio::time::sleep(delay).await;
+        reply = *delayed;
+    }
+    match reply {
+        Reply::Text(text) => send(stream, TEXT, text.as_bytes()).await?,
+        Reply::Delayed(..) => unreachable!("delays are waited out above"),
+        Reply::Disconnect => return Ok(false),
+        Reply::Malformed(Malformed::UnknownKind) => send(stream, 0xff, &[]).await?,
+        Reply::Malformed(Malformed::BadEncoding) => send(stream, TEXT, &[0xff, 0xfe]).await?,
+        Reply::Malformed(Malformed::Binary) => send(stream, BINARY, b"binary").await?,
+        Reply::Malformed(Malformed::Truncated) => {
+            stream.write_all(&[TEXT, 0, 0, 0, 10]).await?;
+            stream.write_all(b"trunc").await?;
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+async fn send(stream: &mut TcpStream, kind: u8, payload: &[u8]) -> io::Result<()> {
+    let len = u32::try_from(payload.len())
+        .ok()
+        .filter(|&len| len as usize <= MAX_PAYLOAD_LEN)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "reply exceeds frame limit"))?
+        .to_be_bytes();
+    stream
+        .write_all(&[kind, len[0], len[1], len[2], len[3]])
+        .await?;
+    stream.write_all(payload).await?;
+    stream.flush().await
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Malformed, MockServer, Reply, CLIENT_HANDSHAKE};
+    use client::{Client, ClientError, SocketInfo};
+    use std::time::{Duration, Instant};
+    use stp::error::{RecvError, RequestError};
+    use stp::MAX_PAYLOAD_LEN;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpStream;
+
+    #[tokio::test]
+    async fn scripted_replies() {
+        let server = MockServer::start().await.unwrap();
+        server.reply("list_sockets", Reply::text("kettle"));
+        server.reply("socket_version|||kettle", Reply::text("kettle,3"));
+        server.reply("fetch_socket|||kettle", Reply::text("kettle,true,2000"));
+        server.reply_once("fetch_socket|||kettle", Reply::text("Unknown socket"));
+
+        let mut client = Client::new(server.addr()).await.unwrap();
+        assert_eq!(client.list_sockets().await.unwrap(), ["kettle"]);
+        let err = client.fetch_socket("kettle").await.unwrap_err();
+        assert!(matches!(err, ClientError::NotFound(_)));
+        let expected = SocketInfo {
+            id: "kettle".into(),
+            state: true,
+            power: 2000,
+            version: 3,
+        };
+        assert_eq!(client.fetch_socket("kettle").await.unwrap(), expected);
+
+        let err = client.fetch_thermo("fridge").await.unwrap_err();
+        assert!(matches!(err, ClientError::Rejected(r) if r == "Bad command"));
+        server.fallback(Reply::text("Unknown thermo"));
+        let err = client.fetch_thermo("fridge").await.unwrap_err();
+        assert!(matches!(err, ClientError::NotFound(_)));
+
+        client.ping().await.unwrap();
+        let requests = [
+            "list_sockets",
+            "socket_version|||kettle",
+            "fetch_socket|||kettle",
+            "socket_version|||kettle",
+            "fetch_socket|||kettle",
+            "fetch_thermo|||fridge",
+            "fetch_thermo|||fridge",
+        ];
+        assert_eq!(server.requests(), requests);
+        assert_eq!(server.connections(), 1);
+    }
+
+    #[tokio::test]
+    async fn ids_looking_like_refusals() {
+        let server = MockServer::start().await.unwrap();
+        server.reply("list_sockets", Reply::text("Bad lamp"));
+        server.reply("socket_version|||Bad lamp", Reply::text("Bad lamp,2"));
+        server.reply("fetch_socket|||Bad lamp", Reply::text("Bad lamp,true,60"));
+        server.reply("list_thermos", Reply::text("Unknown fridge"));
+        let thermo = Reply::text("Thermo Unknown fridge temperature is 4");
+        server.reply("fetch_thermo|||Unknown fridge", thermo);
+
+        let mut client = Client::new(server.addr()).await.unwrap();
+        let (sockets, thermos) = client.fetch_devices().await.unwrap();
+        let expected = SocketInfo {
+            id: "Bad lamp".into(),
+            state: true,
+            power: 60,
+            version: 2,
+        };
+        assert_eq!(sockets, [expected]);
+        assert_eq!(thermos[0].id, "Unknown fridge");
+    }
+
+    #[tokio::test]
+    async fn faults() {
+        let server = MockServer::start().await.unwrap();
+        let delay = Duration::from_millis(100);
+        server.reply("list_thermos", Reply::text("").delayed(delay));
+        server.reply("list_sockets", Reply::Disconnect);
+
+        let mut client = Client::new(server.addr()).await.unwrap();
+        let started = Instant::now();
+        assert!(client.list_thermos().await.unwrap().is_empty());
+        assert!(started.elapsed() >= delay);
+
+        let err = client.list_sockets().await.unwrap_err();
+        assert!(err.is_transport());
+        assert!(client.is_broken());
+
+        let cases = [
+            (Malformed::UnknownKind, "unknown frame kind: 255"),
+            (Malformed::BadEncoding, "bad encoding"),
+            (Malformed::Binary, "expected text, received binary message"),
+        ];
+        for (malformed, message) in cases {
+            server.reply_once("list_houses", Reply::Malformed(malformed));
+            let mut client = Client::new(server.addr()).await.unwrap();
+            let err = client.list_houses().await.unwrap_err();
+            assert_eq!(err.to_string(), message);
+        }
+
+        server.reply_once("list_houses", Reply::Malformed(Malformed::Truncated));
+        let mut client = Client::new(server.addr()).await.unwrap();
+        let err = client.list_houses().await.unwrap_err();
+        let truncated = matches!(
+            err,
+            ClientError::Request(RequestError::Recv(RecvError::Io(_)))
+        );
+        assert!(truncated, "{}", err);
+    }
+
+    #[tokio::test]
+    async fn drop_disconnects_clients() {
+        let server = MockServer::start().await.unwrap();
+        server.reply("list_sockets", Reply::text(""));
+        let mut client = Client::new(server.addr()).await.unwrap();
+        client.list_sockets().await.unwrap();
+
+        drop(server);
+        let err = client.list_sockets().await.unwrap_err();
+        assert!(err.is_transport());
+    }
+
+    #[tokio::test]
+    async fn oversized_frame_closes_connection() {
+        let server = MockServer::start().await.unwrap();
+        let mut stream = TcpStream::connect(server.addr()).await.unwrap();
+        stream.write_all(&CLIENT_HANDSHAKE).await.unwrap();
+        let mut handshake = [0; 4];
+        stream.read_exact(&mut handshake).await.unwrap();
+
+        let len = (MAX_PAYLOAD_LEN as u32 + 1).to_be_bytes();
+        stream
+            .write_all(&[0, len[0], len[1], len[2], len[3]])
+            .await
+            .unwrap();
+        let mut rest = Vec::new();
+        stream.read_to_end(&mut rest).await.unwrap();
+        assert!(rest.is_empty());
+        assert!(server.requests().is_empty());
+    }
+}
@@ -0,0 +1,187 @@
+use crate::handler::Request;
+use crate::home::Home;
+use crate::router::Handler;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+use tokio::sync::{Mutex as AsyncMutex, OwnedMutexGuard};
+use tokio::task;
+
+/// Command serving audit log, routed only when the log is kept.
+pub const HISTORY_COMMAND: &str = "history";
+
+/// History entries returned by `history` command if client doesn't ask for other number.
+const DEFAULT_HISTORY: usize = 10;
+
+/// Change requested by client.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuditRecord {
+    /// RFC 3339 UTC timestamp.
+    pub time: String,
+    pub peer: String,
+    pub identity: String,
+    pub command: String,
+    /// Device or house the command was applied to.
+    pub target: String,
+    pub before: Option<String>,
+    pub after: Option<String>,
+    pub response: String,
+}
+
+impl AuditRecord {
+    pub fn now() -> String {
+        humantime::format_rfc3339_millis(SystemTime::now()).to_string()
+    }
+}
+
+impl fmt::Display for AuditRecord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {}@{} {} {}: {} -> {} ({})",
+            self.time,
+            self.identity,
+            self.peer,
+            self.command,
+            self.target,
+            self.before.as_deref().unwrap_or("-"),
+            self.after.as_deref().unwrap_or("-"),
+            self.response
+        )
+    }
+}
+
+/// Append-only log of changes, one JSON record per line.
+pub struct AuditLog {
+    path: PathBuf,
+    file: Mutex<File>,
+    /// Held by client from reading state before change until it is recorded.
+    changes: Arc<AsyncMutex<()>>,
+}
+
+impl AuditLog {
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        Ok(Self {
+            path,
+            file: Mutex::new(file),
+            changes: Arc::default(),
+        })
+    }
+
+    /// Waits until no other audited change is in progress.
+    pub async fn lock(&self) -> OwnedMutexGuard<()> {
+        self.changes.clone().lock_owned().await
+    }
+
+    pub fn record(&self, record: &AuditRecord) -> io::Result<()> {
+        let mut line = serde_json::to_vec(record)?;
+        line.push(b'\n');
+
+        let mut file = self.file.lock().unwrap_or_else(|e| e.into_inner());
+        file.write_all(&line)?;
+        file.sync_data()
+    }
+
+    /// Up to `limit` latest records about `target`, oldest first.
+    ///
+    /// Unreadable lines, like one torn by crash, are skipped.
+    pub fn history(&self, target: &str, limit: usize) -> io::Result<Vec<AuditRecord>> {
+        let mut recent = VecDeque::new();
+        for line in BufReader::new(File::open(&self.path)?).lines() {
+            let record: AuditRecord = match serde_json::from_str(&line?) {
+                Ok(record) => record,
+                Err(_) => continue,
+            };
+            if record.target != target {
+                continue;
+            }
+            recent.push_back(record);
+            if recent.len() > limit {
+                recent.pop_front();
+            }
+        }
+        Ok(recent.into())
+    }
+}
+
+/// Serves `history|||<target>[|||<limit>]` command from audit log.
+pub struct History(pub Arc<AuditLog>);
+
+#[async_trait]
+impl Handler for History {
+    async fn handle(&self, _: &Home, mut request: Request<'_>) -> String {
+        let target = request.next();
+        if target.is_empty() {
+            return "Select device".into();
+        }
+
+        let limit = match request.next() {
+            "" => DEFAULT_HISTORY,
+            limit => match limit.parse() {
+                Ok(limit) => limit,
+                Err(_) => return "Bad history limit".into(),
+            },
+        };
+
+        let (log, target) = (self.0.clone(), target.to_string());
+        let history = task::spawn_blocking(move || log.history(&target, limit));
+        match history.await.unwrap_or_else(|e| Err(io::Error::other(e))) {
+            Ok(records) => {
+                let lines: Vec<_> = records.iter().map(ToString::to_string).collect();
+                lines.join("\n")
+            }
+            Err(e) => {
+                tracing::error!(error = %e, "failed to read audit log");
+                format!("Can't read history: {}", e)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::audit::{AuditLog, AuditRecord};
+
+    fn record(target: &str, after: &str) -> AuditRecord {
+        AuditRecord {
+            time: AuditRecord::now(),
+            peer: "127.0.0.1:5000".into(),
+            identity: "alice".into(),
+            command: "set_thermo".into(),
+            target: target.into(),
+            before: None,
+            after: Some(after.into()),
+            response: "ok".into(),
+        }
+    }
+
+    #[test]
+    fn recent_history() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("audit.log");
+        let log = AuditLog::open(&path).unwrap();
+
+        for after in ["1", "2", "3"] {
+            log.record(&record("thermo_1", after)).unwrap();
+            log.record(&record("thermo_2", after)).unwrap();
+        }
+        drop(log);
+
+        let log = AuditLog::open(&path).unwrap();
+        let history = log.history("thermo_1", 2).unwrap();
+        let afters: Vec<_> = history.iter().map(|r| r.after.as_deref()).collect();
+        assert_eq!(afters, [Some("2"), Some("3")]);
+        assert!(history[0]
+            .to_string()
+            .ends_with("alice@127.0.0.1:5000 set_thermo thermo_1: - -> 2 (ok)"));
+        assert!(log.history("socket_1", 10).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,585 @@
+use crate::audit::HISTORY_COMMAND;
+use crate::connections::MAX_CONNECTIONS;
+use crate::handler;
+use clap::{Parser, ValueEnum};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use std::{fmt, fs, io};
+use thiserror::Error;
+use tracing::level_filters::LevelFilter;
+
+const DEFAULT_CONFIG: &str = "settings/server.toml";
+/// Bind address file read by versions without config file, still used if there is none.
+const LEGACY_ADDR: &str = "settings/addr";
+
+/// Smart home STP server.
+///
+/// Settings are taken from config file, then overridden by environment variables,
+/// then by command line flags. Without config file bind address is read from
+/// `settings/addr` if it exists.
+#[derive(Debug, Default, Parser)]
+#[command(version)]
+pub struct Args {
+    /// Path to TOML config file [default: settings/server.toml]
+    #[arg(short, long, env = "STP_SERVER_CONFIG")]
+    pub config: Option<PathBuf>,
+
+    /// Validate configuration, print it and exit
+    #[arg(long)]
+    pub check_config: bool,
+
+    /// TCP address to listen on
+    #[arg(long, env = "STP_SERVER_ADDR")]
+    pub addr: Option<String>,
+
+    /// Unix socket to listen on in addition to TCP
+    #[arg(long, env = "STP_SERVER_UNIX_SOCKET")]
+    pub unix_socket: Option<PathBuf>,
+
+    /// File to store home state in
+    #[arg(long, env = "STP_SERVER_STORAGE")]
+    pub storage: Option<PathBuf>,
+
+    /// File to record changes made by clients in
+    #[arg(long, env = "STP_SERVER_AUDIT_LOG")]
+    pub audit_log: Option<PathBuf>,
+
+    /// Max number of simultaneously served clients
+    #[arg(long, env = "STP_SERVER_MAX_CONNECTIONS")]
+    pub max_connections: Option<usize>,
+
+    /// Seconds to wait for the next request before dropping client
+    #[arg(long, env = "STP_SERVER_IDLE_TIMEOUT")]
+    pub idle_timeout: Option<u64>,
+
+    /// Seconds to receive request or send response
+    #[arg(long, env = "STP_SERVER_REQUEST_TIMEOUT")]
+    pub request_timeout: Option<u64>,
+
+    /// Seconds to wait for clients to disconnect on shutdown
+    #[arg(long, env = "STP_SERVER_SHUTDOWN_TIMEOUT")]
+    pub shutdown_timeout: Option<u64>,
+
+    /// Token clients must send with `auth` command before any other request
+    #[arg(long, env = "STP_SERVER_AUTH_TOKEN", hide_env_values = true)]
+    pub auth_token: Option<String>,
+
+    /// Most verbose log messages to print
+    #[arg(long, env = "STP_SERVER_LOG_LEVEL")]
+    pub log_level: Option<LogLevel>,
+
+    /// Address to serve Prometheus metrics on, at `/metrics`
+    #[arg(long, env = "STP_SERVER_METRICS_ADDR")]
+    pub metrics_addr: Option<String>,
+
+    /// UDP address to answer discovery queries on
+    #[arg(long, env = "STP_SERVER_DISCOVERY_ADDR")]
+    pub discovery_addr: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub addr: String,
+    pub unix_socket: Option<PathBuf>,
+    pub storage: PathBuf,
+    /// Changes are not recorded if not set.
+    pub audit_log: Option<PathBuf>,
+    pub limits: Limits,
+    pub auth: Auth,
+    pub logging: Logging,
+    pub metrics: MetricsEndpoint,
+    pub discovery: Discovery,
+    pub rate_limit: RateLimits,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            addr: "127.0.0.1:55331".into(),
+            unix_socket: None,
+            storage: "home.log".into(),
+            audit_log: None,
+            limits: Limits::default(),
+            auth: Auth::default(),
+            logging: Logging::default(),
+            metrics: MetricsEndpoint::default(),
+            discovery: Discovery::default(),
+            rate_limit: RateLimits::default(),
+        }
+    }
+}
+
+/// Timeouts are in seconds.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Limits {
+    pub max_connections: usize,
+    pub idle_timeout: u64,
+    pub request_timeout: u64,
+    pub shutdown_timeout: u64,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            max_connections: 256,
+            idle_timeout: 300,
+            request_timeout: 10,
+            shutdown_timeout: 10,
+        }
+    }
+}
+
+impl Limits {
+    pub fn idle_timeout(&self) -> Duration {
+        Duration::from_secs(self.idle_timeout)
+    }
+
+    pub fn request_timeout(&self) -> Duration {
+        Duration::from_secs(self.request_timeout)
+    }
+
+    pub fn shutdown_timeout(&self) -> Duration {
+        Duration::from_secs(self.shutdown_timeout)
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Auth {
+    /// Clients are not required to authenticate if neither token nor users are set.
+    pub token: Option<String>,
+    /// Personal tokens by user name, name is recorded in audit log.
+    pub users: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Logging {
+    pub level: LogLevel,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MetricsEndpoint {
+    /// Metrics are not served if not set.
+    pub addr: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Discovery {
+    /// Server can't be discovered by clients if not set.
+    pub addr: Option<String>,
+    /// Name clients show in the list of discovered servers.
+    pub name: String,
+}
+
+impl Default for Discovery {
+    fn default() -> Self {
+        Self {
+            addr: None,
+            name: "smart home".into(),
+        }
+    }
+}
+
+/// Requests are not limited unless rates are set.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RateLimits {
+    pub per_connection: Option<Rate>,
+    pub per_ip: Option<Rate>,
+    /// Overrides of the above by command name. Command with own rate is counted
+    /// separately, all other commands share one bucket.
+    pub commands: BTreeMap<String, CommandRates>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CommandRates {
+    pub per_connection: Option<Rate>,
+    pub per_ip: Option<Rate>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Rate {
+    pub per_second: f64,
+    pub burst: u32,
+}
+
+impl Rate {
+    fn is_valid(&self) -> bool {
+        self.per_second.is_finite() && self.per_second > 0.0 && self.burst > 0
+    }
+}
+
+#[derive(
+    Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, ValueEnum,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum LogLevel {
+    Error,
+    Warn,
+    #[default]
+    Info,
+    Debug,
+}
+
+impl From<LogLevel> for LevelFilter {
+    fn from(level: LogLevel) -> Self {
+        match level {
+            LogLevel::Error => LevelFilter::ERROR,
+            LogLevel::Warn => LevelFilter::WARN,
+            LogLevel::Info => LevelFilter::INFO,
+            LogLevel::Debug => LevelFilter::DEBUG,
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum ConfigError {
+    #[error("can't read config file {0}: {1}")]
+    Read(PathBuf, io::Error),
+    #[error("bad config file {0}: {1}")]
+    Parse(PathBuf, toml::de::Error),
+    #[error("invalid configuration:\n{0}")]
+    Invalid(Problems),
+}
+
+/// All problems found by validation, one per line.
+#[derive(Debug)]
+pub struct Problems(Vec<String>);
+
+impl fmt::Display for Problems {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let lines: Vec<_> = self.0.iter().map(|p| format!("  - {}", p)).collect();
+        f.write_str(&lines.join("\n"))
+    }
+}
+
+impl Config {
+    /// Builds configuration from config file and overrides given in `args`.
+    ///
+    /// Default config file may be missing, then built-in defaults are used.
+    pub fn load(args: &Args) -> Result<Self, ConfigError> {
+        let mut config = match &args.config {
+            Some(path) => Self::from_file(path)?,
+            None if Path::new(DEFAULT_CONFIG).exists() => Self::from_file(DEFAULT_CONFIG)?,
+            None => Self::legacy(Path::new(LEGACY_ADDR)),
+        };
+        config.apply(args);
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, ConfigError> {
+        let path = path.as_ref();
+        let content = fs::read_to_string(path).map_err(|e| ConfigError::Read(path.into(), e))?;
+        toml::from_str(&content).map_err(|e| ConfigError::Parse(path.into(), e))
+    }
+
+    /// Built-in defaults with bind address from `addr_file`, if it can be read.
+    fn legacy(addr_file: &Path) -> Self {
+        match fs::read_to_string(addr_file) {
+            Ok(addr) => Self {
+                addr: addr.trim().into(),
+                ..Self::default()
+            },
+            Err(_) => Self::default(),
+        }
+    }
+
+    fn apply(&mut self, args: &Args) {
+        if let Some(addr) = &args.addr {
+            self.addr = addr.clone();
+        }
+        if let Some(path) = &args.unix_socket {
+            self.unix_socket = Some(path.clone());
+        }
+        if let Some(path) = &args.storage {
+            self.storage = path.clone();
+        }
+        if let Some(path) = &args.audit_log {
+            self.audit_log = Some(path.clone());
+        }
+        if let Some(max) = args.max_connections {
+            self.limits.max_connections = max;
+        }
+        if let Some(secs) = args.idle_timeout {
+            self.limits.idle_timeout = secs;
+        }
+        if let Some(secs) = args.request_timeout {
+            self.limits.request_timeout = secs;
+        }
+        if let Some(secs) = args.shutdown_timeout {
+            self.limits.shutdown_timeout = secs;
+        }
+        if let Some(token) = &args.auth_token {
+            self.auth.token = Some(token.clone());
+        }
+        if let Some(level) = args.log_level {
+            self.logging.level = level;
+        }
+        if let Some(addr) = &args.metrics_addr {
+            self.metrics.addr = Some(addr.clone());
+        }
+        if let Some(addr) = &args.discovery_addr {
+            self.discovery.addr = Some(addr.clone());
+        }
+    }
+
+    fn validate(&self) -> Result<(), ConfigError> {
+        let mut problems = Vec::new();
+
+        let mut addrs = vec![("addr", &self.addr)];
+        addrs.extend(self.metrics.addr.iter().map(|addr| ("metrics.addr", addr)));
+        addrs.extend(
+            self.discovery
+                .addr
+                .iter()
+                .map(|addr| ("discovery.addr", addr)),
+        );
+        for (name, addr) in addrs {
+            match addr.rsplit_once(':') {
+                Some((host, port)) if !host.is_empty() && port.parse::<u16>().is_ok() => {}
+                _ => problems.push(format!("{} `{}` must be `host:port`", name, addr)),
+            }
+        }
+        if self.discovery.name.trim().is_empty() {
+            problems.push("discovery.name is empty".into());
+        }
+        if self.storage.as_os_str().is_empty() {
+            problems.push("storage path is empty".into());
+        }
+        if self.limits.max_connections == 0 {
+            problems.push("limits.max_connections must be positive".into());
+        }
+        if self.limits.max_connections > MAX_CONNECTIONS {
+            problems.push(format!(
+                "limits.max_connections must be at most {}",
+                MAX_CONNECTIONS
+            ));
+        }
+        for (name, secs) in [
+            ("idle_timeout", self.limits.idle_timeout),
+            ("request_timeout", self.limits.request_timeout),
+        ] {
+            if secs == 0 {
+                problems.push(format!("limits.{} must be positive", name));
+            }
+        }
+        let limits = &self.rate_limit;
+        let mut rates = vec![
+            (
+                "rate_limit.per_connection".to_string(),
+                limits.per_connection,
+            ),
+            ("rate_limit.per_ip".to_string(), limits.per_ip),
+        ];
+        // Same commands as router built by main, plus `auth` handled before routing.
+        let known = handler::commands();
+        let audited = |command: &str| command == HISTORY_COMMAND && self.audit_log.is_some();
+        for (command, own) in &limits.commands {
+            if command != "auth" && known.command(command).is_none() && !audited(command) {
+                problems.push(format!(
+                    "rate_limit.commands.{} is unknown command",
+                    command
+                ));
+            }
+            let prefix = format!("rate_limit.commands.{}", command);
+            rates.push((format!("{}.per_connection", prefix), own.per_connection));
+            rates.push((format!("{}.per_ip", prefix), own.per_ip));
+        }
+        for (name, rate) in rates {
+            if matches!(rate, Some(rate) if !rate.is_valid()) {
+                problems.push(format!("{} must have positive rate and burst", name));
+            }
+        }
+        if matches!(&self.auth.token, Some(token) if token.is_empty()) {
+            problems.push("auth.token is empty, remove it to disable auth".into());
+        }
+        let mut tokens: Vec<_> = self.auth.token.iter().collect();
+        for (user, token) in &self.auth.users {
+            if token.is_empty() {
+                problems.push(format!("auth.users.{} has empty token", user));
+            } else if tokens.contains(&token) {
+                problems.push(format!("auth.users.{} token is already used", user));
+            }
+            tokens.push(token);
+        }
+
+        match problems.is_empty() {
+            true => Ok(()),
+            false => Err(ConfigError::Invalid(Problems(problems))),
+        }
+    }
+
+    /// Config in TOML format with secrets hidden.
+    pub fn to_toml(&self) -> String {
+        let mut shown = toml::Value::try_from(self).expect("config is always serializable");
+        if let Some(auth) = shown.get_mut("auth") {
+            if let Some(token) = auth.get_mut("token") {
+                *token = "<hidden>".into();
+            }
+            if let Some(users) = auth.get_mut("users").and_then(|u| u.as_table_mut()) {
+                for (_, token) in users.iter_mut() {
+                    *token = "<hidden>".into();
+                }
+            }
+        }
+        toml::to_string(&shown).expect("config is always serializable")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::{Args, Config, ConfigError, LogLevel};
+    use clap::Parser;
+    use std::fs;
+
+    #[test]
+    fn file_then_flags() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("server.toml");
+        let file = r#"
+            addr = "0.0.0.0:4000"
+            storage = "/var/lib/home.log"
+
+            [limits]
+            max_connections = 10
+            idle_timeout = 60
+
+            [auth]
+            token = "secret"
+            users = { alice = "alice-secret" }
+
+            [rate_limit]
+            per_ip = { per_second = 10.0, burst = 20 }
+
+            [rate_limit.commands.toggle_socket]
+            per_connection = { per_second = 1.0, burst = 2 }
+        "#;
+        fs::write(&path, file).unwrap();
+
+        let config_arg = format!("--config={}", path.display());
+        let args = Args::parse_from(["server", &config_arg, "--max-connections=20"]);
+        let config = Config::load(&args).unwrap();
+
+        assert_eq!(config.addr, "0.0.0.0:4000");
+        assert_eq!(config.limits.max_connections, 20);
+        assert_eq!(config.limits.idle_timeout, 60);
+        assert_eq!(config.limits.request_timeout, 10);
+        assert_eq!(config.auth.token.as_deref(), Some("secret"));
+        assert_eq!(config.logging.level, LogLevel::Info);
+        let toggle = &config.rate_limit.commands["toggle_socket"];
+        assert_eq!(toggle.per_connection.map(|r| r.burst), Some(2));
+        assert_eq!(config.auth.users["alice"], "alice-secret");
+        assert!(!config.to_toml().contains("secret"));
+    }
+
+    #[test]
+    fn invalid_values_are_reported() {
+        let args = Args::parse_from([
+            "server",
+            "--addr=localhost",
+            "--max-connections=0",
+            "--auth-token=",
+            "--discovery-addr=55332",
+        ]);
+        let err = Config::load(&args).unwrap_err();
+
+        let ConfigError::Invalid(problems) = err else {
+            panic!("unexpected error: {}", err);
+        };
+        assert_eq!(problems.0.len(), 4);
+
+        let max = format!("--max-connections={}", usize::MAX);
+        let args = Args::parse_from(["server", "--addr=localhost:0", &max]);
+        let err = Config::load(&args).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("limits.max_connections must be at most"));
+    }
+
+    #[test]
+    fn unknown_rate_limited_command_is_reported() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("server.toml");
+        let file = r#"
+            [rate_limit.commands.auth]
+            per_ip = { per_second = 1.0, burst = 5 }
+
+            [rate_limit.commands.toggle_sockets]
+            per_connection = { per_second = 1.0, burst = 2 }
+        "#;
+        fs::write(&path, file).unwrap();
+
+        let args = Args {
+            config: Some(path),
+            ..Args::default()
+        };
+        let ConfigError::Invalid(problems) = Config::load(&args).unwrap_err() else {
+            panic!("config must be invalid");
+        };
+        assert_eq!(
+            problems.0,
+            ["rate_limit.commands.toggle_sockets is unknown command"]
+        );
+    }
+
+    #[test]
+    fn history_is_known_with_audit_log() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("server.toml");
+        let file = r#"
+            [rate_limit.commands.history]
+            per_connection = { per_second = 1.0, burst = 2 }
+        "#;
+        fs::write(&path, file).unwrap();
+
+        let args = Args {
+            config: Some(path.clone()),
+            ..Args::default()
+        };
+        let err = Config::load(&args).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("rate_limit.commands.history is unknown command"));
+
+        let args = Args {
+            config: Some(path),
+            audit_log: Some(dir.path().join("audit.log")),
+            ..Args::default()
+        };
+        let config = Config::load(&args).unwrap();
+        assert!(config.rate_limit.commands.contains_key("history"));
+    }
+
+    #[test]
+    fn legacy_addr_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("addr");
+        assert_eq!(Config::legacy(&path).addr, Config::default().addr);
+
+        fs::write(&path, "0.0.0.0:4000\n").unwrap();
+        assert_eq!(Config::legacy(&path).addr, "0.0.0.0:4000");
+    }
+
+    #[test]
+    fn unknown_keys_are_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("server.toml");
+        fs::write(&path, "[limits]\nmax_conections = 10\n").unwrap();
+
+        let args = Args {
+            config: Some(path),
+            ..Args::default()
+        };
+        assert!(matches!(Config::load(&args), Err(ConfigError::Parse(..))));
+    }
+}
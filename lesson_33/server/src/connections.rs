@@ -0,0 +1,72 @@
+use std::sync::Arc;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+/// Most connections semaphore can count and wait for at once.
+pub const MAX_CONNECTIONS: usize = if Semaphore::MAX_PERMITS < u32::MAX as usize {
+    Semaphore::MAX_PERMITS
+} else {
+    u32::MAX as usize
+};
+
+/// Limits number of concurrent client connections and tracks active ones.
+#[derive(Clone)]
+pub struct Connections {
+    slots: Arc<Semaphore>,
+    max: usize,
+}
+
+impl Connections {
+    /// Allows up to `max` connections, at most [`MAX_CONNECTIONS`].
+    pub fn new(max: usize) -> Self {
+        let max = max.min(MAX_CONNECTIONS);
+        Self {
+            slots: Arc::new(Semaphore::new(max)),
+            max,
+        }
+    }
+
+    /// Takes a free connection slot, if any. Slot is released when returned permit is dropped.
+    pub fn try_acquire(&self) -> Option<OwnedSemaphorePermit> {
+        self.slots.clone().try_acquire_owned().ok()
+    }
+
+    /// Number of currently occupied slots.
+    pub fn active(&self) -> usize {
+        self.max - self.slots.available_permits()
+    }
+
+    /// Waits until all slots are released.
+    pub async fn drained(&self) {
+        let _all = self
+            .slots
+            .acquire_many(u32::try_from(self.max).expect("max is at most MAX_CONNECTIONS"))
+            .await
+            .expect("connection slots are never closed");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Connections;
+    use std::time::Duration;
+    use tokio::time;
+
+    #[tokio::test]
+    async fn active_and_drained() {
+        let connections = Connections::new(2);
+        let first = connections.try_acquire().unwrap();
+        let second = connections.try_acquire().unwrap();
+        assert_eq!(connections.active(), 2);
+        assert!(connections.try_acquire().is_none());
+
+        drop(first);
+        assert_eq!(connections.active(), 1);
+
+        let drained = time::timeout(Duration::from_millis(50), connections.drained()).await;
+        assert!(drained.is_err());
+
+        drop(second);
+        connections.drained().await;
+        assert_eq!(connections.active(), 0);
+    }
+}
@@ -1,146 +1,637 @@
-use crate::home::Home;
-use std::str::Split;
+use crate::audit::{AuditLog, AuditRecord};
+use crate::home::{Home, HomeError};
+use crate::router::{handler, Router, Target};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::task;
 
-pub struct Request<'a>(Split<'a, &'a str>);
+/// Separates ids in response to `list_*` commands.
+pub const LIST_SEPARATOR: &str = "|||";
+
+/// Arguments of request, yet unread part is kept.
+#[derive(Clone, Copy)]
+pub struct Request<'a>(&'a str);
 
 impl<'a> Request<'a> {
     pub fn new(s: &'a str) -> Self {
-        Self(s.split("|||"))
+        Self(s)
     }
 
+    /// Next argument, empty if there are none left.
     pub fn next(&mut self) -> &'a str {
-        self.0.next().unwrap_or("")
+        match self.0.split_once("|||") {
+            Some((arg, rest)) => {
+                self.0 = rest;
+                arg
+            }
+            None => std::mem::take(&mut self.0),
+        }
+    }
+}
+
+/// Identity of clients when authentication is disabled.
+const ANONYMOUS: &str = "anonymous";
+
+/// Tokens accepted by `auth` command, mapped to identities of their owners.
+#[derive(Default)]
+pub struct Credentials(HashMap<String, Arc<str>>);
+
+impl Credentials {
+    pub fn with(mut self, token: &str, identity: &str) -> Self {
+        self.0.insert(token.into(), identity.into());
+        self
     }
 }
 
 pub struct RequestHandler {
     home: Home,
+    router: Arc<Router>,
+    credentials: Option<Arc<Credentials>>,
+    /// Set once client is authorized.
+    identity: Option<Arc<str>>,
+    peer: String,
+    audit: Option<Arc<AuditLog>>,
 }
 
 impl RequestHandler {
     pub fn new(home: Home) -> Self {
-        Self { home }
+        Self {
+            home,
+            router: Arc::new(commands()),
+            credentials: None,
+            identity: Some(ANONYMOUS.into()),
+            peer: "unknown".into(),
+            audit: None,
+        }
     }
 
-    pub fn handle(&mut self, mut request: Request) -> String {
-        let command = request.next();
-        match command {
-            "create_socket" => self.create_socket(request),
-            "fetch_socket" => self.fetch_socket(request),
-            "toggle_socket" => self.toggle_socket(request),
-            "create_thermo" => self.create_thermo(request),
-            "fetch_thermo" => self.fetch_thermo(request),
-            "set_thermo" => self.set_thermo(request),
-            _ => "Bad command".into(),
-        }
+    /// Serves commands registered in `router` instead of default [`commands`].
+    pub fn with_router(mut self, router: Arc<Router>) -> Self {
+        self.router = router;
+        self
     }
 
-    fn fetch_socket(&self, mut request: Request) -> String {
-        let socket_id = request.next();
-        if socket_id.is_empty() {
-            return "Provide socket id".into();
+    /// Requires client to send `auth` command with one of tokens in `credentials`
+    /// before any other request.
+    pub fn with_credentials(mut self, credentials: Option<Arc<Credentials>>) -> Self {
+        if credentials.is_some() {
+            self.identity = None;
         }
+        self.credentials = credentials;
+        self
+    }
 
-        match self.home.socket_info(socket_id.into()) {
-            Some(info) => info,
-            None => "Unknown socket".into(),
-        }
+    /// Records changes made by client from `peer` to `audit` log.
+    pub fn with_audit(mut self, audit: Option<Arc<AuditLog>>, peer: String) -> Self {
+        self.audit = audit;
+        self.peer = peer;
+        self
     }
 
-    fn fetch_thermo(&self, mut request: Request) -> String {
-        let thermo_id = request.next();
-        if thermo_id.is_empty() {
-            return "Provide thermo id".into();
+    /// Static name of supported command, useful as a metrics label.
+    pub fn command(&self, command: &str) -> Option<&'static str> {
+        match command {
+            "auth" => Some("auth"),
+            command => self.router.command(command),
         }
+    }
 
-        match self.home.thermo_info(thermo_id.into()) {
-            Some(info) => info,
-            None => "Unknown thermo".into(),
+    pub async fn handle(&mut self, mut request: Request<'_>) -> String {
+        let command = request.next();
+        if command == "auth" {
+            return self.auth(request);
+        }
+        let identity = match &self.identity {
+            Some(identity) => identity.clone(),
+            None => return "Unauthorized".into(),
+        };
+
+        let audited = self.audit.as_ref().zip(self.router.changes(command));
+        let Some((audit, target)) = audited else {
+            return self.dispatch(command, request).await;
+        };
+
+        // No other audited change can happen between reading `before` and `after`.
+        let audited_changes = audit.lock().await;
+        let mut args = request;
+        let (id, new_id) = (args.next(), args.next());
+        let before = self.state(target, id);
+        let response = self.dispatch(command, request).await;
+        let renamed = command.starts_with("rename_") && self.state(target, id).is_none();
+        let after = self.state(target, if renamed { new_id } else { id });
+
+        let record = AuditRecord {
+            time: AuditRecord::now(),
+            peer: self.peer.clone(),
+            identity: identity.to_string(),
+            command: command.into(),
+            target: id.into(),
+            before,
+            after,
+            response: response.clone(),
+        };
+        let audit = audit.clone();
+        let written = task::spawn_blocking(move || {
+            let _audited_changes = audited_changes;
+            audit.record(&record)
+        });
+        match written.await {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => tracing::error!(error = %e, "failed to write audit log"),
+            Err(e) => tracing::error!(error = %e, "audit log writer failed"),
         }
+        response
     }
 
-    fn create_socket(&mut self, mut request: Request) -> String {
-        let socket_id = request.next();
-        if socket_id.is_empty() {
-            return "Provide socket id".into();
+    async fn dispatch(&self, command: &str, request: Request<'_>) -> String {
+        match self.router.dispatch(&self.home, command, request).await {
+            Some(response) => response,
+            None => "Bad command".into(),
         }
+    }
 
-        let power = request.next();
-        if power.is_empty() {
-            return "Provide socket power".into();
+    /// State of changed object to record in audit log.
+    fn state(&self, target: Target, id: &str) -> Option<String> {
+        match target {
+            Target::Socket => self.home.socket_info(id.into()),
+            Target::Thermo => self.home.thermo_info(id.into()),
+            Target::Layout => None,
         }
+    }
 
-        let state = request.next();
-        if state.is_empty() {
-            return "Provide socket state".into();
+    fn auth(&mut self, mut request: Request) -> String {
+        let token = request.next();
+        self.identity = match &self.credentials {
+            Some(credentials) => credentials.0.get(token).cloned(),
+            None => Some(ANONYMOUS.into()),
+        };
+
+        match self.identity {
+            Some(_) => "Authorized".into(),
+            None => "Bad token".into(),
         }
+    }
+}
 
-        let power_value = power.parse().unwrap_or(0);
+/// Router serving all smart home commands.
+pub fn commands() -> Router {
+    macro_rules! route {
+        ($router:expr, $command:ident) => {
+            $router.route(
+                stringify!($command),
+                handler(|home, r| Box::pin($command(home, r))),
+            )
+        };
+        ($router:expr, $command:ident => $target:ident) => {
+            $router.route_change(
+                stringify!($command),
+                Target::$target,
+                handler(|home, r| Box::pin($command(home, r))),
+            )
+        };
+    }
+    // Commands marked with target change it, which is recorded to audit log.
+    macro_rules! routes {
+        ($($command:ident $(=> $target:ident)?),* $(,)?) => {{
+            let router = Router::default();
+            $(let router = route!(router, $command $(=> $target)?);)*
+            router
+        }};
+    }
 
-        match self
-            .home
-            .create_socket(socket_id.into(), power_value, state == "true")
-        {
-            Some(r) => format!("Socket `{}` created", r),
-            None => format!("Socket `{}` already exists", socket_id),
-        }
+    routes![
+        create_socket => Socket,
+        fetch_socket,
+        socket_version,
+        toggle_socket => Socket,
+        set_socket_state => Socket,
+        set_power => Socket,
+        meter_socket,
+        create_thermo => Thermo,
+        fetch_thermo,
+        set_thermo => Thermo,
+        list_sockets,
+        list_thermos,
+        delete_socket => Socket,
+        delete_thermo => Thermo,
+        rename_socket => Socket,
+        rename_thermo => Thermo,
+        list_houses,
+        create_house => Layout,
+        delete_house => Layout,
+        list_rooms,
+        create_room => Layout,
+        delete_room => Layout,
+        place_socket => Socket,
+        place_thermo => Thermo,
+        report,
+    ]
+}
+
+async fn list_sockets(home: &Home, _: Request<'_>) -> String {
+    home.socket_ids().join(LIST_SEPARATOR)
+}
+
+async fn list_thermos(home: &Home, _: Request<'_>) -> String {
+    home.thermo_ids().join(LIST_SEPARATOR)
+}
+
+async fn list_houses(home: &Home, _: Request<'_>) -> String {
+    home.house_names().join(LIST_SEPARATOR)
+}
+
+/// Replies `id,state,power`, e.g. `kettle,true,2000`.
+async fn fetch_socket(home: &Home, mut request: Request<'_>) -> String {
+    let socket_id = request.next();
+    if socket_id.is_empty() {
+        return "Provide socket id".into();
     }
 
-    fn create_thermo(&mut self, mut request: Request) -> String {
-        let thermo_id = request.next();
-        if thermo_id.is_empty() {
-            return "Provide thermo id".into();
-        }
+    match home.socket_info(socket_id.into()) {
+        Some(info) => info,
+        None => "Unknown socket".into(),
+    }
+}
 
-        let temp = request.next();
-        if temp.is_empty() {
-            return "Provide thermo power".into();
-        }
+/// Replies `id,version`, version to pass to `set_socket_state`.
+async fn socket_version(home: &Home, mut request: Request<'_>) -> String {
+    let socket_id = request.next();
+    if socket_id.is_empty() {
+        return "Provide socket id".into();
+    }
 
-        let temp_value = temp.parse().unwrap_or(0);
-        match self.home.create_thermo(thermo_id.into(), temp_value) {
-            Some(r) => format!("Thermo `{}` created", r),
-            None => format!("Thermo `{}` already exists", thermo_id),
-        }
+    match home.socket_version(socket_id) {
+        Some(version) => format!("{},{}", socket_id, version),
+        None => "Unknown socket".into(),
     }
+}
 
-    fn toggle_socket(&mut self, mut request: Request) -> String {
-        let socket_id = request.next();
-        if socket_id.is_empty() {
-            return "Select socket id".into();
-        }
+async fn fetch_thermo(home: &Home, mut request: Request<'_>) -> String {
+    let thermo_id = request.next();
+    if thermo_id.is_empty() {
+        return "Provide thermo id".into();
+    }
 
-        match self.home.toggle_socket(socket_id) {
-            Some(_) => format!("Socket `{}` toggled", socket_id),
-            None => "Bad socket".into(),
-        }
+    match home.thermo_info(thermo_id.into()) {
+        Some(info) => info,
+        None => "Unknown thermo".into(),
     }
+}
 
-    fn set_thermo(&mut self, mut request: Request) -> String {
-        let thermo_id = request.next();
-        if thermo_id.is_empty() {
-            return "Select thermo id".into();
-        }
+async fn create_socket(home: &Home, mut request: Request<'_>) -> String {
+    let socket_id = request.next();
+    if socket_id.is_empty() {
+        return "Provide socket id".into();
+    }
 
-        let temp = request.next();
-        if temp.is_empty() {
-            return "Provide thermo power".into();
-        }
+    let power = request.next();
+    if power.is_empty() {
+        return "Provide socket power".into();
+    }
 
-        let temp_value = temp.parse().unwrap_or(0);
-        match self.home.set_thermo(thermo_id, temp_value) {
-            Some(_) => format!("Thermo `{}` set temp {}", thermo_id, temp_value),
-            None => "Bad thermo".into(),
-        }
+    let state = request.next();
+    if state.is_empty() {
+        return "Provide socket state".into();
+    }
+
+    let power_value = power.parse().unwrap_or(0);
+
+    match home
+        .create_socket(socket_id.into(), power_value, state == "true")
+        .await
+    {
+        Ok(r) => format!("Socket `{}` created", r),
+        Err(HomeError::AlreadyExists) => format!("Socket `{}` already exists", socket_id),
+        Err(e) => storage_error(e),
+    }
+}
+
+async fn set_socket_state(home: &Home, mut request: Request<'_>) -> String {
+    let socket_id = request.next();
+    if socket_id.is_empty() {
+        return "Select socket id".into();
+    }
+
+    let state = match request.next().parse() {
+        Ok(state) => state,
+        Err(_) => return "Provide socket state [true/false]".into(),
+    };
+
+    let version = match request.next() {
+        "" => None,
+        version => match version.parse() {
+            Ok(version) => Some(version),
+            Err(_) => return "Bad socket version".into(),
+        },
+    };
+
+    match home.set_socket_state(socket_id, state, version).await {
+        Ok(version) => format!(
+            "Socket `{}` state is {}, version {}",
+            socket_id, state, version
+        ),
+        Err(HomeError::NotFound) => "Unknown socket".into(),
+        Err(HomeError::VersionMismatch(current)) => format!(
+            "Socket `{}` was changed, current version is {}",
+            socket_id, current
+        ),
+        Err(e) => storage_error(e),
+    }
+}
+
+async fn set_power(home: &Home, mut request: Request<'_>) -> String {
+    let socket_id = request.next();
+    if socket_id.is_empty() {
+        return "Select socket id".into();
+    }
+
+    let power = match request.next().parse() {
+        Ok(power) => power,
+        Err(_) => return "Provide socket power".into(),
+    };
+
+    match home.set_socket_power(socket_id, power).await {
+        Ok(_) => format!("Socket `{}` power set to {}", socket_id, power),
+        Err(HomeError::NotFound) => "Unknown socket".into(),
+        Err(e) => storage_error(e),
+    }
+}
+
+/// Replies `id,current,energy` in watts and watt-hours.
+///
+/// Energy is counted in memory since the socket was created or the server started,
+/// it resets to 0 after restart.
+async fn meter_socket(home: &Home, mut request: Request<'_>) -> String {
+    let socket_id = request.next();
+    if socket_id.is_empty() {
+        return "Provide socket id".into();
+    }
+
+    match home.socket_metering(socket_id) {
+        Some(metering) => metering,
+        None => "Unknown socket".into(),
+    }
+}
+
+async fn create_thermo(home: &Home, mut request: Request<'_>) -> String {
+    let thermo_id = request.next();
+    if thermo_id.is_empty() {
+        return "Provide thermo id".into();
+    }
+
+    let temp = request.next();
+    if temp.is_empty() {
+        return "Provide thermo power".into();
+    }
+
+    let temp_value = temp.parse().unwrap_or(0);
+    match home.create_thermo(thermo_id.into(), temp_value).await {
+        Ok(r) => format!("Thermo `{}` created", r),
+        Err(HomeError::AlreadyExists) => format!("Thermo `{}` already exists", thermo_id),
+        Err(e) => storage_error(e),
+    }
+}
+
+async fn toggle_socket(home: &Home, mut request: Request<'_>) -> String {
+    let socket_id = request.next();
+    if socket_id.is_empty() {
+        return "Select socket id".into();
+    }
+
+    match home.toggle_socket(socket_id).await {
+        Ok(_) => format!("Socket `{}` toggled", socket_id),
+        Err(HomeError::NotFound) => "Bad socket".into(),
+        Err(e) => storage_error(e),
+    }
+}
+
+async fn set_thermo(home: &Home, mut request: Request<'_>) -> String {
+    let thermo_id = request.next();
+    if thermo_id.is_empty() {
+        return "Select thermo id".into();
+    }
+
+    let temp = request.next();
+    if temp.is_empty() {
+        return "Provide thermo power".into();
+    }
+
+    let temp_value = temp.parse().unwrap_or(0);
+    match home.set_thermo(thermo_id, temp_value).await {
+        Ok(_) => format!("Thermo `{}` set temp {}", thermo_id, temp_value),
+        Err(HomeError::NotFound) => "Bad thermo".into(),
+        Err(e) => storage_error(e),
+    }
+}
+
+async fn delete_socket(home: &Home, mut request: Request<'_>) -> String {
+    let socket_id = request.next();
+    if socket_id.is_empty() {
+        return "Select socket id".into();
+    }
+
+    match home.delete_socket(socket_id).await {
+        Ok(_) => format!("Socket `{}` deleted", socket_id),
+        Err(HomeError::NotFound) => "Unknown socket".into(),
+        Err(e) => storage_error(e),
+    }
+}
+
+async fn delete_thermo(home: &Home, mut request: Request<'_>) -> String {
+    let thermo_id = request.next();
+    if thermo_id.is_empty() {
+        return "Select thermo id".into();
+    }
+
+    match home.delete_thermo(thermo_id).await {
+        Ok(_) => format!("Thermo `{}` deleted", thermo_id),
+        Err(HomeError::NotFound) => "Unknown thermo".into(),
+        Err(e) => storage_error(e),
+    }
+}
+
+async fn rename_socket(home: &Home, mut request: Request<'_>) -> String {
+    let socket_id = request.next();
+    if socket_id.is_empty() {
+        return "Select socket id".into();
+    }
+
+    let new_id = request.next();
+    if new_id.is_empty() {
+        return "Provide new socket id".into();
+    }
+
+    match home.rename_socket(socket_id, new_id.into()).await {
+        Ok(r) => format!("Socket `{}` renamed to `{}`", socket_id, r),
+        Err(HomeError::NotFound) => "Unknown socket".into(),
+        Err(HomeError::AlreadyExists) => format!("Socket `{}` already exists", new_id),
+        Err(e) => storage_error(e),
+    }
+}
+
+async fn rename_thermo(home: &Home, mut request: Request<'_>) -> String {
+    let thermo_id = request.next();
+    if thermo_id.is_empty() {
+        return "Select thermo id".into();
+    }
+
+    let new_id = request.next();
+    if new_id.is_empty() {
+        return "Provide new thermo id".into();
+    }
+
+    match home.rename_thermo(thermo_id, new_id.into()).await {
+        Ok(r) => format!("Thermo `{}` renamed to `{}`", thermo_id, r),
+        Err(HomeError::NotFound) => "Unknown thermo".into(),
+        Err(HomeError::AlreadyExists) => format!("Thermo `{}` already exists", new_id),
+        Err(e) => storage_error(e),
+    }
+}
+
+async fn create_house(home: &Home, mut request: Request<'_>) -> String {
+    let house = request.next();
+    if house.is_empty() {
+        return "Provide house name".into();
+    }
+
+    match home.create_house(house).await {
+        Ok(r) => format!("House `{}` created", r),
+        Err(HomeError::AlreadyExists) => format!("House `{}` already exists", house),
+        Err(e) => layout_error(e),
     }
 }
 
+async fn delete_house(home: &Home, mut request: Request<'_>) -> String {
+    let house = request.next();
+    if house.is_empty() {
+        return "Select house".into();
+    }
+
+    match home.delete_house(house).await {
+        Ok(r) => format!("House `{}` deleted", r),
+        Err(HomeError::NotEmpty) => format!("House `{}` still has rooms", house),
+        Err(e) => layout_error(e),
+    }
+}
+
+async fn list_rooms(home: &Home, mut request: Request<'_>) -> String {
+    let house = request.next();
+    if house.is_empty() {
+        return "Select house".into();
+    }
+
+    match home.room_names(house) {
+        Ok(rooms) => rooms.join(LIST_SEPARATOR),
+        Err(e) => layout_error(e),
+    }
+}
+
+async fn create_room(home: &Home, mut request: Request<'_>) -> String {
+    let house = request.next();
+    if house.is_empty() {
+        return "Select house".into();
+    }
+
+    let room = request.next();
+    if room.is_empty() {
+        return "Provide room name".into();
+    }
+
+    match home.create_room(house, room).await {
+        Ok(r) => format!("Room `{}` created in house `{}`", r, house),
+        Err(HomeError::AlreadyExists) => format!("Room `{}` already exists", room),
+        Err(e) => layout_error(e),
+    }
+}
+
+async fn delete_room(home: &Home, mut request: Request<'_>) -> String {
+    let house = request.next();
+    if house.is_empty() {
+        return "Select house".into();
+    }
+
+    let room = request.next();
+    if room.is_empty() {
+        return "Select room".into();
+    }
+
+    match home.delete_room(house, room).await {
+        Ok(r) => format!("Room `{}` deleted", r),
+        Err(HomeError::NotEmpty) => format!("Room `{}` still has devices", room),
+        Err(e) => layout_error(e),
+    }
+}
+
+async fn place_socket(home: &Home, mut request: Request<'_>) -> String {
+    let socket_id = request.next();
+    if socket_id.is_empty() {
+        return "Select socket id".into();
+    }
+
+    let (house, room) = (request.next(), request.next());
+    if house.is_empty() || room.is_empty() {
+        return "Select house and room".into();
+    }
+
+    match home.place_socket(socket_id, house, room).await {
+        Ok(r) => format!("Socket `{}` placed in room `{}`", r, room),
+        Err(HomeError::NotFound) => "Unknown socket".into(),
+        Err(e) => layout_error(e),
+    }
+}
+
+async fn place_thermo(home: &Home, mut request: Request<'_>) -> String {
+    let thermo_id = request.next();
+    if thermo_id.is_empty() {
+        return "Select thermo id".into();
+    }
+
+    let (house, room) = (request.next(), request.next());
+    if house.is_empty() || room.is_empty() {
+        return "Select house and room".into();
+    }
+
+    match home.place_thermo(thermo_id, house, room).await {
+        Ok(r) => format!("Thermo `{}` placed in room `{}`", r, room),
+        Err(HomeError::NotFound) => "Unknown thermo".into(),
+        Err(e) => layout_error(e),
+    }
+}
+
+async fn report(home: &Home, mut request: Request<'_>) -> String {
+    let house = request.next();
+    if house.is_empty() {
+        return "Select house".into();
+    }
+
+    match home.report(house) {
+        Ok(report) => report,
+        Err(e) => layout_error(e),
+    }
+}
+
+fn layout_error(err: HomeError) -> String {
+    match err {
+        HomeError::NoSuchHouse(house) => format!("Unknown house `{}`", house),
+        HomeError::NoSuchRoom(room) => format!("Unknown room `{}`", room),
+        err => storage_error(err),
+    }
+}
+
+fn storage_error(err: HomeError) -> String {
+    tracing::error!(error = %err, "failed to persist change");
+    format!("Can't save changes: {}", err)
+}
+
 #[cfg(test)]
 mod tests {
+    use crate::audit::{AuditLog, History};
+    use crate::handler::{commands, Credentials};
     use crate::{Home, Request, RequestHandler};
+    use std::sync::Arc;
 
-    #[test]
-    fn sockets() {
+    #[tokio::test]
+    async fn sockets() {
         let home = Home::default();
         let mut handler = RequestHandler::new(home);
 
@@ -148,23 +639,23 @@ mod tests {
         let req_str = format!("create_socket|||{}|||{}|||{}", socket_id, 100, false);
         let req = Request::new(&req_str);
         assert_eq!(
-            handler.handle(req),
+            handler.handle(req).await,
             format!("Socket `{}` created", socket_id)
         );
 
         let req_str = format!("toggle_socket|||{}", socket_id);
         let req = Request::new(&req_str);
-        handler.handle(req);
+        handler.handle(req).await;
 
         let req_str = format!("fetch_socket|||{}", socket_id);
         let req = Request::new(&req_str);
-        let fetched = handler.handle(req);
+        let fetched = handler.handle(req).await;
 
         assert_eq!(fetched, "socket_1,true,100");
     }
 
-    #[test]
-    fn thermos() {
+    #[tokio::test]
+    async fn thermos() {
         let home = Home::default();
         let mut handler = RequestHandler::new(home);
 
@@ -172,18 +663,247 @@ mod tests {
         let req_str = format!("create_thermo|||{}|||{}", thermo_id, 100);
         let req = Request::new(&req_str);
         assert_eq!(
-            handler.handle(req),
+            handler.handle(req).await,
             format!("Thermo `{}` created", thermo_id)
         );
 
         let req_str = format!("set_thermo|||{}|||{}", thermo_id, 50);
         let req = Request::new(&req_str);
-        handler.handle(req);
+        handler.handle(req).await;
 
         let req_str = format!("fetch_thermo|||{}", thermo_id);
         let req = Request::new(&req_str);
-        let fetched = handler.handle(req);
+        let fetched = handler.handle(req).await;
 
         assert_eq!(fetched, "Thermo thermo_1 temperature is 50");
     }
+
+    #[tokio::test]
+    async fn list_rename_delete() {
+        let home = Home::default();
+        let mut handler = RequestHandler::new(home);
+
+        for req_str in [
+            "create_socket|||socket_2|||10|||true",
+            "create_socket|||socket_1|||20|||false",
+            "create_thermo|||thermo_1|||20",
+        ] {
+            handler.handle(Request::new(req_str)).await;
+        }
+        let listed = handler.handle(Request::new("list_sockets")).await;
+        assert_eq!(listed, "socket_1|||socket_2");
+
+        let req = Request::new("rename_socket|||socket_1|||socket_2");
+        assert_eq!(
+            handler.handle(req).await,
+            "Socket `socket_2` already exists"
+        );
+        let req = Request::new("rename_socket|||socket_1|||socket_3");
+        assert_eq!(
+            handler.handle(req).await,
+            "Socket `socket_1` renamed to `socket_3`"
+        );
+        let req = Request::new("fetch_socket|||socket_3");
+        assert_eq!(handler.handle(req).await, "socket_3,false,20");
+
+        let req = Request::new("delete_thermo|||thermo_1");
+        assert_eq!(handler.handle(req).await, "Thermo `thermo_1` deleted");
+        let req = Request::new("delete_thermo|||thermo_1");
+        assert_eq!(handler.handle(req).await, "Unknown thermo");
+        assert_eq!(handler.handle(Request::new("list_thermos")).await, "");
+    }
+
+    #[tokio::test]
+    async fn houses_and_rooms() {
+        let home = Home::default();
+        let mut handler = RequestHandler::new(home);
+
+        for req_str in [
+            "create_house|||home",
+            "create_room|||home|||kitchen",
+            "create_room|||home|||hall",
+            "create_socket|||kettle|||2000|||true",
+            "create_thermo|||fridge|||4",
+        ] {
+            handler.handle(Request::new(req_str)).await;
+        }
+
+        let req = Request::new("place_socket|||kettle|||home|||kitchen");
+        assert_eq!(
+            handler.handle(req).await,
+            "Socket `kettle` placed in room `kitchen`"
+        );
+        let req = Request::new("place_thermo|||fridge|||home|||garage");
+        assert_eq!(handler.handle(req).await, "Unknown room `garage`");
+        let req = Request::new("place_thermo|||fridge|||home|||kitchen");
+        handler.handle(req).await;
+        assert_eq!(
+            handler.handle(Request::new("list_rooms|||home")).await,
+            "hall|||kitchen"
+        );
+
+        let req = Request::new("delete_room|||home|||kitchen");
+        assert_eq!(
+            handler.handle(req).await,
+            "Room `kitchen` still has devices"
+        );
+        let req = Request::new("delete_house|||home");
+        assert_eq!(handler.handle(req).await, "House `home` still has rooms");
+
+        let report = handler.handle(Request::new("report|||home")).await;
+        let expected = "House home report:
+empty report for hall
+Room kitchen:
+kettle,true,2000
+Thermo fridge temperature is 4";
+        assert_eq!(report, expected);
+
+        handler
+            .handle(Request::new("rename_socket|||kettle|||teapot"))
+            .await;
+        handler.handle(Request::new("delete_thermo|||fridge")).await;
+        let report = handler.handle(Request::new("report|||home")).await;
+        assert!(report.ends_with("Room kitchen:\nteapot,true,2000"));
+        assert_eq!(
+            handler.handle(Request::new("report|||cabin")).await,
+            "Unknown house `cabin`"
+        );
+    }
+
+    #[tokio::test]
+    async fn set_socket_state() {
+        let home = Home::default();
+        let mut handler = RequestHandler::new(home);
+        handler
+            .handle(Request::new("create_socket|||socket_1|||100|||false"))
+            .await;
+
+        let req = Request::new("set_socket_state|||socket_1|||true|||0");
+        assert_eq!(
+            handler.handle(req).await,
+            "Socket `socket_1` state is true, version 1"
+        );
+        // Repeated request doesn't change anything.
+        let req = Request::new("set_socket_state|||socket_1|||true|||0");
+        assert_eq!(
+            handler.handle(req).await,
+            "Socket `socket_1` state is true, version 1"
+        );
+
+        // Other client changed state concurrently.
+        let req = Request::new("set_socket_state|||socket_1|||false|||0");
+        assert_eq!(
+            handler.handle(req).await,
+            "Socket `socket_1` was changed, current version is 1"
+        );
+
+        let req = Request::new("set_socket_state|||socket_1|||false");
+        assert_eq!(
+            handler.handle(req).await,
+            "Socket `socket_1` state is false, version 2"
+        );
+        let req = Request::new("fetch_socket|||socket_1");
+        assert_eq!(handler.handle(req).await, "socket_1,false,100");
+        let req = Request::new("socket_version|||socket_1");
+        assert_eq!(handler.handle(req).await, "socket_1,2");
+        let req = Request::new("set_socket_state|||socket_1|||on");
+        assert_eq!(
+            handler.handle(req).await,
+            "Provide socket state [true/false]"
+        );
+    }
+
+    #[tokio::test]
+    async fn power_and_metering() {
+        let home = Home::default();
+        let mut handler = RequestHandler::new(home);
+        handler
+            .handle(Request::new("create_socket|||socket_1|||100|||false"))
+            .await;
+
+        let req = Request::new("meter_socket|||socket_1");
+        assert_eq!(handler.handle(req).await, "socket_1,0,0.000");
+
+        let req = Request::new("set_power|||socket_1|||2000");
+        assert_eq!(
+            handler.handle(req).await,
+            "Socket `socket_1` power set to 2000"
+        );
+        handler
+            .handle(Request::new("toggle_socket|||socket_1"))
+            .await;
+        let req = Request::new("fetch_socket|||socket_1");
+        assert_eq!(handler.handle(req).await, "socket_1,true,2000");
+        let req = Request::new("meter_socket|||socket_1");
+        assert!(handler.handle(req).await.starts_with("socket_1,2000,"));
+
+        let req = Request::new("set_power|||socket_1|||much");
+        assert_eq!(handler.handle(req).await, "Provide socket power");
+        let req = Request::new("meter_socket|||socket_2");
+        assert_eq!(handler.handle(req).await, "Unknown socket");
+    }
+
+    #[tokio::test]
+    async fn auth() {
+        let home = Home::default();
+        let credentials = Credentials::default().with("secret", "alice");
+        let mut handler = RequestHandler::new(home).with_credentials(Some(Arc::new(credentials)));
+
+        let req = Request::new("list_sockets");
+        assert_eq!(handler.handle(req).await, "Unauthorized");
+        assert_eq!(
+            handler.handle(Request::new("auth|||guess")).await,
+            "Bad token"
+        );
+        assert_eq!(
+            handler.handle(Request::new("auth|||secret")).await,
+            "Authorized"
+        );
+        assert_eq!(handler.handle(Request::new("list_sockets")).await, "");
+    }
+
+    #[tokio::test]
+    async fn all_commands_are_handled() {
+        let mut handler = RequestHandler::new(Home::default());
+        for command in commands().commands() {
+            assert_ne!(handler.handle(Request::new(command)).await, "Bad command");
+        }
+        assert_eq!(handler.handle(Request::new("reboot")).await, "Bad command");
+    }
+
+    #[tokio::test]
+    async fn audit() {
+        let dir = tempfile::tempdir().unwrap();
+        let audit = Arc::new(AuditLog::open(dir.path().join("audit.log")).unwrap());
+        let router = commands().route("history", History(audit.clone()));
+        let credentials = Credentials::default().with("secret", "alice");
+        let mut handler = RequestHandler::new(Home::default())
+            .with_router(Arc::new(router))
+            .with_credentials(Some(Arc::new(credentials)))
+            .with_audit(Some(audit), "10.0.0.1:4000".into());
+
+        for req_str in [
+            "auth|||secret",
+            "create_socket|||socket_1|||100|||false",
+            "toggle_socket|||socket_1",
+            "fetch_socket|||socket_1",
+            "rename_socket|||socket_1|||socket_2",
+        ] {
+            handler.handle(Request::new(req_str)).await;
+        }
+
+        let history = handler.handle(Request::new("history|||socket_1")).await;
+        let lines: Vec<_> = history.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0]
+            .contains("alice@10.0.0.1:4000 create_socket socket_1: - -> socket_1,false,100"));
+        assert!(lines[1].ends_with(
+            "toggle_socket socket_1: socket_1,false,100 -> socket_1,true,100 (Socket `socket_1` toggled)"
+        ));
+        assert!(lines[2].contains("rename_socket socket_1: socket_1,true,100 -> socket_2,true,100"));
+
+        let history = handler.handle(Request::new("history|||socket_1|||1")).await;
+        assert!(history.contains("rename_socket"));
+        assert!(!history.contains('\n'));
+    }
 }
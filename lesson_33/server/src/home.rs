@@ -1,63 +1,524 @@
+use crate::layout::{DeviceKind, Layout};
+use crate::meter::Meter;
+use crate::storage::{Event, Storage};
 use dashmap::mapref::entry::Entry;
+use dashmap::mapref::one::RefMut;
 use dashmap::DashMap;
-use std::sync::Arc;
+use std::io;
+use std::sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use std::time::Instant;
+use thiserror::Error;
+use tokio::sync::{Mutex, OwnedMutexGuard};
+use tokio::task;
+
+pub type HomeResult<T> = Result<T, HomeError>;
+
+#[derive(Debug, Error)]
+pub enum HomeError {
+    #[error("already exists")]
+    AlreadyExists,
+    #[error("device not found")]
+    NotFound,
+    #[error("unknown house `{0}`")]
+    NoSuchHouse(String),
+    #[error("unknown room `{0}`")]
+    NoSuchRoom(String),
+    #[error("not empty")]
+    NotEmpty,
+    #[error("version mismatch, current version is {0}")]
+    VersionMismatch(u64),
+    #[error("{0}")]
+    Storage(#[from] io::Error),
+}
 
 #[derive(Default, Clone)]
 pub struct Home {
     sockets: Arc<DashMap<String, Socket>>,
     thermos: Arc<DashMap<String, Thermo>>,
+    // Device and layout locks are never held at once.
+    layout: Arc<RwLock<Layout>>,
+    storage: Option<Arc<dyn Storage>>,
+    /// Held from checking change until it is applied.
+    changes: Arc<Mutex<()>>,
 }
 
 impl Home {
+    /// Home backed by storage. State is restored from events stored before.
+    pub fn with_storage<S: Storage + 'static>(storage: S) -> io::Result<Self> {
+        let events = storage.load()?;
+        let home = Self::default();
+        for event in events {
+            home.replay(event)?;
+        }
+
+        Ok(Self {
+            storage: Some(Arc::new(storage)),
+            ..home
+        })
+    }
+
     pub fn socket_info(&self, socket_id: String) -> Option<String> {
         Some(self.sockets.get(&socket_id)?.info())
     }
 
+    /// Incremented on every state change, see `set_socket_state`.
+    pub fn socket_version(&self, socket_id: &str) -> Option<u64> {
+        Some(self.sockets.get(socket_id)?.version)
+    }
+
+    /// Power drawn by socket right now and energy it has drawn, see [`Socket::metering`].
+    pub fn socket_metering(&self, socket_id: &str) -> Option<String> {
+        Some(self.sockets.get(socket_id)?.metering())
+    }
+
     pub fn thermo_info(&self, thermo_id: String) -> Option<String> {
         Some(self.thermos.get(&thermo_id)?.info())
     }
 
-    pub fn create_socket(&self, socket_id: String, power: u64, state: bool) -> Option<String> {
-        let socket_entry = self.sockets.entry(socket_id.clone());
-        match socket_entry {
-            Entry::Occupied(_) => None,
-            Entry::Vacant(v) => {
-                let socket = Socket::new(&socket_id, power, state);
-                v.insert(socket);
-                Some(socket_id)
+    /// Ids of all sockets, sorted.
+    pub fn socket_ids(&self) -> Vec<String> {
+        let mut ids: Vec<_> = self.sockets.iter().map(|s| s.key().clone()).collect();
+        ids.sort();
+        ids
+    }
+
+    /// Ids of all thermos, sorted.
+    pub fn thermo_ids(&self) -> Vec<String> {
+        let mut ids: Vec<_> = self.thermos.iter().map(|t| t.key().clone()).collect();
+        ids.sort();
+        ids
+    }
+
+    pub async fn create_socket(
+        &self,
+        socket_id: String,
+        power: u64,
+        state: bool,
+    ) -> HomeResult<String> {
+        let event = Event::SocketCreated {
+            id: socket_id.clone(),
+            power,
+            state,
+        };
+        self.commit(event).await?;
+        Ok(socket_id)
+    }
+
+    pub async fn create_thermo(&self, thermo_id: String, temp: i64) -> HomeResult<String> {
+        let event = Event::ThermoCreated {
+            id: thermo_id.clone(),
+            temp,
+        };
+        self.commit(event).await?;
+        Ok(thermo_id)
+    }
+
+    pub async fn toggle_socket(&self, socket_id: &str) -> HomeResult<String> {
+        let event = Event::SocketToggled {
+            id: socket_id.into(),
+        };
+        self.commit(event).await?;
+        Ok(socket_id.into())
+    }
+
+    /// Sets socket state and returns its version after the change.
+    ///
+    /// With `expected_version` state is changed only if socket wasn't changed since that version.
+    /// Setting state socket already has is a no-op and always succeeds,
+    /// so retrying the same request is safe.
+    pub async fn set_socket_state(
+        &self,
+        socket_id: &str,
+        state: bool,
+        expected_version: Option<u64>,
+    ) -> HomeResult<u64> {
+        let changes = self.lock_changes().await;
+        let version = {
+            let socket = self.sockets.get(socket_id).ok_or(HomeError::NotFound)?;
+            if socket.state == state {
+                return Ok(socket.version);
+            }
+            match expected_version {
+                Some(version) if version != socket.version => {
+                    return Err(HomeError::VersionMismatch(socket.version))
+                }
+                _ => socket.version,
+            }
+        };
+
+        let event = Event::SocketStateSet {
+            id: socket_id.into(),
+            state,
+        };
+        self.commit_locked(changes, event).await?;
+        Ok(version + 1)
+    }
+
+    pub async fn set_socket_power(&self, socket_id: &str, power: u64) -> HomeResult<String> {
+        let event = Event::SocketPowerSet {
+            id: socket_id.into(),
+            power,
+        };
+        self.commit(event).await?;
+        Ok(socket_id.into())
+    }
+
+    pub async fn set_thermo(&self, thermo_id: &str, temp: i64) -> HomeResult<String> {
+        let event = Event::ThermoSet {
+            id: thermo_id.into(),
+            temp,
+        };
+        self.commit(event).await?;
+        Ok(thermo_id.into())
+    }
+
+    pub async fn delete_socket(&self, socket_id: &str) -> HomeResult<String> {
+        let event = Event::SocketDeleted {
+            id: socket_id.into(),
+        };
+        self.commit(event).await?;
+        Ok(socket_id.into())
+    }
+
+    pub async fn delete_thermo(&self, thermo_id: &str) -> HomeResult<String> {
+        let event = Event::ThermoDeleted {
+            id: thermo_id.into(),
+        };
+        self.commit(event).await?;
+        Ok(thermo_id.into())
+    }
+
+    pub async fn rename_socket(&self, socket_id: &str, new_id: String) -> HomeResult<String> {
+        let event = Event::SocketRenamed {
+            id: socket_id.into(),
+            new_id: new_id.clone(),
+        };
+        self.commit(event).await?;
+        Ok(new_id)
+    }
+
+    pub async fn rename_thermo(&self, thermo_id: &str, new_id: String) -> HomeResult<String> {
+        let event = Event::ThermoRenamed {
+            id: thermo_id.into(),
+            new_id: new_id.clone(),
+        };
+        self.commit(event).await?;
+        Ok(new_id)
+    }
+
+    /// Names of all houses, sorted.
+    pub fn house_names(&self) -> Vec<String> {
+        self.layout().house_names()
+    }
+
+    /// Names of rooms in house, sorted.
+    pub fn room_names(&self, house: &str) -> HomeResult<Vec<String>> {
+        let layout = self.layout();
+        Ok(layout.house(house)?.rooms.keys().cloned().collect())
+    }
+
+    pub async fn create_house(&self, house: &str) -> HomeResult<String> {
+        let event = Event::HouseCreated {
+            house: house.into(),
+        };
+        self.commit(event).await?;
+        Ok(house.into())
+    }
+
+    pub async fn delete_house(&self, house: &str) -> HomeResult<String> {
+        let event = Event::HouseDeleted {
+            house: house.into(),
+        };
+        self.commit(event).await?;
+        Ok(house.into())
+    }
+
+    pub async fn create_room(&self, house: &str, room: &str) -> HomeResult<String> {
+        let event = Event::RoomCreated {
+            house: house.into(),
+            room: room.into(),
+        };
+        self.commit(event).await?;
+        Ok(room.into())
+    }
+
+    pub async fn delete_room(&self, house: &str, room: &str) -> HomeResult<String> {
+        let event = Event::RoomDeleted {
+            house: house.into(),
+            room: room.into(),
+        };
+        self.commit(event).await?;
+        Ok(room.into())
+    }
+
+    pub async fn place_socket(
+        &self,
+        socket_id: &str,
+        house: &str,
+        room: &str,
+    ) -> HomeResult<String> {
+        let event = Event::SocketPlaced {
+            id: socket_id.into(),
+            house: house.into(),
+            room: room.into(),
+        };
+        self.commit(event).await?;
+        Ok(socket_id.into())
+    }
+
+    pub async fn place_thermo(
+        &self,
+        thermo_id: &str,
+        house: &str,
+        room: &str,
+    ) -> HomeResult<String> {
+        let event = Event::ThermoPlaced {
+            id: thermo_id.into(),
+            house: house.into(),
+            room: room.into(),
+        };
+        self.commit(event).await?;
+        Ok(thermo_id.into())
+    }
+
+    /// Report on every device in house, grouped by room.
+    ///
+    /// Same as `SmartHouse::create_report`, but rooms without devices are reported too.
+    pub fn report(&self, house: &str) -> HomeResult<String> {
+        // Layout is copied, so device locks are not taken while it is locked.
+        let rooms = self.layout().house(house)?.rooms.clone();
+
+        let mut report = format!("House {} report:", house);
+        for (name, room) in rooms {
+            let sockets = room
+                .sockets
+                .into_iter()
+                .filter_map(|id| self.socket_info(id));
+            let thermos = room
+                .thermos
+                .into_iter()
+                .filter_map(|id| self.thermo_info(id));
+            let devices: Vec<String> = sockets.chain(thermos).collect();
+            if devices.is_empty() {
+                report.push_str(&format!("\nempty report for {}", name));
+            } else {
+                report.push_str(&format!("\nRoom {}:\n{}", name, devices.join("\n")));
             }
         }
+
+        Ok(report)
+    }
+
+    fn layout(&self) -> RwLockReadGuard<'_, Layout> {
+        self.layout.read().unwrap_or_else(|e| e.into_inner())
     }
 
-    pub fn create_thermo(&self, thermo_id: String, temp: i64) -> Option<String> {
-        let thermo_entry = self.thermos.entry(thermo_id.clone());
-        match thermo_entry {
-            Entry::Occupied(_) => None,
-            Entry::Vacant(v) => {
-                let thermo = Thermo::new(&thermo_id, temp);
-                v.insert(thermo);
-                Some(thermo_id)
+    fn layout_mut(&self) -> RwLockWriteGuard<'_, Layout> {
+        self.layout.write().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Waits until no other change is in progress. Changes stay serialized until guard is dropped.
+    async fn lock_changes(&self) -> OwnedMutexGuard<()> {
+        self.changes.clone().lock_owned().await
+    }
+
+    async fn commit(&self, event: Event) -> HomeResult<()> {
+        let changes = self.lock_changes().await;
+        self.commit_locked(changes, event).await
+    }
+
+    /// Checks event against current state, stores it and only then applies it,
+    /// so state in memory never gets ahead of storage.
+    ///
+    /// Storage is written on blocking thread without holding any device or layout lock.
+    /// `changes` guard is released after event is applied, even if caller stops waiting,
+    /// so no other change is checked against stale state.
+    async fn commit_locked(&self, changes: OwnedMutexGuard<()>, event: Event) -> HomeResult<()> {
+        self.check(&event)?;
+        let home = self.clone();
+        let commit = move || {
+            let _changes = changes;
+            if let Some(storage) = &home.storage {
+                storage.append(&event)?;
             }
+            home.apply(event)
+        };
+        match self.storage {
+            Some(_) => task::spawn_blocking(commit)
+                .await
+                .map_err(io::Error::other)?,
+            None => commit(),
         }
     }
 
-    pub fn toggle_socket(&self, socket_id: &str) -> Option<String> {
-        let mut socket = self.sockets.get_mut(socket_id)?;
-        socket.toggle();
-        Some(socket_id.into())
+    /// Fails the same way as [`apply`](Self::apply) would, but changes nothing.
+    fn check(&self, event: &Event) -> HomeResult<()> {
+        let socket = |id: &str| match self.sockets.contains_key(id) {
+            true => Ok(()),
+            false => Err(HomeError::NotFound),
+        };
+        let thermo = |id: &str| match self.thermos.contains_key(id) {
+            true => Ok(()),
+            false => Err(HomeError::NotFound),
+        };
+        match event {
+            Event::SocketCreated { id, .. } if self.sockets.contains_key(id) => {
+                return Err(HomeError::AlreadyExists)
+            }
+            Event::ThermoCreated { id, .. } if self.thermos.contains_key(id) => {
+                return Err(HomeError::AlreadyExists)
+            }
+            Event::SocketCreated { .. } | Event::ThermoCreated { .. } => {}
+            Event::SocketToggled { id }
+            | Event::SocketStateSet { id, .. }
+            | Event::SocketPowerSet { id, .. }
+            | Event::SocketDeleted { id }
+            | Event::SocketPlaced { id, .. } => socket(id)?,
+            Event::ThermoSet { id, .. }
+            | Event::ThermoDeleted { id }
+            | Event::ThermoPlaced { id, .. } => thermo(id)?,
+            Event::SocketRenamed { id, new_id } => {
+                socket(id)?;
+                if new_id != id && self.sockets.contains_key(new_id) {
+                    return Err(HomeError::AlreadyExists);
+                }
+            }
+            Event::ThermoRenamed { id, new_id } => {
+                thermo(id)?;
+                if new_id != id && self.thermos.contains_key(new_id) {
+                    return Err(HomeError::AlreadyExists);
+                }
+            }
+            Event::HouseCreated { .. }
+            | Event::HouseDeleted { .. }
+            | Event::RoomCreated { .. }
+            | Event::RoomDeleted { .. } => {}
+        }
+        // Layout is small, changing its copy is the simplest way to validate.
+        let mut layout = self.layout().clone();
+        change_layout(&mut layout, event)
+    }
+
+    /// Changes state in memory, without storing event.
+    fn apply(&self, event: Event) -> HomeResult<()> {
+        match &event {
+            Event::SocketCreated { id, power, state } => match self.sockets.entry(id.clone()) {
+                Entry::Occupied(_) => return Err(HomeError::AlreadyExists),
+                Entry::Vacant(v) => drop(v.insert(Socket::new(id, *power, *state))),
+            },
+            Event::ThermoCreated { id, temp } => match self.thermos.entry(id.clone()) {
+                Entry::Occupied(_) => return Err(HomeError::AlreadyExists),
+                Entry::Vacant(v) => drop(v.insert(Thermo::new(id, *temp))),
+            },
+            Event::SocketToggled { id } => self.socket_mut(id)?.toggle(),
+            Event::SocketStateSet { id, state } => self.socket_mut(id)?.set_state(*state),
+            Event::SocketPowerSet { id, power } => self.socket_mut(id)?.set_power(*power),
+            Event::ThermoSet { id, temp } => self.thermo_mut(id)?.set_temp(*temp),
+            Event::SocketDeleted { id } => {
+                drop(self.sockets.remove(id).ok_or(HomeError::NotFound)?)
+            }
+            Event::ThermoDeleted { id } => {
+                drop(self.thermos.remove(id).ok_or(HomeError::NotFound)?)
+            }
+            Event::SocketRenamed { id, new_id } => rename(&self.sockets, id, new_id)?,
+            Event::ThermoRenamed { id, new_id } => rename(&self.thermos, id, new_id)?,
+            Event::SocketPlaced { id, .. } => drop(self.socket_mut(id)?),
+            Event::ThermoPlaced { id, .. } => drop(self.thermo_mut(id)?),
+            Event::HouseCreated { .. }
+            | Event::HouseDeleted { .. }
+            | Event::RoomCreated { .. }
+            | Event::RoomDeleted { .. } => {}
+        }
+        change_layout(&mut self.layout_mut(), &event)
+    }
+
+    fn socket_mut(&self, socket_id: &str) -> HomeResult<RefMut<'_, String, Socket>> {
+        self.sockets.get_mut(socket_id).ok_or(HomeError::NotFound)
+    }
+
+    fn thermo_mut(&self, thermo_id: &str) -> HomeResult<RefMut<'_, String, Thermo>> {
+        self.thermos.get_mut(thermo_id).ok_or(HomeError::NotFound)
+    }
+
+    fn replay(&self, event: Event) -> io::Result<()> {
+        match self.apply(event) {
+            Ok(_) => Ok(()),
+            Err(HomeError::Storage(e)) => Err(e),
+            Err(e) => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("inconsistent stored event: {}", e),
+            )),
+        }
+    }
+}
+
+/// Applies part of event concerning layout. Device must be already changed.
+fn change_layout(layout: &mut Layout, event: &Event) -> HomeResult<()> {
+    match event {
+        Event::HouseCreated { house } => return layout.create_house(house),
+        Event::HouseDeleted { house } => return layout.delete_house(house),
+        Event::RoomCreated { house, room } => return layout.create_room(house, room),
+        Event::RoomDeleted { house, room } => return layout.delete_room(house, room),
+        Event::SocketPlaced { id, house, room } => {
+            return layout.place(DeviceKind::Socket, id, house, room)
+        }
+        Event::ThermoPlaced { id, house, room } => {
+            return layout.place(DeviceKind::Thermo, id, house, room)
+        }
+        Event::SocketDeleted { id } => layout.remove_device(DeviceKind::Socket, id),
+        Event::ThermoDeleted { id } => layout.remove_device(DeviceKind::Thermo, id),
+        Event::SocketRenamed { id, new_id } => layout.rename_device(DeviceKind::Socket, id, new_id),
+        Event::ThermoRenamed { id, new_id } => layout.rename_device(DeviceKind::Thermo, id, new_id),
+        _ => {}
     }
+    Ok(())
+}
+
+trait Named {
+    fn rename(&mut self, name: &str);
+}
+
+/// Moves device to new key.
+///
+/// Shards holding both keys are locked for the whole move, lower shard index first, so
+/// concurrent renames can't deadlock and readers never see the device missing.
+fn rename<D: Named>(devices: &DashMap<String, D>, id: &str, new_id: &str) -> HomeResult<()> {
+    let from = devices.determine_map(id);
+    let to = devices.determine_map(new_id);
+    let shards = devices.shards();
+    let mut low = shards[from.min(to)].write();
+    let mut high = match from == to {
+        true => None,
+        false => Some(shards[from.max(to)].write()),
+    };
+    let (source, target) = match &mut high {
+        None => (&mut *low, None),
+        Some(high) if from < to => (&mut *low, Some(&mut **high)),
+        Some(high) => (&mut **high, Some(&mut *low)),
+    };
 
-    pub fn set_thermo(&self, thermo_id: &str, temp: i64) -> Option<String> {
-        let mut thermo = self.thermos.get_mut(thermo_id)?;
-        thermo.set_temp(temp);
-        Some(thermo_id.into())
+    if !source.contains_key(id) {
+        return Err(HomeError::NotFound);
     }
+    let taken = target.as_deref().unwrap_or(source).contains_key(new_id);
+    if taken && new_id != id {
+        return Err(HomeError::AlreadyExists);
+    }
+
+    let (_, mut device) = source.remove_entry(id).expect("presence checked above");
+    device.get_mut().rename(new_id);
+    target.unwrap_or(source).insert(new_id.into(), device);
+    Ok(())
 }
 
 pub struct Socket {
     name: String,
+    /// Power drawn when socket is on, in watts.
     power: u64,
     state: bool,
+    /// Incremented on every state change.
+    version: u64,
+    meter: Meter,
 }
 
 impl Socket {
@@ -66,18 +527,49 @@ impl Socket {
             name: String::from(name),
             power,
             state,
+            version: 0,
+            meter: Meter::new(Instant::now()),
         }
     }
 
     pub fn info(&self) -> String {
-        format!(
-            "{},{},{}",
-            self.name, self.state, self.power
-        )
+        format!("{},{},{}", self.name, self.state, self.power)
     }
 
     pub fn toggle(&mut self) {
-        self.state = !self.state;
+        self.set_state(!self.state);
+    }
+
+    pub fn set_state(&mut self, state: bool) {
+        self.meter.settle(self.current_power(), Instant::now());
+        self.state = state;
+        self.version += 1;
+    }
+
+    pub fn set_power(&mut self, power: u64) {
+        self.meter.settle(self.current_power(), Instant::now());
+        self.power = power;
+    }
+
+    /// Power drawn right now, in watts.
+    pub fn current_power(&self) -> u64 {
+        match self.state {
+            true => self.power,
+            false => 0,
+        }
+    }
+
+    /// Current power in watts and energy drawn in watt-hours: `socket_1,100,0.250`.
+    pub fn metering(&self) -> String {
+        let current = self.current_power();
+        let energy = self.meter.energy_at(current, Instant::now()) / 3600.0;
+        format!("{},{},{:.3}", self.name, current, energy)
+    }
+}
+
+impl Named for Socket {
+    fn rename(&mut self, name: &str) {
+        self.name = name.into();
     }
 }
 
@@ -103,24 +595,203 @@ impl Thermo {
     }
 }
 
+impl Named for Thermo {
+    fn rename(&mut self, name: &str) {
+        self.name = name.into();
+    }
+}
+
 #[cfg(test)]
 mod tests {
+    use crate::home::HomeError;
+    use crate::storage::{Event, LogStorage, Storage};
     use crate::Home;
+    use std::io;
+    use std::time::Duration;
+
+    /// Takes a while to store every event.
+    struct SlowStorage(LogStorage);
+
+    impl Storage for SlowStorage {
+        fn append(&self, event: &Event) -> io::Result<()> {
+            std::thread::sleep(Duration::from_millis(100));
+            self.0.append(event)
+        }
+
+        fn load(&self) -> io::Result<Vec<Event>> {
+            self.0.load()
+        }
+    }
 
-    #[test]
-    fn fetch_after_append() {
+    #[tokio::test]
+    async fn fetch_after_append() {
         let home = Home::default();
 
         let socket_id_1 = "socket_1".into();
         let socket_id_2 = "socket_2".into();
 
-        let socket1 = home.create_socket(socket_id_1, 100, true).unwrap();
-        let socket2 = home.create_socket(socket_id_2, 50, false).unwrap();
+        let socket1 = home.create_socket(socket_id_1, 100, true).await.unwrap();
+        let socket2 = home.create_socket(socket_id_2, 50, false).await.unwrap();
 
-        home.toggle_socket(&socket1).unwrap();
-        home.toggle_socket(&socket2).unwrap();
+        home.toggle_socket(&socket1).await.unwrap();
+        home.toggle_socket(&socket2).await.unwrap();
 
         let info = home.socket_info(socket1);
         println!("message: {:?}", info);
     }
+
+    #[tokio::test]
+    async fn restore_from_storage() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("home.log");
+
+        let home = Home::with_storage(LogStorage::open(&path).unwrap()).unwrap();
+        home.create_socket("socket_1".into(), 100, false)
+            .await
+            .unwrap();
+        home.toggle_socket("socket_1").await.unwrap();
+        home.set_socket_state("socket_1", false, Some(1))
+            .await
+            .unwrap();
+        home.set_socket_state("socket_1", true, None).await.unwrap();
+        home.create_thermo("thermo_1".into(), 20).await.unwrap();
+        home.set_thermo("thermo_1", 25).await.unwrap();
+        drop(home);
+
+        let home = Home::with_storage(LogStorage::open(&path).unwrap()).unwrap();
+        let socket = home.socket_info("socket_1".into());
+        assert_eq!(socket.unwrap(), "socket_1,true,100");
+        assert_eq!(home.socket_version("socket_1"), Some(3));
+        let thermo = home.thermo_info("thermo_1".into());
+        assert_eq!(thermo.unwrap(), "Thermo thermo_1 temperature is 25");
+        assert!(home
+            .create_socket("socket_1".into(), 1, true)
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn delete_and_rename() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("home.log");
+
+        let home = Home::with_storage(LogStorage::open(&path).unwrap()).unwrap();
+        home.create_socket("socket_1".into(), 100, false)
+            .await
+            .unwrap();
+        home.create_socket("socket_2".into(), 50, true)
+            .await
+            .unwrap();
+        home.create_thermo("thermo_1".into(), 20).await.unwrap();
+
+        let taken = home.rename_socket("socket_1", "socket_2".into()).await;
+        assert!(matches!(taken, Err(HomeError::AlreadyExists)));
+        let missing = home.rename_thermo("thermo_2", "thermo_3".into()).await;
+        assert!(matches!(missing, Err(HomeError::NotFound)));
+
+        home.rename_socket("socket_1", "socket_3".into())
+            .await
+            .unwrap();
+        home.delete_socket("socket_2").await.unwrap();
+        home.delete_thermo("thermo_1").await.unwrap();
+        let deleted = home.delete_thermo("thermo_1").await;
+        assert!(matches!(deleted, Err(HomeError::NotFound)));
+        drop(home);
+
+        let home = Home::with_storage(LogStorage::open(&path).unwrap()).unwrap();
+        assert_eq!(home.socket_ids(), vec!["socket_3"]);
+        assert!(home.thermo_ids().is_empty());
+        let socket = home.socket_info("socket_3".into());
+        assert_eq!(socket.unwrap(), "socket_3,false,100");
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn concurrent_rename_and_create() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("home.log");
+
+        let home = Home::with_storage(LogStorage::open(&path).unwrap()).unwrap();
+        let ids: Vec<_> = (0..64).map(|i| format!("socket_{}", i)).collect();
+        for id in &ids {
+            home.create_socket(id.clone(), 100, false).await.unwrap();
+        }
+
+        // Creating socket under old id races with renames moving sockets away and back.
+        let threads: Vec<_> = (0..2)
+            .map(|thread| {
+                let (home, ids) = (home.clone(), ids.clone());
+                tokio::spawn(async move {
+                    for round in 0..20 {
+                        for id in &ids {
+                            let moved = format!("{}_moved", id);
+                            match (round + thread) % 2 {
+                                0 => drop(home.rename_socket(id, moved).await),
+                                _ => drop(home.rename_socket(&moved, id.clone()).await),
+                            }
+                            let _ = home.create_socket(id.clone(), 1, true).await;
+                        }
+                    }
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.await.unwrap();
+        }
+        let ids = home.socket_ids();
+        drop(home);
+
+        let home = Home::with_storage(LogStorage::open(&path).unwrap()).unwrap();
+        assert_eq!(home.socket_ids(), ids);
+    }
+
+    #[tokio::test]
+    async fn cancelled_change_is_completed() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("home.log");
+
+        let storage = SlowStorage(LogStorage::open(&path).unwrap());
+        let home = Home::with_storage(storage).unwrap();
+        let create = home.create_socket("kettle".into(), 2000, false);
+        let cancelled = tokio::time::timeout(Duration::from_millis(10), create).await;
+        assert!(cancelled.is_err());
+        // Not visible until stored.
+        assert!(home.socket_info("kettle".into()).is_none());
+
+        let again = home.create_socket("kettle".into(), 2000, false).await;
+        assert!(matches!(again, Err(HomeError::AlreadyExists)));
+        drop(home);
+
+        let home = Home::with_storage(LogStorage::open(&path).unwrap()).unwrap();
+        assert_eq!(home.socket_ids(), vec!["kettle"]);
+    }
+
+    #[tokio::test]
+    async fn restore_layout() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("home.log");
+
+        let home = Home::with_storage(LogStorage::open(&path).unwrap()).unwrap();
+        home.create_house("home").await.unwrap();
+        home.create_house("cabin").await.unwrap();
+        home.create_room("home", "kitchen").await.unwrap();
+        home.create_room("cabin", "porch").await.unwrap();
+        home.create_socket("kettle".into(), 2000, false)
+            .await
+            .unwrap();
+        home.place_socket("kettle", "home", "kitchen")
+            .await
+            .unwrap();
+        home.place_socket("kettle", "cabin", "porch").await.unwrap();
+        home.delete_room("home", "kitchen").await.unwrap();
+        drop(home);
+
+        let home = Home::with_storage(LogStorage::open(&path).unwrap()).unwrap();
+        assert_eq!(home.house_names(), vec!["cabin", "home"]);
+        assert!(home.room_names("home").unwrap().is_empty());
+        let report = home.report("cabin").unwrap();
+        assert_eq!(
+            report,
+            "House cabin report:\nRoom porch:\nkettle,false,2000"
+        );
+    }
 }
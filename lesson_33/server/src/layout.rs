@@ -0,0 +1,136 @@
+use crate::home::{HomeError, HomeResult};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Houses, their rooms and devices placed in each room.
+///
+/// Devices themselves live in `Home`, layout only refers to them by id.
+/// Failed mutation changes nothing.
+#[derive(Default, Clone)]
+pub struct Layout {
+    houses: BTreeMap<String, House>,
+}
+
+#[derive(Default, Clone)]
+pub struct House {
+    pub rooms: BTreeMap<String, Room>,
+}
+
+#[derive(Default, Clone)]
+pub struct Room {
+    pub sockets: BTreeSet<String>,
+    pub thermos: BTreeSet<String>,
+}
+
+impl Room {
+    fn is_empty(&self) -> bool {
+        self.sockets.is_empty() && self.thermos.is_empty()
+    }
+
+    fn devices_mut(&mut self, kind: DeviceKind) -> &mut BTreeSet<String> {
+        match kind {
+            DeviceKind::Socket => &mut self.sockets,
+            DeviceKind::Thermo => &mut self.thermos,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum DeviceKind {
+    Socket,
+    Thermo,
+}
+
+impl Layout {
+    pub fn house_names(&self) -> Vec<String> {
+        self.houses.keys().cloned().collect()
+    }
+
+    pub fn house(&self, house: &str) -> HomeResult<&House> {
+        self.houses
+            .get(house)
+            .ok_or_else(|| HomeError::NoSuchHouse(house.into()))
+    }
+
+    pub fn create_house(&mut self, house: &str) -> HomeResult<()> {
+        if self.houses.contains_key(house) {
+            return Err(HomeError::AlreadyExists);
+        }
+
+        self.houses.insert(house.into(), House::default());
+        Ok(())
+    }
+
+    /// Only house without rooms can be deleted.
+    pub fn delete_house(&mut self, house: &str) -> HomeResult<()> {
+        if !self.house(house)?.rooms.is_empty() {
+            return Err(HomeError::NotEmpty);
+        }
+
+        self.houses.remove(house);
+        Ok(())
+    }
+
+    pub fn create_room(&mut self, house: &str, room: &str) -> HomeResult<()> {
+        let rooms = &mut self.house_mut(house)?.rooms;
+        if rooms.contains_key(room) {
+            return Err(HomeError::AlreadyExists);
+        }
+
+        rooms.insert(room.into(), Room::default());
+        Ok(())
+    }
+
+    /// Only room without devices can be deleted.
+    pub fn delete_room(&mut self, house: &str, room: &str) -> HomeResult<()> {
+        let rooms = &mut self.house_mut(house)?.rooms;
+        match rooms.get(room) {
+            None => return Err(HomeError::NoSuchRoom(room.into())),
+            Some(r) if !r.is_empty() => return Err(HomeError::NotEmpty),
+            Some(_) => {}
+        }
+
+        rooms.remove(room);
+        Ok(())
+    }
+
+    /// Puts device into room, removing it from room it was placed before.
+    pub fn place(&mut self, kind: DeviceKind, id: &str, house: &str, room: &str) -> HomeResult<()> {
+        let rooms = &self.house(house)?.rooms;
+        if !rooms.contains_key(room) {
+            return Err(HomeError::NoSuchRoom(room.into()));
+        }
+
+        self.remove_device(kind, id);
+        if let Some(room) = self.house_mut(house)?.rooms.get_mut(room) {
+            room.devices_mut(kind).insert(id.into());
+        }
+        Ok(())
+    }
+
+    /// Forgets deleted device.
+    pub fn remove_device(&mut self, kind: DeviceKind, id: &str) {
+        for room in self.rooms_mut() {
+            room.devices_mut(kind).remove(id);
+        }
+    }
+
+    /// Keeps renamed device in its room.
+    pub fn rename_device(&mut self, kind: DeviceKind, id: &str, new_id: &str) {
+        for room in self.rooms_mut() {
+            let devices = room.devices_mut(kind);
+            if devices.remove(id) {
+                devices.insert(new_id.into());
+            }
+        }
+    }
+
+    fn house_mut(&mut self, house: &str) -> HomeResult<&mut House> {
+        self.houses
+            .get_mut(house)
+            .ok_or_else(|| HomeError::NoSuchHouse(house.into()))
+    }
+
+    fn rooms_mut(&mut self) -> impl Iterator<Item = &mut Room> {
+        self.houses.values_mut().flat_map(|h| h.rooms.values_mut())
+    }
+}
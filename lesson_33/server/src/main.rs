@@ -1,49 +1,430 @@
+mod audit;
+mod config;
+mod connections;
 mod handler;
 mod home;
+mod layout;
+mod meter;
+mod metrics;
+mod rate_limit;
+mod router;
+mod storage;
 
-use handler::{Request, RequestHandler};
+use audit::{AuditLog, History};
+use clap::Parser;
+use config::{Args, Config};
+use connections::Connections;
+use handler::{Credentials, Request, RequestHandler};
 use home::Home;
-use stp::server::{StpConnection, StpServer};
-use tokio::fs;
+use metrics::Metrics;
+use rate_limit::{ConnectionLimiter, RateLimiter};
+use router::Router;
+use std::future::{self, Future};
+use std::io;
+use std::net::SocketAddr;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use storage::LogStorage;
+use stp::discovery::Responder;
+use stp::error::{RecvError, SendError};
+use stp::server::{Incoming, StpConnection, StpServer};
+use stp::Message;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::TcpListener;
+use tokio::sync::watch;
+use tokio::{fs, signal, time};
+use tracing::{debug, info, info_span, warn, Instrument};
+
+/// Response to client connected over the connection limit, right before it's dropped.
+const SERVER_BUSY: &str = "Server busy";
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    let addr = fs::read_to_string("settings/addr")
-        .await
-        .unwrap_or_else(|_| String::from("127.0.0.1:55331"));
-    let server = StpServer::bind(addr).await?;
-    let home = Home::default();
+    let args = Args::parse();
+    let config = Config::load(&args)?;
+    if args.check_config {
+        print!("{}", config.to_toml());
+        println!("# Configuration is valid");
+        return Ok(());
+    }
+    tracing_subscriber::fmt()
+        .with_max_level(config.logging.level)
+        .init();
+
+    let limits = &config.limits;
+    let idle_timeout = Some(limits.idle_timeout());
+    let request_timeout = Some(limits.request_timeout());
+    let mut server = StpServer::bind(&config.addr).await?;
+    server.set_idle_timeout(idle_timeout);
+    server.set_request_timeout(request_timeout);
+    info!(addr = %config.addr, "listening");
+    let home = Home::with_storage(LogStorage::open(&config.storage)?)?;
+    info!(path = %config.storage.display(), "home state restored");
+    let mut router = handler::commands();
+    let audit = match &config.audit_log {
+        Some(path) => {
+            let audit = Arc::new(AuditLog::open(path)?);
+            router = router.route(audit::HISTORY_COMMAND, History(audit.clone()));
+            info!(path = %path.display(), "recording changes");
+            Some(audit)
+        }
+        None => None,
+    };
+    let context = Context {
+        home,
+        credentials: credentials(&config.auth).map(Arc::new),
+        audit,
+        connections: Connections::new(limits.max_connections),
+        metrics: Metrics::default(),
+        router: Arc::new(router),
+        limiter: RateLimiter::new(config.rate_limit),
+    };
+    debug!(commands = ?context.router.commands(), "serving commands");
+    let limiter = context.limiter.clone();
+    tokio::spawn(async move {
+        let mut interval = time::interval(Duration::from_secs(60));
+        loop {
+            interval.tick().await;
+            limiter.prune(Instant::now());
+        }
+    });
+    if let Some(addr) = &config.metrics.addr {
+        let listener = TcpListener::bind(addr).await?;
+        info!(%addr, "serving metrics");
+        tokio::spawn(context.metrics.clone().serve(listener));
+    }
+    if let Some(addr) = &config.discovery.addr {
+        let port = server.local_addr()?.port();
+        let responder = Responder::bind(addr, port, &config.discovery.name).await?;
+        info!(%addr, name = %config.discovery.name, "answering discovery queries");
+        tokio::spawn(async move {
+            if let Err(e) = responder.serve().await {
+                warn!(error = %e, "discovery stopped");
+            }
+        });
+    }
+    let unix_socket = &config.unix_socket;
+    let (shutdown_tx, shutdown_rx) = watch::channel(());
+
+    let accept_tcp = || async {
+        let connection = server.accept().await?;
+        let addr = match connection.peer_addr() {
+            Ok(addr) => addr.to_string(),
+            Err(_) => "unknown".into(),
+        };
+        Ok((connection, addr))
+    };
+    let serve_tcp = serve(accept_tcp, &context, shutdown_rx.clone());
+    let serve_unix = async {
+        match &unix_socket {
+            Some(path) => {
+                let server = bind_unix(path, idle_timeout, request_timeout)?;
+                let name = path.display().to_string();
+                let accept_unix = || async { Ok((server.accept().await?, name.clone())) };
+                serve(accept_unix, &context, shutdown_rx.clone()).await;
+                Ok::<_, anyhow::Error>(())
+            }
+            None => future::pending().await,
+        }
+    };
+
+    tokio::select! {
+        _ = serve_tcp => {}
+        res = serve_unix => res?,
+        _ = shutdown_signal() => {
+            info!("shutting down, no longer accepting connections");
+        }
+    }
+    drop(server);
+    if let Some(path) = unix_socket {
+        fs::remove_file(path).await.ok();
+    }
+
+    shutdown_tx.send(()).ok();
+    let deadline = limits.shutdown_timeout();
+    let connections = &context.connections;
+    match time::timeout(deadline, connections.drained()).await {
+        Ok(()) => info!("all connections closed"),
+        Err(_) => warn!(
+            active = connections.active(),
+            "shutdown deadline exceeded, dropping connections"
+        ),
+    }
+
+    Ok(())
+}
+
+/// Tokens accepted from clients, `None` if authentication is disabled.
+fn credentials(auth: &config::Auth) -> Option<Credentials> {
+    if auth.token.is_none() && auth.users.is_empty() {
+        return None;
+    }
+
+    let mut credentials = Credentials::default();
+    if let Some(token) = &auth.token {
+        credentials = credentials.with(token, "shared");
+    }
+    for (user, token) in &auth.users {
+        credentials = credentials.with(token, user);
+    }
+    Some(credentials)
+}
+
+#[cfg(unix)]
+fn bind_unix(
+    path: &Path,
+    idle_timeout: Option<Duration>,
+    request_timeout: Option<Duration>,
+) -> anyhow::Result<stp::server::UnixStpServer> {
+    // Socket file left after unclean shutdown prevents binding.
+    std::fs::remove_file(path).ok();
+    let mut server = stp::server::UnixStpServer::bind(path)?;
+    server.set_idle_timeout(idle_timeout);
+    server.set_request_timeout(request_timeout);
+    info!(path = %path.display(), "listening on unix socket");
+    Ok(server)
+}
+
+#[cfg(not(unix))]
+fn bind_unix(_: &Path, _: Option<Duration>, _: Option<Duration>) -> anyhow::Result<StpServer> {
+    anyhow::bail!("Unix sockets are not supported on this platform")
+}
+
+/// State shared by all connections.
+#[derive(Clone)]
+struct Context {
+    home: Home,
+    credentials: Option<Arc<Credentials>>,
+    audit: Option<Arc<AuditLog>>,
+    connections: Connections,
+    metrics: Metrics,
+    router: Arc<Router>,
+    limiter: RateLimiter,
+}
 
+/// Accepts connections with `accept` and serves each of them in separate task.
+async fn serve<S, F, Fut>(accept: F, context: &Context, shutdown: watch::Receiver<()>)
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    F: Fn() -> Fut,
+    Fut: Future<Output = io::Result<(Incoming<S>, String)>>,
+{
+    let Context {
+        connections,
+        metrics,
+        ..
+    } = context;
     loop {
-        let connection = match server.accept().await {
+        let (incoming, addr) = match accept().await {
             Ok(c) => c,
             Err(e) => {
-                eprintln!("Can't establish connection: {}", e);
+                metrics.error("accept");
+                warn!(error = %e, "can't accept connection");
                 continue;
             }
         };
 
-        let addr = match connection.peer_addr().await {
-            Ok(addr) => addr.to_string(),
-            Err(_) => "unknown".into(),
-        };
+        let span = info_span!("connection", peer = %addr);
+        // Taken before handshake, so clients that never finish it count against the limit.
+        let slot = connections.try_acquire();
+        let ip = addr.parse::<SocketAddr>().ok().map(|addr| addr.ip());
+        let limiter = context.limiter.connection(ip);
+        let context = context.clone();
+        let shutdown = shutdown.clone();
+        // Handshake is done here, so slow client doesn't hold up accepting others.
+        let task = async move {
+            let connection = match incoming.handshake().await {
+                Ok(connection) => connection,
+                Err(e) => {
+                    context.metrics.error("handshake");
+                    warn!(error = %e, "can't establish connection");
+                    return;
+                }
+            };
+            let Some(slot) = slot else {
+                context.metrics.error("rejected");
+                warn!("connection limit reached, rejecting client");
+                let mut connection = connection;
+                let _ = connection.send_response(SERVER_BUSY).await;
+                return;
+            };
 
-        println!("New client connected: {}", addr);
+            let active = context.connections.active();
+            context.metrics.set_active_connections(active);
+            info!(active, "client connected");
 
-        let home = home.clone();
-        tokio::spawn(async move {
-            if handle_connection(connection, home).await.is_err() {
-                println!("Client disconnected: {}", addr);
+            let handler = RequestHandler::new(context.home.clone())
+                .with_router(context.router.clone())
+                .with_credentials(context.credentials.clone())
+                .with_audit(context.audit.clone(), addr);
+            let result =
+                handle_connection(connection, handler, limiter, &context.metrics, shutdown).await;
+            drop(slot);
+
+            let active = context.connections.active();
+            context.metrics.set_active_connections(active);
+            match result {
+                Ok(()) => info!(active, "client closed on shutdown"),
+                Err(e) if is_timeout(&e) => {
+                    context.metrics.error("timeout");
+                    info!(active, "client timed out, dropping connection")
+                }
+                Err(_) => info!(active, "client disconnected"),
             }
-        });
+        };
+        tokio::spawn(task.instrument(span));
     }
 }
 
-async fn handle_connection(connection: StpConnection, home: Home) -> Result<(), anyhow::Error> {
-    let mut handler = RequestHandler::new(home);
+/// Serves client requests until it disconnects or server shuts down.
+///
+/// Shutdown doesn't interrupt request that is already being handled.
+async fn handle_connection<S>(
+    mut connection: StpConnection<S>,
+    mut handler: RequestHandler,
+    mut limiter: ConnectionLimiter,
+    metrics: &Metrics,
+    mut shutdown: watch::Receiver<()>,
+) -> Result<(), anyhow::Error>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
     loop {
-        let req_str = connection.recv_request().await?;
-        let req = Request::new(&req_str);
-        connection.send_response(handler.handle(req)).await?;
+        let message = match connection.recv_message_or(shutdown.changed()).await? {
+            Some(message) => message,
+            None => return Ok(()),
+        };
+        let response = match message {
+            Message::Text(req_str) => {
+                // Only command is logged, arguments may contain secrets.
+                let command = req_str.split("|||").next().unwrap_or_default();
+                let command = handler.command(command).unwrap_or("unknown");
+                let span = info_span!("request", command);
+
+                let started = Instant::now();
+                if let Err(wait) = limiter.check(command, started) {
+                    metrics.error("throttled");
+                    debug!(parent: &span, ?wait, "request throttled");
+                    let response = format!("Throttled, retry in {} ms", wait.as_millis().max(1));
+                    connection.send_response(response).await?;
+                    continue;
+                }
+
+                let response = handler
+                    .handle(Request::new(&req_str))
+                    .instrument(span.clone())
+                    .await;
+                let took = started.elapsed();
+                metrics.request(command, took);
+                debug!(parent: &span, ?took, "request handled");
+                response
+            }
+            Message::Binary(_) => "Binary requests are not supported".into(),
+        };
+        connection.send_response(response).await?;
+    }
+}
+
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        let mut terminate = signal::unix::signal(signal::unix::SignalKind::terminate())
+            .expect("failed to install SIGTERM handler");
+        tokio::select! {
+            _ = signal::ctrl_c() => {}
+            _ = terminate.recv() => {}
+        }
+    }
+
+    #[cfg(not(unix))]
+    signal::ctrl_c().await.ok();
+}
+
+fn is_timeout(err: &anyhow::Error) -> bool {
+    matches!(err.downcast_ref(), Some(RecvError::Timeout))
+        || matches!(err.downcast_ref(), Some(SendError::Timeout))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{handle_connection, Home, Metrics, RateLimiter, RequestHandler};
+    use stp::client::StpClient;
+    use stp::server::StpConnection;
+    use tokio::io::{self, AsyncReadExt, AsyncWriteExt};
+    use tokio::sync::watch;
+
+    #[tokio::test]
+    async fn serve_over_duplex() {
+        let (client, server) = io::duplex(1024);
+        let (client, connection) = tokio::join!(
+            StpClient::handshake(client),
+            StpConnection::handshake(server)
+        );
+        let (mut client, connection) = (client.unwrap(), connection.unwrap());
+
+        let (shutdown_tx, shutdown_rx) = watch::channel(());
+        let handler = RequestHandler::new(Home::default());
+        let metrics = Metrics::default();
+        let serving = tokio::spawn({
+            let metrics = metrics.clone();
+            let limiter = RateLimiter::default().connection(None);
+            async move { handle_connection(connection, handler, limiter, &metrics, shutdown_rx).await }
+        });
+
+        let created = client.send_request("create_socket|||socket_1|||100|||false");
+        assert_eq!(created.await.unwrap(), "Socket `socket_1` created");
+        client
+            .send_request("toggle_socket|||socket_1")
+            .await
+            .unwrap();
+        let fetched = client
+            .send_request("fetch_socket|||socket_1")
+            .await
+            .unwrap();
+        assert_eq!(fetched, "socket_1,true,100");
+
+        shutdown_tx.send(()).unwrap();
+        assert!(serving.await.unwrap().is_ok());
+        let rendered = metrics.render();
+        assert!(rendered.contains("stp_requests_total{command=\"create_socket\"} 1"));
+    }
+
+    #[tokio::test]
+    async fn shutdown_waits_for_started_request() {
+        let (mut client, server) = io::duplex(1024);
+        client.write_all(b"clnt").await.unwrap();
+        let connection = StpConnection::handshake(server).await.unwrap();
+        let mut handshake = [0; 4];
+        client.read_exact(&mut handshake).await.unwrap();
+
+        let (shutdown_tx, shutdown_rx) = watch::channel(());
+        let handler = RequestHandler::new(Home::default());
+        let serving = tokio::spawn(async move {
+            let limiter = RateLimiter::default().connection(None);
+            handle_connection(
+                connection,
+                handler,
+                limiter,
+                &Metrics::default(),
+                shutdown_rx,
+            )
+            .await
+        });
+
+        let request = b"list_sockets";
+        client
+            .write_all(&[0, 0, 0, 0, request.len() as u8])
+            .await
+            .unwrap();
+        client.write_all(&request[..4]).await.unwrap();
+        tokio::task::yield_now().await;
+        shutdown_tx.send(()).unwrap();
+        client.write_all(&request[4..]).await.unwrap();
+
+        let mut header = [0; 5];
+        client.read_exact(&mut header).await.unwrap();
+        assert_eq!(header, [0, 0, 0, 0, 0], "empty list is answered");
+        assert!(serving.await.unwrap().is_ok());
     }
 }
@@ -0,0 +1,54 @@
+use std::time::Instant;
+
+/// Integrates power drawn by socket over time.
+///
+/// Energy is counted in memory since the socket was created or restored,
+/// it isn't persisted by storage.
+pub struct Meter {
+    /// Watt-seconds drawn before `since`.
+    energy: f64,
+    since: Instant,
+}
+
+impl Meter {
+    pub fn new(now: Instant) -> Self {
+        Self {
+            energy: 0.0,
+            since: now,
+        }
+    }
+
+    /// Accounts energy drawn with `power` until `now`.
+    ///
+    /// Must be called before power drawn by socket changes.
+    pub fn settle(&mut self, power: u64, now: Instant) {
+        self.energy = self.energy_at(power, now);
+        self.since = now;
+    }
+
+    /// Watt-seconds drawn by `now`, given socket has drawn `power` since last settle.
+    pub fn energy_at(&self, power: u64, now: Instant) -> f64 {
+        let elapsed = now.saturating_duration_since(self.since).as_secs_f64();
+        self.energy + power as f64 * elapsed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::meter::Meter;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn accumulates_energy() {
+        let start = Instant::now();
+        let mut meter = Meter::new(start);
+
+        let hour_later = start + Duration::from_secs(3600);
+        assert_eq!(meter.energy_at(100, hour_later), 360_000.0);
+
+        meter.settle(100, hour_later);
+        let two_hours_later = hour_later + Duration::from_secs(3600);
+        assert_eq!(meter.energy_at(0, two_hours_later), 360_000.0);
+        assert_eq!(meter.energy_at(50, two_hours_later), 540_000.0);
+    }
+}
@@ -0,0 +1,167 @@
+use prometheus::{
+    Encoder, HistogramOpts, HistogramVec, IntCounterVec, IntGauge, Opts, Registry, TextEncoder,
+};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+/// Server metrics in Prometheus format.
+#[derive(Clone)]
+pub struct Metrics {
+    registry: Registry,
+    requests: IntCounterVec,
+    latency: HistogramVec,
+    active_connections: IntGauge,
+    errors: IntCounterVec,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        let requests = IntCounterVec::new(
+            Opts::new("stp_requests_total", "Handled requests by command"),
+            &["command"],
+        )
+        .expect("valid metric");
+        let latency = HistogramVec::new(
+            HistogramOpts::new(
+                "stp_request_duration_seconds",
+                "Time to handle request by command",
+            ),
+            &["command"],
+        )
+        .expect("valid metric");
+        let active_connections =
+            IntGauge::new("stp_active_connections", "Currently served clients")
+                .expect("valid metric");
+        let errors = IntCounterVec::new(
+            Opts::new("stp_errors_total", "Connection errors by kind"),
+            &["kind"],
+        )
+        .expect("valid metric");
+
+        let registry = Registry::new();
+        registry
+            .register(Box::new(requests.clone()))
+            .expect("unique metric");
+        registry
+            .register(Box::new(latency.clone()))
+            .expect("unique metric");
+        registry
+            .register(Box::new(active_connections.clone()))
+            .expect("unique metric");
+        registry
+            .register(Box::new(errors.clone()))
+            .expect("unique metric");
+
+        Self {
+            registry,
+            requests,
+            latency,
+            active_connections,
+            errors,
+        }
+    }
+}
+
+impl Metrics {
+    /// Counts handled request. Unknown commands should be reported as `unknown`
+    /// to keep number of label values bounded.
+    pub fn request(&self, command: &str, took: Duration) {
+        self.requests.with_label_values(&[command]).inc();
+        self.latency
+            .with_label_values(&[command])
+            .observe(took.as_secs_f64());
+    }
+
+    pub fn set_active_connections(&self, active: usize) {
+        self.active_connections.set(active as i64);
+    }
+
+    /// Counts error, `kind` is one of a few fixed names like `timeout`.
+    pub fn error(&self, kind: &str) {
+        self.errors.with_label_values(&[kind]).inc();
+    }
+
+    /// All metrics in Prometheus text exposition format.
+    pub fn render(&self) -> String {
+        let mut buf = Vec::new();
+        TextEncoder::new()
+            .encode(&self.registry.gather(), &mut buf)
+            .expect("metrics are always encodable");
+        String::from_utf8(buf).expect("metrics are valid utf-8")
+    }
+
+    /// Serves `GET /metrics` over plain HTTP until cancelled.
+    pub async fn serve(self, listener: TcpListener) {
+        loop {
+            let stream = match listener.accept().await {
+                Ok((stream, _)) => stream,
+                Err(e) => {
+                    tracing::warn!(error = %e, "can't accept metrics connection");
+                    continue;
+                }
+            };
+
+            let metrics = self.clone();
+            tokio::spawn(async move {
+                if let Err(e) = metrics.respond(stream).await {
+                    tracing::debug!(error = %e, "metrics request failed");
+                }
+            });
+        }
+    }
+
+    async fn respond(&self, mut stream: TcpStream) -> std::io::Result<()> {
+        // Only request line matters, rest of the request is ignored.
+        let mut buf = [0; 1024];
+        let read = tokio::time::timeout(Duration::from_secs(5), stream.read(&mut buf)).await??;
+        let request = String::from_utf8_lossy(&buf[..read]);
+
+        let (status, body) = match request.split_whitespace().take(2).collect::<Vec<_>>()[..] {
+            ["GET", "/metrics"] => ("200 OK", self.render()),
+            _ => ("404 Not Found", "Not found\n".into()),
+        };
+        let response = format!(
+            "HTTP/1.1 {}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            status,
+            body.len(),
+            body
+        );
+        stream.write_all(response.as_bytes()).await?;
+        stream.shutdown().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::metrics::Metrics;
+    use std::time::Duration;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::{TcpListener, TcpStream};
+
+    #[tokio::test]
+    async fn serves_metrics() {
+        let metrics = Metrics::default();
+        metrics.request("fetch_socket", Duration::from_millis(3));
+        metrics.set_active_connections(2);
+        metrics.error("timeout");
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(metrics.serve(listener));
+
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        stream
+            .write_all(b"GET /metrics HTTP/1.1\r\n\r\n")
+            .await
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert!(response.contains("stp_requests_total{command=\"fetch_socket\"} 1"));
+        assert!(response.contains("stp_request_duration_seconds_count{command=\"fetch_socket\"} 1"));
+        assert!(response.contains("stp_active_connections 2"));
+        assert!(response.contains("stp_errors_total{kind=\"timeout\"} 1"));
+    }
+}
@@ -0,0 +1,256 @@
+use crate::config::{Rate, RateLimits};
+use dashmap::DashMap;
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Allows `rate.burst` requests at once, then refills at `rate.per_second`.
+pub struct TokenBucket {
+    rate: Rate,
+    tokens: f64,
+    updated: Instant,
+}
+
+impl TokenBucket {
+    pub fn new(rate: Rate, now: Instant) -> Self {
+        Self {
+            rate,
+            tokens: rate.burst as f64,
+            updated: now,
+        }
+    }
+
+    /// Takes one token, or returns time until it becomes available.
+    pub fn try_take(&mut self, now: Instant) -> Result<(), Duration> {
+        self.check(now)?;
+        self.tokens -= 1.0;
+        Ok(())
+    }
+
+    /// Like `try_take`, but leaves token in bucket.
+    pub fn check(&mut self, now: Instant) -> Result<(), Duration> {
+        self.refill(now);
+        if self.tokens >= 1.0 {
+            return Ok(());
+        }
+
+        let missing = 1.0 - self.tokens;
+        Err(Duration::from_secs_f64(missing / self.rate.per_second))
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate.per_second).min(self.rate.burst as f64);
+        self.updated = now;
+    }
+
+    fn is_full(&mut self, now: Instant) -> bool {
+        self.refill(now);
+        self.tokens >= self.rate.burst as f64
+    }
+}
+
+/// Command with its own rate, or `None` for bucket shared by all other commands.
+type BucketKey = Option<&'static str>;
+
+/// Rate limits shared by all connections, keeps per-IP buckets.
+#[derive(Clone, Default)]
+pub struct RateLimiter {
+    limits: Arc<RateLimits>,
+    per_ip: Arc<DashMap<(IpAddr, BucketKey), TokenBucket>>,
+}
+
+impl RateLimiter {
+    pub fn new(limits: RateLimits) -> Self {
+        Self {
+            limits: Arc::new(limits),
+            per_ip: Arc::default(),
+        }
+    }
+
+    /// Limiter for single connection. Clients without IP, like ones connected
+    /// over Unix socket, are only limited per connection.
+    pub fn connection(&self, ip: Option<IpAddr>) -> ConnectionLimiter {
+        ConnectionLimiter {
+            shared: self.clone(),
+            ip,
+            buckets: HashMap::new(),
+        }
+    }
+
+    /// Forgets per-IP buckets that have refilled, as they don't limit anything.
+    pub fn prune(&self, now: Instant) {
+        self.per_ip.retain(|_, bucket| !bucket.is_full(now));
+    }
+
+    /// Buckets to take token from, per connection and per IP.
+    /// Command overriding rate gets its own bucket, others share one.
+    fn rates(&self, command: &'static str) -> [Option<(BucketKey, Rate)>; 2] {
+        let limits = &self.limits;
+        let own = limits.commands.get(command);
+        let pick = |own: Option<Rate>, shared: Option<Rate>| match own {
+            Some(rate) => Some((Some(command), rate)),
+            None => shared.map(|rate| (None, rate)),
+        };
+        [
+            pick(own.and_then(|o| o.per_connection), limits.per_connection),
+            pick(own.and_then(|o| o.per_ip), limits.per_ip),
+        ]
+    }
+}
+
+pub struct ConnectionLimiter {
+    shared: RateLimiter,
+    ip: Option<IpAddr>,
+    buckets: HashMap<BucketKey, TokenBucket>,
+}
+
+impl ConnectionLimiter {
+    /// Accounts request with `command`, returns time to wait if it exceeds limits.
+    /// Rejected request takes no tokens.
+    pub fn check(&mut self, command: &'static str, now: Instant) -> Result<(), Duration> {
+        let [per_connection, per_ip] = self.shared.rates(command);
+        let mut connection_bucket = per_connection.map(|(key, rate)| {
+            self.buckets
+                .entry(key)
+                .or_insert_with(|| TokenBucket::new(rate, now))
+        });
+        if let Some(bucket) = &mut connection_bucket {
+            bucket.check(now)?;
+        }
+        if let (Some((key, rate)), Some(ip)) = (per_ip, self.ip) {
+            self.shared
+                .per_ip
+                .entry((ip, key))
+                .or_insert_with(|| TokenBucket::new(rate, now))
+                .try_take(now)?;
+        }
+        if let Some(bucket) = connection_bucket {
+            bucket.try_take(now)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::{CommandRates, Rate, RateLimits};
+    use crate::rate_limit::{RateLimiter, TokenBucket};
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn bucket_refills() {
+        let start = Instant::now();
+        let rate = Rate {
+            per_second: 2.0,
+            burst: 2,
+        };
+        let mut bucket = TokenBucket::new(rate, start);
+
+        assert!(bucket.try_take(start).is_ok());
+        assert!(bucket.try_take(start).is_ok());
+        assert_eq!(bucket.try_take(start), Err(Duration::from_millis(500)));
+
+        let later = start + Duration::from_millis(500);
+        assert!(bucket.try_take(later).is_ok());
+        assert!(bucket.try_take(later).is_err());
+    }
+
+    #[test]
+    fn limits_by_command_and_ip() {
+        let once = Rate {
+            per_second: 0.1,
+            burst: 1,
+        };
+        let mut limits = RateLimits {
+            per_ip: Some(Rate {
+                per_second: 0.1,
+                burst: 2,
+            }),
+            ..RateLimits::default()
+        };
+        let toggle = CommandRates {
+            per_connection: Some(once),
+            per_ip: None,
+        };
+        limits.commands.insert("toggle_socket".into(), toggle);
+        let limiter = RateLimiter::new(limits);
+        let now = Instant::now();
+
+        let ip = Some("10.0.0.1".parse().unwrap());
+        let mut first = limiter.connection(ip);
+        assert!(first.check("toggle_socket", now).is_ok());
+        assert!(first.check("toggle_socket", now).is_err());
+
+        // Other connection has own toggle bucket, but shares limit of IP.
+        let mut second = limiter.connection(ip);
+        assert!(second.check("toggle_socket", now).is_ok());
+        let mut third = limiter.connection(ip);
+        assert!(third.check("toggle_socket", now).is_err());
+
+        let mut local = limiter.connection(None);
+        for _ in 0..10 {
+            assert!(local.check("fetch_socket", now).is_ok());
+        }
+
+        limiter.prune(now + Duration::from_secs(60));
+        assert!(limiter.per_ip.is_empty());
+    }
+
+    #[test]
+    fn commands_share_bucket_unless_overridden() {
+        let mut limits = RateLimits {
+            per_connection: Some(Rate {
+                per_second: 0.1,
+                burst: 2,
+            }),
+            ..RateLimits::default()
+        };
+        let toggle = CommandRates {
+            per_connection: Some(Rate {
+                per_second: 0.1,
+                burst: 1,
+            }),
+            per_ip: None,
+        };
+        limits.commands.insert("toggle_socket".into(), toggle);
+        let limiter = RateLimiter::new(limits);
+        let now = Instant::now();
+
+        let mut connection = limiter.connection(None);
+        assert!(connection.check("fetch_socket", now).is_ok());
+        assert!(connection.check("list_sockets", now).is_ok());
+        assert!(connection.check("fetch_thermo", now).is_err());
+        assert!(connection.check("toggle_socket", now).is_ok());
+        assert!(connection.check("toggle_socket", now).is_err());
+    }
+
+    #[test]
+    fn rejected_request_takes_no_tokens() {
+        let limits = RateLimits {
+            per_connection: Some(Rate {
+                per_second: 0.1,
+                burst: 1,
+            }),
+            per_ip: Some(Rate {
+                per_second: 0.1,
+                burst: 1,
+            }),
+            ..RateLimits::default()
+        };
+        let limiter = RateLimiter::new(limits);
+        let now = Instant::now();
+
+        let ip = Some("10.0.0.1".parse().unwrap());
+        let mut first = limiter.connection(ip);
+        assert!(first.check("fetch_socket", now).is_ok());
+
+        // Rejected by IP limit, so own token is kept for later.
+        let mut second = limiter.connection(ip);
+        assert!(second.check("fetch_socket", now).is_err());
+        let other = Some("10.0.0.2".parse().unwrap());
+        second.ip = other;
+        assert!(second.check("fetch_socket", now).is_ok());
+    }
+}
@@ -0,0 +1,155 @@
+use crate::handler::Request;
+use crate::home::Home;
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// Handles single command, `request` holds arguments following command name.
+#[async_trait]
+pub trait Handler: Send + Sync {
+    async fn handle(&self, home: &Home, request: Request<'_>) -> String;
+}
+
+#[async_trait]
+impl<F> Handler for F
+where
+    F: for<'a> Fn(&'a Home, Request<'a>) -> BoxFuture<'a, String> + Send + Sync,
+{
+    async fn handle(&self, home: &Home, request: Request<'_>) -> String {
+        self(home, request).await
+    }
+}
+
+/// Wraps `async fn(&Home, Request) -> String` into [`Handler`].
+///
+/// ```ignore
+/// router.route("ping", handler(|_, _| Box::pin(async { "pong".into() })));
+/// ```
+pub fn handler<F>(f: F) -> F
+where
+    F: for<'a> Fn(&'a Home, Request<'a>) -> BoxFuture<'a, String> + Send + Sync,
+{
+    f
+}
+
+/// Kind of objects changed by command.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Target {
+    Socket,
+    Thermo,
+    Layout,
+}
+
+struct Route {
+    handler: Box<dyn Handler>,
+    changes: Option<Target>,
+}
+
+/// Maps command names to their handlers.
+#[derive(Default)]
+pub struct Router {
+    routes: HashMap<&'static str, Route>,
+}
+
+impl Router {
+    /// Registers `handler` for `command`, replacing previous one.
+    pub fn route<H: Handler + 'static>(self, command: &'static str, handler: H) -> Self {
+        self.insert(command, handler, None)
+    }
+
+    /// Registers `handler` for `command` that changes object of `target` kind,
+    /// named by its first argument.
+    pub fn route_change<H: Handler + 'static>(
+        self,
+        command: &'static str,
+        target: Target,
+        handler: H,
+    ) -> Self {
+        self.insert(command, handler, Some(target))
+    }
+
+    fn insert<H: Handler + 'static>(
+        mut self,
+        command: &'static str,
+        handler: H,
+        changes: Option<Target>,
+    ) -> Self {
+        let handler = Box::new(handler);
+        self.routes.insert(command, Route { handler, changes });
+        self
+    }
+
+    /// Kind of objects changed by `command`, `None` if it changes nothing or isn't registered.
+    pub fn changes(&self, command: &str) -> Option<Target> {
+        self.routes.get(command)?.changes
+    }
+
+    /// Static name of registered command, useful as a metrics label.
+    pub fn command(&self, command: &str) -> Option<&'static str> {
+        self.routes.get_key_value(command).map(|(name, _)| *name)
+    }
+
+    /// Names of all registered commands, sorted.
+    pub fn commands(&self) -> Vec<&'static str> {
+        let mut commands: Vec<_> = self.routes.keys().copied().collect();
+        commands.sort_unstable();
+        commands
+    }
+
+    /// Runs handler of `command`, returns `None` if it isn't registered.
+    pub async fn dispatch(
+        &self,
+        home: &Home,
+        command: &str,
+        request: Request<'_>,
+    ) -> Option<String> {
+        let route = self.routes.get(command)?;
+        Some(route.handler.handle(home, request).await)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::handler::Request;
+    use crate::home::Home;
+    use crate::router::{handler, Handler, Router, Target};
+    use async_trait::async_trait;
+
+    struct Echo;
+
+    #[async_trait]
+    impl Handler for Echo {
+        async fn handle(&self, _: &Home, mut request: Request<'_>) -> String {
+            request.next().into()
+        }
+    }
+
+    #[tokio::test]
+    async fn dispatch() {
+        let router = Router::default()
+            .route("echo", Echo)
+            .route(
+                "count",
+                handler(|home, _| Box::pin(async move { home.socket_ids().len().to_string() })),
+            )
+            .route_change("rename", Target::Socket, Echo);
+        let home = Home::default();
+
+        let echoed = router.dispatch(&home, "echo", Request::new("hello"));
+        assert_eq!(echoed.await.as_deref(), Some("hello"));
+        let counted = router.dispatch(&home, "count", Request::new(""));
+        assert_eq!(counted.await.as_deref(), Some("0"));
+        assert_eq!(
+            router.dispatch(&home, "reboot", Request::new("")).await,
+            None
+        );
+
+        assert_eq!(router.command("echo"), Some("echo"));
+        assert_eq!(router.commands(), ["count", "echo", "rename"]);
+        assert_eq!(router.changes("rename"), Some(Target::Socket));
+        assert_eq!(router.changes("echo"), None);
+    }
+}
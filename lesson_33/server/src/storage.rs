@@ -0,0 +1,259 @@
+use serde::{Deserialize, Serialize};
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Change of `Home` state, persisted by storage and replayed on startup.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+    SocketCreated {
+        id: String,
+        power: u64,
+        state: bool,
+    },
+    SocketToggled {
+        id: String,
+    },
+    SocketStateSet {
+        id: String,
+        state: bool,
+    },
+    SocketPowerSet {
+        id: String,
+        power: u64,
+    },
+    ThermoCreated {
+        id: String,
+        temp: i64,
+    },
+    ThermoSet {
+        id: String,
+        temp: i64,
+    },
+    SocketDeleted {
+        id: String,
+    },
+    ThermoDeleted {
+        id: String,
+    },
+    SocketRenamed {
+        id: String,
+        new_id: String,
+    },
+    ThermoRenamed {
+        id: String,
+        new_id: String,
+    },
+    HouseCreated {
+        house: String,
+    },
+    HouseDeleted {
+        house: String,
+    },
+    RoomCreated {
+        house: String,
+        room: String,
+    },
+    RoomDeleted {
+        house: String,
+        room: String,
+    },
+    SocketPlaced {
+        id: String,
+        house: String,
+        room: String,
+    },
+    ThermoPlaced {
+        id: String,
+        house: String,
+        room: String,
+    },
+}
+
+/// Durable storage backend for `Home`.
+pub trait Storage: Send + Sync {
+    /// Durably store event. Event is considered applied only if this call succeeds.
+    fn append(&self, event: &Event) -> io::Result<()>;
+
+    /// All stored events in order they were appended.
+    fn load(&self) -> io::Result<Vec<Event>>;
+}
+
+/// Append-only log of events, one JSON object per line.
+///
+/// Every append is flushed to disk before it returns. Line torn by crash
+/// in the middle of append is discarded on load. Failed append is cut off
+/// right away; if even that fails, storage refuses further appends.
+pub struct LogStorage {
+    path: PathBuf,
+    file: Mutex<Log>,
+}
+
+struct Log {
+    file: File,
+    /// Set when failed append couldn't be cut off, so log may end with partial line.
+    poisoned: bool,
+}
+
+impl LogStorage {
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        Ok(Self {
+            path,
+            file: Mutex::new(Log {
+                file,
+                poisoned: false,
+            }),
+        })
+    }
+}
+
+impl Storage for LogStorage {
+    fn append(&self, event: &Event) -> io::Result<()> {
+        let mut line = serde_json::to_vec(event)?;
+        line.push(b'\n');
+
+        let mut log = self.file.lock().unwrap_or_else(|e| e.into_inner());
+        if log.poisoned {
+            return Err(io::Error::other(
+                "log is left unusable by earlier failed append",
+            ));
+        }
+        let len = log.file.metadata()?.len();
+        let written = log.file.write_all(&line).and_then(|_| log.file.sync_data());
+        if written.is_err() {
+            // Partial line would be glued to the next appended one.
+            let truncated = log.file.set_len(len).and_then(|_| log.file.sync_data());
+            log.poisoned = truncated.is_err();
+        }
+        written
+    }
+
+    fn load(&self) -> io::Result<Vec<Event>> {
+        let log = self.file.lock().unwrap_or_else(|e| e.into_inner());
+
+        let mut content = Vec::new();
+        File::open(&self.path)?.read_to_end(&mut content)?;
+
+        let complete = match content.iter().rposition(|&b| b == b'\n') {
+            Some(pos) => pos + 1,
+            None => 0,
+        };
+        if complete < content.len() {
+            // Torn write, drop it so next append starts from a new line.
+            log.file.set_len(complete as u64)?;
+            log.file.sync_data()?;
+        }
+
+        content[..complete]
+            .split(|&b| b == b'\n')
+            .filter(|line| !line.is_empty())
+            .map(|line| serde_json::from_slice(line).map_err(io::Error::from))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::storage::{Event, LogStorage, Storage};
+    use std::fs::OpenOptions;
+    use std::io::{ErrorKind, Write};
+
+    fn events() -> Vec<Event> {
+        vec![
+            Event::SocketCreated {
+                id: "socket_1".into(),
+                power: 100,
+                state: false,
+            },
+            Event::SocketToggled {
+                id: "socket_1".into(),
+            },
+            Event::ThermoCreated {
+                id: "thermo\n1".into(),
+                temp: 20,
+            },
+        ]
+    }
+
+    #[test]
+    fn append_and_load() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("home.log");
+
+        let storage = LogStorage::open(&path).unwrap();
+        for event in events() {
+            storage.append(&event).unwrap();
+        }
+        drop(storage);
+
+        let storage = LogStorage::open(&path).unwrap();
+        assert_eq!(storage.load().unwrap(), events());
+    }
+
+    #[test]
+    fn torn_write_is_discarded() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("home.log");
+
+        let storage = LogStorage::open(&path).unwrap();
+        for event in events() {
+            storage.append(&event).unwrap();
+        }
+        drop(storage);
+
+        // Crash in the middle of writing the next event.
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(br#"{"event":"thermo_set","id":"th"#)
+            .unwrap();
+        drop(file);
+
+        let storage = LogStorage::open(&path).unwrap();
+        assert_eq!(storage.load().unwrap(), events());
+
+        let set = Event::ThermoSet {
+            id: "thermo\n1".into(),
+            temp: 25,
+        };
+        storage.append(&set).unwrap();
+        drop(storage);
+
+        let mut expected = events();
+        expected.push(set);
+        let storage = LogStorage::open(&path).unwrap();
+        assert_eq!(storage.load().unwrap(), expected);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn failed_append_poisons_log_it_cant_cut_off() {
+        // Every write fails with "no space left" and the device can't be truncated.
+        let storage = LogStorage::open("/dev/full").unwrap();
+        let err = storage.append(&events()[0]).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::StorageFull);
+
+        let err = storage.append(&events()[0]).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Other);
+    }
+
+    #[test]
+    fn corrupted_record_is_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("home.log");
+
+        let storage = LogStorage::open(&path).unwrap();
+        storage.append(&events()[0]).unwrap();
+        drop(storage);
+
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(b"garbage\n").unwrap();
+        drop(file);
+
+        let storage = LogStorage::open(&path).unwrap();
+        let err = storage.load().unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,103 @@
+use crate::error::{RecvError, SendResult};
+use crate::frame::{self, Frame, Header, Kind};
+use crate::Message;
+use std::future::Future;
+use std::io;
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::time::{self, error::Elapsed};
+
+pub(crate) async fn with_timeout<F: Future>(
+    limit: Option<Duration>,
+    fut: F,
+) -> Result<F::Output, Elapsed> {
+    match limit {
+        Some(limit) => time::timeout(limit, fut).await,
+        None => Ok(fut.await),
+    }
+}
+
+async fn send_frame<W>(kind: Kind, payload: &[u8], stream: &mut W) -> SendResult
+where
+    W: AsyncWrite + Unpin,
+{
+    let header = Header::encode(kind, payload)?;
+    stream.write_all(&header).await?;
+    stream.write_all(payload).await?;
+    stream.flush().await?;
+    Ok(())
+}
+
+pub(crate) async fn send_message<W>(message: &Message, stream: &mut W) -> SendResult
+where
+    W: AsyncWrite + Unpin,
+{
+    let kind = frame::message_kind(message);
+    send_frame(kind, message.as_bytes(), stream).await
+}
+
+pub(crate) async fn send_string<Data, W>(data: Data, stream: &mut W) -> SendResult
+where
+    Data: AsRef<str>,
+    W: AsyncWrite + Unpin,
+{
+    send_frame(Kind::Text, data.as_ref().as_bytes(), stream).await
+}
+
+pub(crate) async fn send_ping<W: AsyncWrite + Unpin>(stream: &mut W) -> SendResult {
+    send_frame(Kind::Ping, &[], stream).await
+}
+
+pub(crate) async fn send_pong<W: AsyncWrite + Unpin>(stream: &mut W) -> SendResult {
+    send_frame(Kind::Pong, &[], stream).await
+}
+
+/// Receive next frame.
+///
+/// `idle` limits waiting for the frame to start, `request` limits reading the rest of it.
+pub(crate) async fn recv_frame<R: AsyncRead + Unpin>(
+    stream: &mut R,
+    idle: Option<Duration>,
+    request: Option<Duration>,
+) -> Result<Frame, RecvError> {
+    let first = with_timeout(idle, recv_first_byte(stream)).await??;
+    recv_frame_rest(stream, first, request).await
+}
+
+/// Waits for the next frame to start. Cancel safe, nothing is consumed unless it completes.
+pub(crate) async fn recv_first_byte<R: AsyncRead + Unpin>(stream: &mut R) -> io::Result<u8> {
+    let mut first = [0; 1];
+    match stream.read(&mut first).await? {
+        0 => Err(io::ErrorKind::UnexpectedEof.into()),
+        _ => Ok(first[0]),
+    }
+}
+
+/// Receive the rest of frame which first byte is already received.
+pub(crate) async fn recv_frame_rest<R: AsyncRead + Unpin>(
+    stream: &mut R,
+    first: u8,
+    request: Option<Duration>,
+) -> Result<Frame, RecvError> {
+    let mut buf = [first; frame::HEADER_LEN];
+    with_timeout(request, stream.read_exact(&mut buf[1..])).await??;
+    let header = Header::parse(buf)?;
+
+    let mut payload = vec![0; header.len];
+    with_timeout(request, stream.read_exact(&mut payload)).await??;
+    frame::decode(header.kind, payload)
+}
+
+pub(crate) async fn write_handshake<W: AsyncWrite + Unpin>(
+    stream: &mut W,
+    handshake: [u8; 4],
+) -> io::Result<()> {
+    stream.write_all(&handshake).await?;
+    stream.flush().await
+}
+
+pub(crate) async fn read_handshake<R: AsyncRead + Unpin>(stream: &mut R) -> io::Result<[u8; 4]> {
+    let mut buf = [0; 4];
+    stream.read_exact(&mut buf).await?;
+    Ok(buf)
+}
@@ -0,0 +1,160 @@
+use crate::error::{ConnectResult, RecvError};
+use crate::frame::{self, Frame};
+use crate::Message;
+use std::io::{self, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::{Duration, Instant};
+
+pub use crate::error::{RequestError, RequestResult};
+
+/// Represent client-side connection for STP
+pub struct StpClient {
+    stream: TcpStream,
+    idle_timeout: Option<Duration>,
+    request_timeout: Option<Duration>,
+    last_activity: Instant,
+    broken: bool,
+}
+
+impl StpClient {
+    /// Try to connect to specified address and perform handshake.
+    pub fn connect<Addrs>(addrs: Addrs) -> ConnectResult<Self>
+    where
+        Addrs: ToSocketAddrs,
+    {
+        let stream = TcpStream::connect(addrs)?;
+        Self::try_handshake(stream, None)
+    }
+
+    /// Like [`connect`](Self::connect), but connecting and handshake each take at most `timeout`.
+    ///
+    /// Addresses are tried in order, until connection to one of them succeeds.
+    pub fn connect_timeout<Addrs>(addrs: Addrs, timeout: Duration) -> ConnectResult<Self>
+    where
+        Addrs: ToSocketAddrs,
+    {
+        let timeout = timeout.max(Duration::from_nanos(1));
+        let mut last_error = None;
+        for addr in addrs.to_socket_addrs()? {
+            match TcpStream::connect_timeout(&addr, timeout) {
+                Ok(stream) => return Self::try_handshake(stream, Some(timeout)),
+                Err(e) => last_error = Some(e),
+            }
+        }
+        let e = last_error.unwrap_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "no addresses to connect to")
+        });
+        Err(super::connect_error(e))
+    }
+
+    /// Connection idle for longer than `timeout` is checked with ping before the next request.
+    ///
+    /// `None` disables the check.
+    pub fn set_idle_timeout(&mut self, timeout: Option<Duration>) {
+        self.idle_timeout = timeout;
+    }
+
+    /// Max time for single read or write while sending request and receiving response.
+    ///
+    /// `None` means to wait forever.
+    pub fn set_request_timeout(&mut self, timeout: Option<Duration>) {
+        self.request_timeout = timeout;
+    }
+
+    /// Whether earlier request failed midway, e.g. timed out.
+    ///
+    /// Response to such request may still arrive, so connection can't be used anymore
+    /// and further requests fail with [`RequestError::Broken`].
+    pub fn is_broken(&self) -> bool {
+        self.broken
+    }
+
+    /// Send request to connected STP server.
+    ///
+    /// Fails with [`RecvError::UnexpectedBinary`](crate::error::RecvError::UnexpectedBinary)
+    /// if server responds with binary message.
+    pub fn send_request<R: AsRef<str>>(&mut self, req: R) -> RequestResult {
+        let response = self.send_message(req.as_ref())?;
+        Ok(frame::into_text(response)?)
+    }
+
+    /// Send text or binary request to connected STP server.
+    pub fn send_message<M: Into<Message>>(&mut self, req: M) -> RequestResult<Message> {
+        if self.is_idle() {
+            self.ping()?;
+        }
+        self.check_broken()?;
+
+        // Stays set if exchange fails midway.
+        self.broken = true;
+        super::send_message(&req.into(), &mut self.stream, self.request_timeout)?;
+        let response = self.recv_response()?;
+        self.broken = false;
+        self.last_activity = Instant::now();
+        Ok(response)
+    }
+
+    /// Check that server is alive. Returns round trip time.
+    pub fn ping(&mut self) -> Result<Duration, RequestError> {
+        self.check_broken()?;
+        let started = Instant::now();
+        self.broken = true;
+        super::send_ping(&mut self.stream, self.request_timeout)?;
+        loop {
+            match self.recv_frame()? {
+                Frame::Pong => break,
+                Frame::Ping => super::send_pong(&mut self.stream, self.request_timeout)?,
+                Frame::Message(_) => return Err(RecvError::UnexpectedMessage.into()),
+            }
+        }
+        self.broken = false;
+        self.last_activity = Instant::now();
+        Ok(started.elapsed())
+    }
+
+    fn check_broken(&self) -> Result<(), RequestError> {
+        match self.broken {
+            true => Err(RequestError::Broken),
+            false => Ok(()),
+        }
+    }
+
+    fn is_idle(&self) -> bool {
+        match self.idle_timeout {
+            Some(timeout) => self.last_activity.elapsed() >= timeout,
+            None => false,
+        }
+    }
+
+    fn recv_response(&mut self) -> RequestResult<Message> {
+        loop {
+            match self.recv_frame()? {
+                Frame::Message(response) => return Ok(response),
+                Frame::Ping => super::send_pong(&mut self.stream, self.request_timeout)?,
+                Frame::Pong => {}
+            }
+        }
+    }
+
+    fn recv_frame(&mut self) -> Result<Frame, RequestError> {
+        let timeout = self.request_timeout;
+        Ok(super::recv_frame(&mut self.stream, timeout, timeout)?)
+    }
+
+    fn try_handshake(mut stream: TcpStream, timeout: Option<Duration>) -> ConnectResult<Self> {
+        stream.set_read_timeout(timeout)?;
+        stream.set_write_timeout(timeout)?;
+        stream
+            .write_all(&frame::CLIENT_HANDSHAKE)
+            .map_err(super::connect_error)?;
+        let received = super::read_handshake(&mut stream).map_err(super::connect_error)?;
+        frame::check_handshake(received, frame::SERVER_HANDSHAKE)?;
+        Ok(Self {
+            stream,
+            idle_timeout: None,
+            request_timeout: None,
+            last_activity: Instant::now(),
+            broken: false,
+        })
+    }
+}
@@ -0,0 +1,97 @@
+//! Blocking counterpart of [`crate::discovery`].
+
+use crate::discovery::{
+    check_sent, collect, is_transient, reply, Announcement, MAX_DATAGRAM, QUERY,
+};
+use std::io;
+use std::net::{Ipv4Addr, SocketAddr, ToSocketAddrs, UdpSocket};
+use std::time::{Duration, Instant};
+
+/// Sends discovery query to `targets` and collects replies for `wait`.
+pub fn discover(targets: &[SocketAddr], wait: Duration) -> io::Result<Vec<Announcement>> {
+    let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0))?;
+    socket.set_broadcast(true)?;
+
+    let (mut sent, mut error) = (false, None);
+    for target in targets {
+        match socket.send_to(QUERY, target) {
+            Ok(_) => sent = true,
+            Err(e) => error = Some(e),
+        }
+    }
+    check_sent(sent, error)?;
+
+    let deadline = Instant::now() + wait;
+    let mut found = Vec::new();
+    let mut buf = [0; MAX_DATAGRAM];
+    loop {
+        let left = deadline.saturating_duration_since(Instant::now());
+        if left.is_zero() {
+            break;
+        }
+        socket.set_read_timeout(Some(left))?;
+        // Errors caused by unreachable targets don't prevent others from replying,
+        // timeout is detected by the deadline check.
+        if let Ok((len, from)) = socket.recv_from(&mut buf) {
+            collect(&mut found, &buf[..len], from);
+        }
+    }
+    Ok(found)
+}
+
+/// Replies to discovery queries on behalf of STP server.
+pub struct Responder {
+    socket: UdpSocket,
+    reply: Vec<u8>,
+}
+
+impl Responder {
+    /// Listens for queries on `addr`, announcing server accepting connections on `stp_port`.
+    pub fn bind<Addrs>(addrs: Addrs, stp_port: u16, name: &str) -> io::Result<Self>
+    where
+        Addrs: ToSocketAddrs,
+    {
+        Ok(Self {
+            socket: UdpSocket::bind(addrs)?,
+            reply: reply(stp_port, name),
+        })
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.socket.local_addr()
+    }
+
+    /// Replies to queries until socket fails, run it in separate thread.
+    pub fn serve(self) -> io::Result<()> {
+        let mut buf = [0; MAX_DATAGRAM];
+        loop {
+            let (len, from) = match self.socket.recv_from(&mut buf) {
+                Ok(received) => received,
+                Err(e) if is_transient(&e) => continue,
+                Err(e) => return Err(e),
+            };
+            if &buf[..len] == QUERY {
+                // Client may be gone already, nothing to do about it.
+                let _ = self.socket.send_to(&self.reply, from);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::blocking::discovery::{discover, Responder};
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn discover_over_loopback() {
+        let responder = Responder::bind("127.0.0.1:0", 4000, "garage").unwrap();
+        let addr = responder.local_addr().unwrap();
+        thread::spawn(move || responder.serve());
+
+        let found = discover(&[addr], Duration::from_millis(200)).unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].to_string(), "garage (127.0.0.1:4000)");
+    }
+}
@@ -0,0 +1,110 @@
+//! Blocking STP implementation over `std::net`.
+//!
+//! Timeouts are implemented with socket read and write timeouts, so they limit
+//! every single IO operation rather than the whole request.
+
+pub mod client;
+pub mod discovery;
+pub mod server;
+
+use crate::error::{ConnectError, RecvError, SendError, SendResult};
+use crate::frame::{self, Frame, Header, Kind};
+use crate::Message;
+use std::io::{self, Read, Write};
+use std::net::TcpStream;
+use std::time::Duration;
+
+fn is_timeout(e: &io::Error) -> bool {
+    matches!(
+        e.kind(),
+        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+    )
+}
+
+fn connect_error(e: io::Error) -> ConnectError {
+    match is_timeout(&e) {
+        true => ConnectError::Timeout,
+        false => ConnectError::Io(e),
+    }
+}
+
+fn send_error(e: io::Error) -> SendError {
+    match is_timeout(&e) {
+        true => SendError::Timeout,
+        false => SendError::Io(e),
+    }
+}
+
+fn recv_error(e: io::Error) -> RecvError {
+    match is_timeout(&e) {
+        true => RecvError::Timeout,
+        false => RecvError::Io(e),
+    }
+}
+
+/// Socket timeouts reject zero duration, it is replaced with the shortest one accepted.
+fn socket_timeout(timeout: Option<Duration>) -> Option<Duration> {
+    timeout.map(|t| t.max(Duration::from_nanos(1)))
+}
+
+fn send_frame(
+    kind: Kind,
+    payload: &[u8],
+    stream: &mut TcpStream,
+    timeout: Option<Duration>,
+) -> SendResult {
+    let header = Header::encode(kind, payload)?;
+    stream.set_write_timeout(socket_timeout(timeout))?;
+    stream.write_all(&header).map_err(send_error)?;
+    stream.write_all(payload).map_err(send_error)
+}
+
+fn send_message(
+    message: &Message,
+    stream: &mut TcpStream,
+    timeout: Option<Duration>,
+) -> SendResult {
+    let kind = frame::message_kind(message);
+    send_frame(kind, message.as_bytes(), stream, timeout)
+}
+
+fn send_string<D: AsRef<str>>(
+    d: D,
+    stream: &mut TcpStream,
+    timeout: Option<Duration>,
+) -> SendResult {
+    send_frame(Kind::Text, d.as_ref().as_bytes(), stream, timeout)
+}
+
+fn send_ping(stream: &mut TcpStream, timeout: Option<Duration>) -> SendResult {
+    send_frame(Kind::Ping, &[], stream, timeout)
+}
+
+fn send_pong(stream: &mut TcpStream, timeout: Option<Duration>) -> SendResult {
+    send_frame(Kind::Pong, &[], stream, timeout)
+}
+
+/// Receive next frame.
+///
+/// `idle` limits waiting for the frame to start, `request` limits reading its body.
+fn recv_frame(
+    stream: &mut TcpStream,
+    idle: Option<Duration>,
+    request: Option<Duration>,
+) -> Result<Frame, RecvError> {
+    stream.set_read_timeout(socket_timeout(idle))?;
+    let mut buf = [0; frame::HEADER_LEN];
+    stream.read_exact(&mut buf).map_err(recv_error)?;
+    let header = Header::parse(buf)?;
+
+    stream.set_read_timeout(socket_timeout(request))?;
+    let mut payload = vec![0; header.len];
+    stream.read_exact(&mut payload).map_err(recv_error)?;
+    frame::decode(header.kind, payload)
+}
+
+fn read_handshake(stream: &mut TcpStream) -> io::Result<[u8; 4]> {
+    let mut buf = [0; 4];
+    stream.read_exact(&mut buf)?;
+    Ok(buf)
+}
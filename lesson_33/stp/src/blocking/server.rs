@@ -0,0 +1,186 @@
+use crate::error::{ConnectError, ConnectResult, RecvResult, SendResult};
+use crate::frame::{self, Frame};
+use crate::Message;
+use std::io::{self, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+pub use crate::error::BindError;
+
+/// Represent STP server, that can accept incoming connections.
+pub struct StpServer {
+    tcp: TcpListener,
+    idle_timeout: Option<Duration>,
+    request_timeout: Option<Duration>,
+}
+
+impl StpServer {
+    /// Binds server to specified socket.
+    pub fn bind<Addrs>(addrs: Addrs) -> BindResult
+    where
+        Addrs: ToSocketAddrs,
+    {
+        let tcp = TcpListener::bind(addrs)?;
+        Ok(Self {
+            tcp,
+            idle_timeout: None,
+            request_timeout: None,
+        })
+    }
+
+    /// Idle timeout for accepted connections. See [`StpConnection::set_idle_timeout`].
+    pub fn set_idle_timeout(&mut self, timeout: Option<Duration>) {
+        self.idle_timeout = timeout;
+    }
+
+    /// Request timeout for accepted connections. See [`StpConnection::set_request_timeout`].
+    ///
+    /// Also limits handshake duration.
+    pub fn set_request_timeout(&mut self, timeout: Option<Duration>) {
+        self.request_timeout = timeout;
+    }
+
+    /// Address server is bound to.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.tcp.local_addr()
+    }
+
+    /// Blocking iterator for incoming connections.
+    pub fn incoming(&self) -> impl Iterator<Item = ConnectResult<StpConnection>> + '_ {
+        self.tcp.incoming().map(|s| match s {
+            Ok(s) => self.try_handshake(s),
+            Err(e) => Err(ConnectError::Io(e)),
+        })
+    }
+
+    fn try_handshake(&self, mut stream: TcpStream) -> ConnectResult<StpConnection> {
+        stream.set_read_timeout(super::socket_timeout(self.request_timeout))?;
+        stream.set_write_timeout(super::socket_timeout(self.request_timeout))?;
+        let received = super::read_handshake(&mut stream).map_err(super::connect_error)?;
+        frame::check_handshake(received, frame::CLIENT_HANDSHAKE)?;
+        stream.write_all(&frame::SERVER_HANDSHAKE)?;
+        Ok(StpConnection {
+            stream,
+            idle_timeout: self.idle_timeout,
+            request_timeout: self.request_timeout,
+        })
+    }
+}
+
+pub type BindResult = Result<StpServer, BindError>;
+
+/// Represent connection from client.
+///
+/// Allows to receive requests and send responses.
+/// Pings from client are answered transparently while waiting for request.
+pub struct StpConnection {
+    stream: TcpStream,
+    idle_timeout: Option<Duration>,
+    request_timeout: Option<Duration>,
+}
+
+impl StpConnection {
+    /// Max time to wait for the next request or ping from client.
+    ///
+    /// `None` means to wait forever.
+    pub fn set_idle_timeout(&mut self, timeout: Option<Duration>) {
+        self.idle_timeout = timeout;
+    }
+
+    /// Max time for single read or write of request or response.
+    ///
+    /// `None` means to wait forever.
+    pub fn set_request_timeout(&mut self, timeout: Option<Duration>) {
+        self.request_timeout = timeout;
+    }
+
+    /// Send response to client
+    pub fn send_response<Resp: AsRef<str>>(&mut self, response: Resp) -> SendResult {
+        super::send_string(response, &mut self.stream, self.request_timeout)
+    }
+
+    /// Send text or binary response to client
+    pub fn send_message<M: Into<Message>>(&mut self, response: M) -> SendResult {
+        super::send_message(&response.into(), &mut self.stream, self.request_timeout)
+    }
+
+    /// Receive requests from client
+    ///
+    /// Fails with [`RecvError::UnexpectedBinary`](crate::error::RecvError::UnexpectedBinary)
+    /// if client sends binary message.
+    pub fn recv_request(&mut self) -> RecvResult {
+        frame::into_text(self.recv_message()?)
+    }
+
+    /// Receive text or binary request from client
+    pub fn recv_message(&mut self) -> RecvResult<Message> {
+        loop {
+            let frame =
+                super::recv_frame(&mut self.stream, self.idle_timeout, self.request_timeout);
+            match frame? {
+                Frame::Message(request) => return Ok(request),
+                Frame::Ping => super::send_pong(&mut self.stream, self.request_timeout)?,
+                Frame::Pong => {}
+            }
+        }
+    }
+
+    /// Address of connected client
+    pub fn peer_addr(&self) -> io::Result<SocketAddr> {
+        self.stream.peer_addr()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::blocking::client::StpClient;
+    use crate::blocking::server::StpServer;
+    use crate::error::{ConnectError, RecvError, RequestError};
+    use std::net::TcpListener;
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn ping_and_idle_timeout() {
+        let mut server = StpServer::bind("127.0.0.1:0").unwrap();
+        server.set_idle_timeout(Some(Duration::from_millis(200)));
+        let addr = server.local_addr().unwrap();
+
+        let client = thread::spawn(move || {
+            let mut client = StpClient::connect(addr).unwrap();
+            client.ping().unwrap();
+            let response = client.send_request("hello").unwrap();
+            (client, response)
+        });
+
+        let mut connection = server.incoming().next().unwrap().unwrap();
+        assert_eq!(connection.recv_request().unwrap(), "hello");
+        connection.send_response("world").unwrap();
+        let (_client, response) = client.join().unwrap();
+        assert_eq!(response, "world");
+
+        let err = connection.recv_request().unwrap_err();
+        assert!(matches!(err, RecvError::Timeout));
+    }
+
+    #[test]
+    fn handshake_and_request_timeouts() {
+        // Connection is queued by OS, but handshake is never answered.
+        let silent = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = silent.local_addr().unwrap();
+        let connect = StpClient::connect_timeout(addr, Duration::from_millis(50));
+        assert!(matches!(connect, Err(ConnectError::Timeout)));
+
+        let server = StpServer::bind("127.0.0.1:0").unwrap();
+        let addr = server.local_addr().unwrap();
+        let client = thread::spawn(move || {
+            let mut client = StpClient::connect_timeout(addr, Duration::from_secs(1)).unwrap();
+            client.set_request_timeout(Some(Duration::ZERO));
+            client.send_request("hello").unwrap_err()
+        });
+        let mut connection = server.incoming().next().unwrap().unwrap();
+        assert_eq!(connection.recv_request().unwrap(), "hello");
+        let err = client.join().unwrap();
+        assert!(matches!(err, RequestError::Recv(RecvError::Timeout)));
+    }
+}
@@ -1,11 +1,23 @@
-use crate::error::{ConnectError, ConnectResult, RecvError, SendError};
-use thiserror::Error;
+use crate::async_io::{self, with_timeout};
+use crate::error::{ConnectResult, RecvError};
+use crate::frame::{self, Frame};
+use crate::Message;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncRead, AsyncWrite};
 use tokio::net::TcpStream;
 use tokio::net::ToSocketAddrs;
 
+pub use crate::error::{RequestError, RequestResult};
+
 /// Represent client-side connection for STP
-pub struct StpClient {
-    stream: TcpStream,
+///
+/// Works over any byte stream, TCP is used by default.
+pub struct StpClient<S = TcpStream> {
+    stream: S,
+    idle_timeout: Option<Duration>,
+    request_timeout: Option<Duration>,
+    last_activity: Instant,
+    broken: bool,
 }
 
 impl StpClient {
@@ -15,38 +27,144 @@ impl StpClient {
         Addrs: ToSocketAddrs,
     {
         let stream = TcpStream::connect(addrs).await?;
-        Self::try_handshake(stream).await
+        Self::handshake(stream).await
+    }
+
+    /// Like [`connect`](Self::connect), but connecting and handshake each take at most `timeout`.
+    pub async fn connect_timeout<Addrs>(addrs: Addrs, timeout: Duration) -> ConnectResult<Self>
+    where
+        Addrs: ToSocketAddrs,
+    {
+        let stream = with_timeout(Some(timeout), TcpStream::connect(addrs)).await??;
+        with_timeout(Some(timeout), Self::handshake(stream)).await?
+    }
+}
+
+#[cfg(unix)]
+impl StpClient<tokio::net::UnixStream> {
+    /// Try to connect to Unix socket at specified path and perform handshake.
+    pub async fn connect_unix<P>(path: P) -> ConnectResult<Self>
+    where
+        P: AsRef<std::path::Path>,
+    {
+        let stream = tokio::net::UnixStream::connect(path).await?;
+        Self::handshake(stream).await
+    }
+}
+
+impl<S> StpClient<S>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    /// Perform handshake over already established stream.
+    pub async fn handshake(mut stream: S) -> ConnectResult<Self> {
+        async_io::write_handshake(&mut stream, frame::CLIENT_HANDSHAKE).await?;
+        let received = async_io::read_handshake(&mut stream).await?;
+        frame::check_handshake(received, frame::SERVER_HANDSHAKE)?;
+        Ok(Self {
+            stream,
+            idle_timeout: None,
+            request_timeout: None,
+            last_activity: Instant::now(),
+            broken: false,
+        })
+    }
+
+    /// Connection idle for longer than `timeout` is checked with ping before the next request.
+    ///
+    /// `None` disables the check.
+    pub fn set_idle_timeout(&mut self, timeout: Option<Duration>) {
+        self.idle_timeout = timeout;
+    }
+
+    /// Max time for the whole request: sending it and receiving the response.
+    ///
+    /// `None` means to wait forever.
+    pub fn set_request_timeout(&mut self, timeout: Option<Duration>) {
+        self.request_timeout = timeout;
+    }
+
+    /// Whether earlier request failed or was cancelled midway.
+    ///
+    /// Response to such request may still arrive, so connection can't be used anymore
+    /// and further requests fail with [`RequestError::Broken`].
+    pub fn is_broken(&self) -> bool {
+        self.broken
     }
 
     /// Send request to connected STP server.
+    ///
+    /// Fails with [`RecvError::UnexpectedBinary`](crate::error::RecvError::UnexpectedBinary)
+    /// if server responds with binary message.
     pub async fn send_request<R: AsRef<str>>(&mut self, req: R) -> RequestResult {
-        super::send_string_async(req, &self.stream).await?;
-        let response = super::recv_string_async(&self.stream).await?;
+        let response = self.send_message(req.as_ref()).await?;
+        Ok(frame::into_text(response)?)
+    }
+
+    /// Send text or binary request to connected STP server.
+    pub async fn send_message<M: Into<Message>>(&mut self, req: M) -> RequestResult<Message> {
+        if self.is_idle() {
+            self.ping().await?;
+        }
+        self.check_broken()?;
+
+        let req = req.into();
+        let timeout = self.request_timeout;
+        // Stays set if exchange fails or is cancelled midway.
+        self.broken = true;
+        let exchange = async {
+            async_io::send_message(&req, &mut self.stream).await?;
+            self.recv_response().await
+        };
+        let response = with_timeout(timeout, exchange).await??;
+        self.broken = false;
+        self.last_activity = Instant::now();
         Ok(response)
     }
 
-    async fn try_handshake(s: TcpStream) -> ConnectResult<Self> {
-        super::write_all_async(&s, b"clnt").await?;
-        let mut buf = [0; 4];
-        super::read_exact_async(&s, &mut buf).await?;
-        if &buf != b"serv" {
-            let msg = format!("received: {:?}", buf);
-            return Err(ConnectError::BadHandshake(msg));
+    /// Check that server is alive. Returns round trip time.
+    pub async fn ping(&mut self) -> Result<Duration, RequestError> {
+        self.check_broken()?;
+        let started = Instant::now();
+        let timeout = self.request_timeout;
+        self.broken = true;
+        let exchange = async {
+            async_io::send_ping(&mut self.stream).await?;
+            loop {
+                match async_io::recv_frame(&mut self.stream, None, None).await? {
+                    Frame::Pong => return Ok::<_, RequestError>(()),
+                    Frame::Ping => async_io::send_pong(&mut self.stream).await?,
+                    Frame::Message(_) => return Err(RecvError::UnexpectedMessage.into()),
+                }
+            }
+        };
+        with_timeout(timeout, exchange).await??;
+        self.broken = false;
+        self.last_activity = Instant::now();
+        Ok(started.elapsed())
+    }
+
+    fn check_broken(&self) -> Result<(), RequestError> {
+        match self.broken {
+            true => Err(RequestError::Broken),
+            false => Ok(()),
         }
-        Ok(Self { stream: s })
     }
-}
 
-pub type RequestResult = Result<String, RequestError>;
+    fn is_idle(&self) -> bool {
+        match self.idle_timeout {
+            Some(timeout) => self.last_activity.elapsed() >= timeout,
+            None => false,
+        }
+    }
 
-/// Error for request sending. It consists from two steps: sending and receiving data.
-///
-/// `SendError` caused by send data error.
-/// `RecvError` caused by receive data error.
-#[derive(Debug, Error)]
-pub enum RequestError {
-    #[error(transparent)]
-    Send(#[from] SendError),
-    #[error(transparent)]
-    Recv(#[from] RecvError),
+    async fn recv_response(&mut self) -> RequestResult<Message> {
+        loop {
+            match async_io::recv_frame(&mut self.stream, None, None).await? {
+                Frame::Message(response) => return Ok(response),
+                Frame::Ping => async_io::send_pong(&mut self.stream).await?,
+                Frame::Pong => {}
+            }
+        }
+    }
 }
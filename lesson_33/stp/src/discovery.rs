@@ -0,0 +1,233 @@
+//! Discovery of STP servers on the local network over UDP.
+//!
+//! Client sends `STP-DISCOVER` datagram, usually to broadcast address, and every server
+//! listening for queries replies with `STP-SERVER <port> <name>`, where port is the one
+//! it accepts STP connections on. Server host is taken from the source of the reply.
+//!
+//! Async implementation lives here (`tokio` feature),
+//! blocking one in [`crate::blocking::discovery`] (`blocking` feature).
+
+use std::fmt;
+use std::io::{self, ErrorKind};
+use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4};
+
+/// Port servers listen for discovery queries on, unless configured otherwise.
+pub const DEFAULT_PORT: u16 = 55332;
+
+pub(crate) const QUERY: &[u8] = b"STP-DISCOVER";
+const REPLY_PREFIX: &str = "STP-SERVER ";
+/// Longer datagrams are not discovery replies.
+pub(crate) const MAX_DATAGRAM: usize = 512;
+
+/// Server that replied to discovery query.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Announcement {
+    /// Address to connect to with STP client.
+    pub addr: SocketAddr,
+    pub name: String,
+}
+
+impl fmt::Display for Announcement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({})", self.name, self.addr)
+    }
+}
+
+/// Where to send queries by default: every host of the local network and this host,
+/// as loopback doesn't receive broadcasts.
+pub fn default_targets() -> [SocketAddr; 2] {
+    [
+        SocketAddrV4::new(Ipv4Addr::BROADCAST, DEFAULT_PORT).into(),
+        SocketAddrV4::new(Ipv4Addr::LOCALHOST, DEFAULT_PORT).into(),
+    ]
+}
+
+/// Reply announcing server, `name` is cut so that reply fits in [`MAX_DATAGRAM`].
+pub(crate) fn reply(stp_port: u16, name: &str) -> Vec<u8> {
+    let mut reply = format!("{}{} ", REPLY_PREFIX, stp_port);
+    let mut end = name.len().min(MAX_DATAGRAM - reply.len());
+    while !name.is_char_boundary(end) {
+        end -= 1;
+    }
+    reply.push_str(&name[..end]);
+    reply.into_bytes()
+}
+
+/// Whether responder may go on after failing to receive query.
+///
+/// These are reported for a single datagram, like ICMP unreachable caused by earlier reply,
+/// others mean the socket is unusable.
+pub(crate) fn is_transient(e: &io::Error) -> bool {
+    matches!(
+        e.kind(),
+        ErrorKind::ConnectionReset
+            | ErrorKind::ConnectionRefused
+            | ErrorKind::Interrupted
+            | ErrorKind::WouldBlock
+            | ErrorKind::TimedOut
+    )
+}
+
+fn parse_reply(datagram: &[u8], mut from: SocketAddr) -> Option<Announcement> {
+    let reply = std::str::from_utf8(datagram)
+        .ok()?
+        .strip_prefix(REPLY_PREFIX)?;
+    let (port, name) = reply.split_once(' ')?;
+    from.set_port(port.parse().ok()?);
+    Some(Announcement {
+        addr: from,
+        name: name.into(),
+    })
+}
+
+/// Adds server replied with `datagram` to `found`, unless it's there already.
+pub(crate) fn collect(found: &mut Vec<Announcement>, datagram: &[u8], from: SocketAddr) {
+    if let Some(server) = parse_reply(datagram, from) {
+        if !found.contains(&server) {
+            found.push(server);
+        }
+    }
+}
+
+/// Passes if query was sent to at least one of the targets.
+pub(crate) fn check_sent(sent: bool, error: Option<io::Error>) -> io::Result<()> {
+    match (sent, error) {
+        (true, _) => Ok(()),
+        (false, Some(e)) => Err(e),
+        (false, None) => Err(io::Error::new(
+            ErrorKind::InvalidInput,
+            "no discovery targets",
+        )),
+    }
+}
+
+#[cfg(feature = "tokio")]
+pub use self::async_discovery::{discover, Responder};
+
+#[cfg(feature = "tokio")]
+mod async_discovery {
+    use super::{check_sent, collect, is_transient, reply, Announcement, MAX_DATAGRAM, QUERY};
+    use std::io;
+    use std::net::{Ipv4Addr, SocketAddr};
+    use std::time::Duration;
+    use tokio::net::{ToSocketAddrs, UdpSocket};
+    use tokio::time::{self, Instant};
+
+    /// Sends discovery query to `targets` and collects replies for `wait`.
+    pub async fn discover(targets: &[SocketAddr], wait: Duration) -> io::Result<Vec<Announcement>> {
+        let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0)).await?;
+        socket.set_broadcast(true)?;
+
+        let (mut sent, mut error) = (false, None);
+        for target in targets {
+            match socket.send_to(QUERY, target).await {
+                Ok(_) => sent = true,
+                Err(e) => error = Some(e),
+            }
+        }
+        check_sent(sent, error)?;
+
+        let deadline = Instant::now() + wait;
+        let mut found = Vec::new();
+        let mut buf = [0; MAX_DATAGRAM];
+        while let Ok(received) = time::timeout_at(deadline, socket.recv_from(&mut buf)).await {
+            // Errors caused by unreachable targets don't prevent others from replying.
+            if let Ok((len, from)) = received {
+                collect(&mut found, &buf[..len], from);
+            }
+        }
+        Ok(found)
+    }
+
+    /// Replies to discovery queries on behalf of STP server.
+    pub struct Responder {
+        socket: UdpSocket,
+        reply: Vec<u8>,
+    }
+
+    impl Responder {
+        /// Listens for queries on `addr`, announcing server accepting connections on `stp_port`.
+        pub async fn bind<Addrs>(addrs: Addrs, stp_port: u16, name: &str) -> io::Result<Self>
+        where
+            Addrs: ToSocketAddrs,
+        {
+            Ok(Self {
+                socket: UdpSocket::bind(addrs).await?,
+                reply: reply(stp_port, name),
+            })
+        }
+
+        pub fn local_addr(&self) -> io::Result<SocketAddr> {
+            self.socket.local_addr()
+        }
+
+        /// Replies to queries until socket fails.
+        pub async fn serve(self) -> io::Result<()> {
+            let mut buf = [0; MAX_DATAGRAM];
+            loop {
+                let (len, from) = match self.socket.recv_from(&mut buf).await {
+                    Ok(received) => received,
+                    Err(e) if is_transient(&e) => continue,
+                    Err(e) => return Err(e),
+                };
+                if &buf[..len] == QUERY {
+                    // Client may be gone already, nothing to do about it.
+                    let _ = self.socket.send_to(&self.reply, from).await;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::discovery::{parse_reply, reply, Announcement, MAX_DATAGRAM};
+
+    #[test]
+    fn replies() {
+        let from = "192.168.1.5:40000".parse().unwrap();
+        let server = parse_reply(&reply(55331, "my home"), from).unwrap();
+        let expected = Announcement {
+            addr: "192.168.1.5:55331".parse().unwrap(),
+            name: "my home".into(),
+        };
+        assert_eq!(server, expected);
+        assert_eq!(server.to_string(), "my home (192.168.1.5:55331)");
+
+        assert_eq!(parse_reply(b"STP-DISCOVER", from), None);
+        assert_eq!(parse_reply(b"STP-SERVER home", from), None);
+    }
+
+    #[test]
+    fn long_name_is_cut() {
+        let from = "192.168.1.5:40000".parse().unwrap();
+        let name = "домик".repeat(100);
+        let datagram = reply(55331, &name);
+        assert!(datagram.len() <= MAX_DATAGRAM);
+        let server = parse_reply(&datagram, from).unwrap();
+        assert!(server.name.len() > 400 && name.starts_with(&server.name));
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn discover_over_loopback() {
+        use crate::discovery::{discover, Responder};
+        use std::time::Duration;
+
+        let responder = Responder::bind("127.0.0.1:0", 55331, "kitchen")
+            .await
+            .unwrap();
+        let addr = responder.local_addr().unwrap();
+        tokio::spawn(responder.serve());
+
+        // Same server queried twice is listed once.
+        let found = discover(&[addr, addr], Duration::from_millis(200))
+            .await
+            .unwrap();
+        let expected = Announcement {
+            addr: "127.0.0.1:55331".parse().unwrap(),
+            name: "kitchen".into(),
+        };
+        assert_eq!(found, [expected]);
+    }
+}
@@ -1,33 +1,116 @@
 use std::io;
 use thiserror::Error;
 
+/// Bind to socket error
+#[derive(Debug, Error)]
+pub enum BindError {
+    #[error("IO error: {0}")]
+    Io(#[from] io::Error),
+}
+
 pub type ConnectResult<T> = Result<T, ConnectError>;
 
-/// Connection error. Includes IO and handshake error.
+/// Connection error. Includes IO, timeout and handshake error.
 #[derive(Debug, Error)]
 pub enum ConnectError {
     #[error("Unexpected handshake response: {0}")]
     BadHandshake(String),
     #[error("IO error: {0}")]
     Io(#[from] io::Error),
+    #[error("handshake timed out")]
+    Timeout,
 }
 
 pub type SendResult = Result<(), SendError>;
 
-/// Send data error
+/// Send data error. Includes IO and timeout error.
 #[derive(Debug, Error)]
 pub enum SendError {
     #[error("IO error: {0}")]
     Io(#[from] io::Error),
+    #[error("send timed out")]
+    Timeout,
+    #[error("payload of {0} bytes exceeds frame limit")]
+    TooLarge(usize),
 }
 
-pub type RecvResult = Result<String, RecvError>;
+pub type RecvResult<T = String> = Result<T, RecvError>;
 
-/// Send data error. Includes IO and encoding error.
+/// Send data error. Includes IO, timeout and encoding error.
 #[derive(Debug, Error)]
 pub enum RecvError {
     #[error("IO error: {0}")]
     Io(#[from] io::Error),
     #[error("bad encoding")]
     BadEncoding,
+    #[error("receive timed out")]
+    Timeout,
+    #[error("unknown frame kind: {0}")]
+    UnknownFrame(u8),
+    #[error("frame payload of {0} bytes exceeds limit")]
+    TooLarge(usize),
+    #[error("expected text, received binary message")]
+    UnexpectedBinary,
+    #[error("expected pong, received message")]
+    UnexpectedMessage,
+}
+
+impl From<SendError> for RecvError {
+    fn from(e: SendError) -> Self {
+        match e {
+            SendError::Io(e) => Self::Io(e),
+            SendError::Timeout => Self::Timeout,
+            SendError::TooLarge(len) => Self::TooLarge(len),
+        }
+    }
+}
+
+pub type RequestResult<T = String> = Result<T, RequestError>;
+
+/// Error for request sending. It consists from two steps: sending and receiving data.
+///
+/// `SendError` caused by send data error.
+/// `RecvError` caused by receive data error.
+/// `Timeout` caused by server not responding in time.
+/// `Broken` caused by earlier request that failed midway.
+#[derive(Debug, Error)]
+pub enum RequestError {
+    #[error(transparent)]
+    Send(#[from] SendError),
+    #[error(transparent)]
+    Recv(#[from] RecvError),
+    #[error("request timed out")]
+    Timeout,
+    #[error("connection is out of sync after failed request")]
+    Broken,
+}
+
+#[cfg(feature = "tokio")]
+mod elapsed {
+    use super::{ConnectError, RecvError, RequestError, SendError};
+    use tokio::time::error::Elapsed;
+
+    impl From<Elapsed> for ConnectError {
+        fn from(_: Elapsed) -> Self {
+            Self::Timeout
+        }
+    }
+
+    impl From<Elapsed> for SendError {
+        fn from(_: Elapsed) -> Self {
+            Self::Timeout
+        }
+    }
+
+    impl From<Elapsed> for RecvError {
+        fn from(_: Elapsed) -> Self {
+            Self::Timeout
+        }
+    }
+
+    impl From<Elapsed> for RequestError {
+        fn from(_: Elapsed) -> Self {
+            Self::Timeout
+        }
+    }
 }
@@ -0,0 +1,99 @@
+//! Wire format shared by blocking and async implementations.
+//!
+//! Every frame starts with 5-byte header: frame kind byte and big-endian length
+//! of payload that follows it. Control frames (ping, pong) have empty payload.
+
+use crate::error::{ConnectError, ConnectResult, RecvError, SendError};
+use crate::Message;
+
+pub(crate) const CLIENT_HANDSHAKE: [u8; 4] = *b"clnt";
+pub(crate) const SERVER_HANDSHAKE: [u8; 4] = *b"serv";
+
+pub(crate) const HEADER_LEN: usize = 5;
+
+/// Largest payload accepted from the other side, checked before memory for it is allocated.
+///
+/// Larger payloads are refused on sending as well.
+pub const MAX_PAYLOAD_LEN: usize = 16 * 1024 * 1024;
+
+/// Frame kind byte values.
+#[derive(Clone, Copy)]
+pub(crate) enum Kind {
+    Text = 0,
+    Binary = 1,
+    Ping = 2,
+    Pong = 3,
+}
+
+/// Single frame received from the other side of connection.
+pub(crate) enum Frame {
+    Message(Message),
+    Ping,
+    Pong,
+}
+
+/// Parsed frame header.
+pub(crate) struct Header {
+    pub kind: Kind,
+    pub len: usize,
+}
+
+impl Header {
+    pub fn encode(kind: Kind, payload: &[u8]) -> Result<[u8; HEADER_LEN], SendError> {
+        let len = match u32::try_from(payload.len()) {
+            Ok(len) if payload.len() <= MAX_PAYLOAD_LEN => len,
+            _ => return Err(SendError::TooLarge(payload.len())),
+        };
+        let mut header = [0; HEADER_LEN];
+        header[0] = kind as u8;
+        header[1..].copy_from_slice(&len.to_be_bytes());
+        Ok(header)
+    }
+
+    pub fn parse(buf: [u8; HEADER_LEN]) -> Result<Self, RecvError> {
+        let kind = match buf[0] {
+            0 => Kind::Text,
+            1 => Kind::Binary,
+            2 => Kind::Ping,
+            3 => Kind::Pong,
+            unknown => return Err(RecvError::UnknownFrame(unknown)),
+        };
+        let len = u32::from_be_bytes([buf[1], buf[2], buf[3], buf[4]]) as usize;
+        if len > MAX_PAYLOAD_LEN {
+            return Err(RecvError::TooLarge(len));
+        }
+        Ok(Self { kind, len })
+    }
+}
+
+pub(crate) fn message_kind(message: &Message) -> Kind {
+    match message {
+        Message::Text(_) => Kind::Text,
+        Message::Binary(_) => Kind::Binary,
+    }
+}
+
+pub(crate) fn decode(kind: Kind, payload: Vec<u8>) -> Result<Frame, RecvError> {
+    let frame = match kind {
+        Kind::Text => {
+            let text = String::from_utf8(payload).map_err(|_| RecvError::BadEncoding)?;
+            Frame::Message(Message::Text(text))
+        }
+        Kind::Binary => Frame::Message(Message::Binary(payload)),
+        Kind::Ping => Frame::Ping,
+        Kind::Pong => Frame::Pong,
+    };
+    Ok(frame)
+}
+
+pub(crate) fn into_text(message: Message) -> Result<String, RecvError> {
+    message.into_text().ok_or(RecvError::UnexpectedBinary)
+}
+
+pub(crate) fn check_handshake(received: [u8; 4], expected: [u8; 4]) -> ConnectResult<()> {
+    if received != expected {
+        let msg = format!("received: {:?}", received);
+        return Err(ConnectError::BadHandshake(msg));
+    }
+    Ok(())
+}
@@ -1,62 +1,25 @@
-use crate::error::{RecvError, RecvResult, SendResult};
-use std::io;
-use tokio::net::TcpStream;
-
-pub mod client;
+//! Simple text protocol (STP).
+//!
+//! Despite the name, besides UTF-8 text it can carry raw binary payloads, see [`Message`].
+//!
+//! Async implementation over tokio lives in [`client`] and [`server`] modules (`tokio` feature).
+//! Blocking implementation over `std::net` lives in [`blocking`] module (`blocking` feature).
+//!
+//! Servers on the local network can be found with [`discovery`].
+
+pub mod discovery;
 pub mod error;
+mod frame;
+mod message;
+
+#[cfg(feature = "tokio")]
+mod async_io;
+#[cfg(feature = "blocking")]
+pub mod blocking;
+#[cfg(feature = "tokio")]
+pub mod client;
+#[cfg(feature = "tokio")]
 pub mod server;
 
-async fn read_exact_async(s: &TcpStream, buf: &mut [u8]) -> io::Result<()> {
-    let mut red = 0;
-    while red < buf.len() {
-        s.readable().await?;
-        match s.try_read(&mut buf[red..]) {
-            Ok(0) => break,
-            Ok(n) => {
-                red += n;
-            }
-            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {}
-            Err(e) => return Err(e),
-        }
-    }
-
-    Ok(())
-}
-
-async fn write_all_async(stream: &TcpStream, buf: &[u8]) -> io::Result<()> {
-    let mut written = 0;
-
-    while written < buf.len() {
-        stream.writable().await?;
-
-        match stream.try_write(&buf[written..]) {
-            Ok(0) => break,
-            Ok(n) => {
-                written += n;
-            }
-            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {}
-            Err(e) => return Err(e),
-        }
-    }
-
-    Ok(())
-}
-
-async fn send_string_async<Data: AsRef<str>>(data: Data, stream: &TcpStream) -> SendResult {
-    let bytes = data.as_ref().as_bytes();
-    let len = bytes.len() as u32;
-    let len_bytes = len.to_be_bytes();
-    write_all_async(stream, &len_bytes).await?;
-    write_all_async(stream, bytes).await?;
-    Ok(())
-}
-
-async fn recv_string_async(stream: &TcpStream) -> RecvResult {
-    let mut buf = [0; 4];
-    read_exact_async(stream, &mut buf).await?;
-    let len = u32::from_be_bytes(buf);
-
-    let mut buf = vec![0; len as _];
-    read_exact_async(stream, &mut buf).await?;
-    String::from_utf8(buf).map_err(|_| RecvError::BadEncoding)
-}
+pub use frame::MAX_PAYLOAD_LEN;
+pub use message::Message;
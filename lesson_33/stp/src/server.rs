@@ -1,13 +1,24 @@
-use crate::error::{ConnectError, ConnectResult};
-use crate::{RecvResult, SendResult};
+use crate::async_io::{self, with_timeout};
+use crate::error::{ConnectResult, RecvResult, SendResult};
+use crate::frame::{self, Frame};
+use crate::Message;
+use std::future::Future;
 use std::io;
 use std::net::SocketAddr;
-use thiserror::Error;
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncWrite};
 use tokio::net::{TcpListener, TcpStream, ToSocketAddrs};
 
+pub use crate::error::BindError;
+
+/// Limits handshake of accepted connection if server has no request timeout.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
 /// Represent STP server, that can accept incoming connections.
 pub struct StpServer {
     tcp: TcpListener,
+    idle_timeout: Option<Duration>,
+    request_timeout: Option<Duration>,
 }
 
 impl StpServer {
@@ -17,56 +28,409 @@ impl StpServer {
         Addrs: ToSocketAddrs,
     {
         let tcp = TcpListener::bind(addrs).await?;
-        Ok(Self { tcp })
+        Ok(Self {
+            tcp,
+            idle_timeout: None,
+            request_timeout: None,
+        })
     }
 
-    /// Blocking iterator for incoming connections.
-    pub async fn accept(&self) -> ConnectResult<StpConnection> {
-        let (connection, _) = self.tcp.accept().await?;
-        Self::try_handshake(connection).await
+    /// Idle timeout for accepted connections. See [`StpConnection::set_idle_timeout`].
+    pub fn set_idle_timeout(&mut self, timeout: Option<Duration>) {
+        self.idle_timeout = timeout;
     }
 
-    async fn try_handshake(stream: TcpStream) -> ConnectResult<StpConnection> {
-        let mut buf = [0; 4];
-        super::read_exact_async(&stream, &mut buf).await?;
-        if &buf != b"clnt" {
-            let msg = format!("received: {:?}", buf);
-            return Err(ConnectError::BadHandshake(msg));
-        }
-        super::write_all_async(&stream, b"serv").await?;
-        Ok(StpConnection { stream })
+    /// Request timeout for accepted connections. See [`StpConnection::set_request_timeout`].
+    ///
+    /// Also limits handshake duration, which is otherwise limited to 10 seconds.
+    pub fn set_request_timeout(&mut self, timeout: Option<Duration>) {
+        self.request_timeout = timeout;
+    }
+
+    /// Address server is bound to.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.tcp.local_addr()
+    }
+
+    /// Waits for the next client. Handshake is left to [`Incoming::handshake`],
+    /// so it can be done in separate task without holding up accepting others.
+    pub async fn accept(&self) -> io::Result<Incoming> {
+        let (stream, _) = self.tcp.accept().await?;
+        Ok(Incoming::new(
+            stream,
+            self.idle_timeout,
+            self.request_timeout,
+        ))
     }
 }
 
-pub type BindResult = Result<StpServer, BindError>;
+pub type BindResult<S = StpServer> = Result<S, BindError>;
 
-/// Bind to socket error
-#[derive(Debug, Error)]
-pub enum BindError {
-    #[error("IO error: {0}")]
-    Io(#[from] io::Error),
+/// Represent STP server listening on Unix socket.
+#[cfg(unix)]
+pub struct UnixStpServer {
+    unix: tokio::net::UnixListener,
+    idle_timeout: Option<Duration>,
+    request_timeout: Option<Duration>,
+}
+
+#[cfg(unix)]
+impl UnixStpServer {
+    /// Binds server to socket file at specified path.
+    pub fn bind<P>(path: P) -> BindResult<Self>
+    where
+        P: AsRef<std::path::Path>,
+    {
+        let unix = tokio::net::UnixListener::bind(path)?;
+        Ok(Self {
+            unix,
+            idle_timeout: None,
+            request_timeout: None,
+        })
+    }
+
+    /// Idle timeout for accepted connections. See [`StpConnection::set_idle_timeout`].
+    pub fn set_idle_timeout(&mut self, timeout: Option<Duration>) {
+        self.idle_timeout = timeout;
+    }
+
+    /// Request timeout for accepted connections. See [`StpConnection::set_request_timeout`].
+    ///
+    /// Also limits handshake duration, which is otherwise limited to 10 seconds.
+    pub fn set_request_timeout(&mut self, timeout: Option<Duration>) {
+        self.request_timeout = timeout;
+    }
+
+    /// Waits for the next client, see [`StpServer::accept`].
+    pub async fn accept(&self) -> io::Result<Incoming<tokio::net::UnixStream>> {
+        let (stream, _) = self.unix.accept().await?;
+        Ok(Incoming::new(
+            stream,
+            self.idle_timeout,
+            self.request_timeout,
+        ))
+    }
+}
+
+/// Client accepted by server that hasn't done handshake yet.
+pub struct Incoming<S = TcpStream> {
+    stream: S,
+    idle_timeout: Option<Duration>,
+    request_timeout: Option<Duration>,
+}
+
+impl<S> Incoming<S>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    fn new(stream: S, idle_timeout: Option<Duration>, request_timeout: Option<Duration>) -> Self {
+        Self {
+            stream,
+            idle_timeout,
+            request_timeout,
+        }
+    }
+
+    /// Performs server-side handshake, fails with [`ConnectError::Timeout`] if client
+    /// doesn't complete it within server's request timeout.
+    ///
+    /// [`ConnectError::Timeout`]: crate::error::ConnectError::Timeout
+    pub async fn handshake(self) -> ConnectResult<StpConnection<S>> {
+        let timeout = self.request_timeout.unwrap_or(HANDSHAKE_TIMEOUT);
+        let handshake = StpConnection::handshake(self.stream);
+        let mut connection = with_timeout(Some(timeout), handshake).await??;
+        connection.set_idle_timeout(self.idle_timeout);
+        connection.set_request_timeout(self.request_timeout);
+        Ok(connection)
+    }
+}
+
+impl Incoming {
+    /// Address of connected client
+    pub fn peer_addr(&self) -> io::Result<SocketAddr> {
+        self.stream.peer_addr()
+    }
 }
 
 /// Represent connection from client.
 ///
 /// Allows to receive requests and send responses.
-pub struct StpConnection {
-    stream: TcpStream,
+/// Pings from client are answered transparently while waiting for request.
+/// Works over any byte stream, TCP is used by default.
+pub struct StpConnection<S = TcpStream> {
+    stream: S,
+    idle_timeout: Option<Duration>,
+    request_timeout: Option<Duration>,
 }
 
-impl StpConnection {
+impl<S> StpConnection<S>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    /// Perform server-side handshake over already established stream.
+    pub async fn handshake(mut stream: S) -> ConnectResult<Self> {
+        let received = async_io::read_handshake(&mut stream).await?;
+        frame::check_handshake(received, frame::CLIENT_HANDSHAKE)?;
+        async_io::write_handshake(&mut stream, frame::SERVER_HANDSHAKE).await?;
+        Ok(Self {
+            stream,
+            idle_timeout: None,
+            request_timeout: None,
+        })
+    }
+
+    /// Max time to wait for the next request or ping from client.
+    ///
+    /// `None` means to wait forever.
+    pub fn set_idle_timeout(&mut self, timeout: Option<Duration>) {
+        self.idle_timeout = timeout;
+    }
+
+    /// Max time to receive the rest of started request or to send a response.
+    ///
+    /// `None` means to wait forever.
+    pub fn set_request_timeout(&mut self, timeout: Option<Duration>) {
+        self.request_timeout = timeout;
+    }
+
     /// Send response to client
-    pub async fn send_response<Resp: AsRef<str>>(&self, response: Resp) -> SendResult {
-        super::send_string_async(response, &self.stream).await
+    pub async fn send_response<Resp: AsRef<str>>(&mut self, response: Resp) -> SendResult {
+        let send = async_io::send_string(response, &mut self.stream);
+        with_timeout(self.request_timeout, send).await?
+    }
+
+    /// Send text or binary response to client
+    pub async fn send_message<M: Into<Message>>(&mut self, response: M) -> SendResult {
+        let response = response.into();
+        let send = async_io::send_message(&response, &mut self.stream);
+        with_timeout(self.request_timeout, send).await?
     }
 
     /// Receive requests from client
-    pub async fn recv_request(&self) -> RecvResult {
-        super::recv_string_async(&self.stream).await
+    ///
+    /// Fails with [`RecvError::UnexpectedBinary`](crate::error::RecvError::UnexpectedBinary)
+    /// if client sends binary message.
+    pub async fn recv_request(&mut self) -> RecvResult {
+        frame::into_text(self.recv_message().await?)
     }
 
+    /// Receive text or binary request from client
+    pub async fn recv_message(&mut self) -> RecvResult<Message> {
+        loop {
+            let frame =
+                async_io::recv_frame(&mut self.stream, self.idle_timeout, self.request_timeout);
+            let frame = frame.await?;
+            if let Some(request) = self.on_frame(frame).await? {
+                return Ok(request);
+            }
+        }
+    }
+
+    /// Like [`recv_message`](Self::recv_message), but gives up once `stop` completes
+    /// while waiting for the next request, in which case returns `None`.
+    ///
+    /// Request that started to arrive is always received completely,
+    /// so connection isn't left with half of it.
+    pub async fn recv_message_or<F: Future>(&mut self, stop: F) -> RecvResult<Option<Message>> {
+        tokio::pin!(stop);
+        loop {
+            let first = async_io::recv_first_byte(&mut self.stream);
+            let first = tokio::select! {
+                first = with_timeout(self.idle_timeout, first) => first??,
+                _ = &mut stop => return Ok(None),
+            };
+            let frame = async_io::recv_frame_rest(&mut self.stream, first, self.request_timeout);
+            let frame = frame.await?;
+            if let Some(request) = self.on_frame(frame).await? {
+                return Ok(Some(request));
+            }
+        }
+    }
+
+    /// Answers control frames, returns messages.
+    async fn on_frame(&mut self, frame: Frame) -> RecvResult<Option<Message>> {
+        match frame {
+            Frame::Message(request) => return Ok(Some(request)),
+            Frame::Ping => {
+                let pong = async_io::send_pong(&mut self.stream);
+                with_timeout(self.request_timeout, pong).await??;
+            }
+            Frame::Pong => {}
+        }
+        Ok(None)
+    }
+}
+
+impl StpConnection {
     /// Address of connected client
     pub async fn peer_addr(&self) -> io::Result<SocketAddr> {
         self.stream.peer_addr()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::client::StpClient;
+    use crate::error::{ConnectError, RecvError, RequestError, SendError};
+    use crate::server::{StpConnection, StpServer};
+    use crate::{Message, MAX_PAYLOAD_LEN};
+    use std::time::Duration;
+    use tokio::io::{self, AsyncWriteExt};
+
+    #[tokio::test]
+    async fn ping_keeps_connection_alive() {
+        let mut server = StpServer::bind("127.0.0.1:0").await.unwrap();
+        server.set_idle_timeout(Some(Duration::from_millis(200)));
+        let addr = server.local_addr().unwrap();
+
+        let client = tokio::spawn(async move {
+            let mut client = StpClient::connect(addr).await.unwrap();
+            for _ in 0..3 {
+                tokio::time::sleep(Duration::from_millis(100)).await;
+                client.ping().await.unwrap();
+            }
+            client.send_request("hello").await.unwrap()
+        });
+
+        let incoming = server.accept().await.unwrap();
+        let mut connection = incoming.handshake().await.unwrap();
+        assert_eq!(connection.recv_request().await.unwrap(), "hello");
+        connection.send_response("world").await.unwrap();
+        assert_eq!(client.await.unwrap(), "world");
+    }
+
+    #[tokio::test]
+    async fn idle_connection_times_out() {
+        let mut server = StpServer::bind("127.0.0.1:0").await.unwrap();
+        server.set_idle_timeout(Some(Duration::from_millis(50)));
+        let addr = server.local_addr().unwrap();
+
+        let client = tokio::spawn(StpClient::connect(addr));
+        let mut connection = server.accept().await.unwrap().handshake().await.unwrap();
+        let _client = client.await.unwrap().unwrap();
+        let err = connection.recv_request().await.unwrap_err();
+        assert!(matches!(err, RecvError::Timeout));
+    }
+
+    #[tokio::test]
+    async fn handshake_times_out() {
+        // Connection is queued by OS, but handshake is never answered.
+        let silent = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = silent.local_addr().unwrap();
+        let connect = StpClient::connect_timeout(addr, Duration::from_millis(50));
+        assert!(matches!(connect.await, Err(ConnectError::Timeout)));
+    }
+
+    #[tokio::test]
+    async fn silent_client_doesnt_block_accept() {
+        let mut server = StpServer::bind("127.0.0.1:0").await.unwrap();
+        server.set_request_timeout(Some(Duration::from_millis(50)));
+        let addr = server.local_addr().unwrap();
+
+        // Never sends handshake.
+        let _silent = tokio::net::TcpStream::connect(addr).await.unwrap();
+        let silent = server.accept().await.unwrap();
+        let client = tokio::spawn(StpClient::connect(addr));
+        let incoming = server.accept().await.unwrap();
+        incoming.handshake().await.unwrap();
+        client.await.unwrap().unwrap();
+
+        let err = silent.handshake().await.err().unwrap();
+        assert!(matches!(err, ConnectError::Timeout));
+    }
+
+    #[tokio::test]
+    async fn in_memory_transport() {
+        let (client, server) = io::duplex(64);
+        let (client, connection) = tokio::join!(
+            StpClient::handshake(client),
+            StpConnection::handshake(server)
+        );
+        let (mut client, mut connection) = (client.unwrap(), connection.unwrap());
+
+        let serve = async {
+            let request = connection.recv_request().await.unwrap();
+            connection
+                .send_response(request.to_uppercase())
+                .await
+                .unwrap();
+        };
+        let (response, _) = tokio::join!(client.send_request("hello"), serve);
+        assert_eq!(response.unwrap(), "HELLO");
+    }
+
+    #[tokio::test]
+    async fn binary_messages() {
+        let (client, server) = io::duplex(64);
+        let (client, connection) = tokio::join!(
+            StpClient::handshake(client),
+            StpConnection::handshake(server)
+        );
+        let (mut client, mut connection) = (client.unwrap(), connection.unwrap());
+
+        let blob: Vec<u8> = (0..=255).collect();
+        let expected = blob.clone();
+        let serve = async move {
+            let request = connection.recv_message().await.unwrap();
+            assert_eq!(request, Message::Binary(expected));
+            let mut reversed = request.as_bytes().to_vec();
+            reversed.reverse();
+            connection.send_message(reversed).await.unwrap();
+
+            let err = connection.recv_request().await.unwrap_err();
+            assert!(matches!(err, RecvError::UnexpectedBinary));
+        };
+        let exchange = async {
+            let response = client.send_message(blob.as_slice()).await.unwrap();
+            let mut expected = blob.clone();
+            expected.reverse();
+            assert_eq!(response, Message::Binary(expected));
+            client.send_message(vec![0xff, 0xfe]).await
+        };
+        let (_, response) = tokio::join!(serve, exchange);
+        assert!(response.is_err());
+    }
+
+    #[tokio::test]
+    async fn timed_out_request_breaks_connection() {
+        let (client, server) = io::duplex(64);
+        let (client, connection) = tokio::join!(
+            StpClient::handshake(client),
+            StpConnection::handshake(server)
+        );
+        let (mut client, mut connection) = (client.unwrap(), connection.unwrap());
+        client.set_request_timeout(Some(Duration::from_millis(50)));
+
+        let err = client.send_request("slow").await.unwrap_err();
+        assert!(matches!(err, RequestError::Timeout));
+        assert!(client.is_broken());
+
+        // Late response must not be taken for response to the next request.
+        assert_eq!(connection.recv_request().await.unwrap(), "slow");
+        connection.send_response("late").await.unwrap();
+        let err = client.send_request("next").await.unwrap_err();
+        assert!(matches!(err, RequestError::Broken));
+        assert!(matches!(client.ping().await, Err(RequestError::Broken)));
+    }
+
+    #[tokio::test]
+    async fn oversized_frame_is_rejected() {
+        let (mut client, server) = io::duplex(64);
+        client.write_all(b"clnt").await.unwrap();
+        let mut connection = StpConnection::handshake(server).await.unwrap();
+
+        let len = (MAX_PAYLOAD_LEN as u32 + 1).to_be_bytes();
+        client
+            .write_all(&[0, len[0], len[1], len[2], len[3]])
+            .await
+            .unwrap();
+        let err = connection.recv_request().await.unwrap_err();
+        assert!(matches!(err, RecvError::TooLarge(len) if len == MAX_PAYLOAD_LEN + 1));
+
+        let err = connection
+            .send_message(vec![0; MAX_PAYLOAD_LEN + 1])
+            .await
+            .unwrap_err();
+        assert!(matches!(err, SendError::TooLarge(len) if len == MAX_PAYLOAD_LEN + 1));
+    }
+}